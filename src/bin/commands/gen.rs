@@ -0,0 +1,75 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use failure::Error;
+use std::str::FromStr;
+
+use sac::blob;
+use sac::field::Fieldname;
+use sac::gen::Generator;
+use sac::kind::Kind;
+use sac::rsf::{Command, Entry, Scope};
+use sac::schema::Cardinality;
+use sac::value::hash::{Alg, Hash};
+use sac::value::timestamp::Timestamp;
+use sac::value::{Parse, Value};
+
+/// Generates `count` blobs for the schema, one per line either as JSON or as
+/// RSF items and entries.
+///
+/// Entries are keyed by the value of the `key` field or by their position
+/// when there is no key field, and share a timestamp drawn from the seed.
+pub fn run(
+    schema_path: &str,
+    count: usize,
+    seed: u64,
+    rsf: bool,
+    key: Option<&str>,
+    invalid: u8,
+) -> Result<Vec<String>, Error> {
+    let schema = super::schema::load(schema_path)?;
+    let key = match key {
+        Some(name) => {
+            let name = Fieldname::from_str(name)?;
+            match schema.get(&name) {
+                Some(field) if !field.nullable && field.cardinality == Cardinality::One => Some(name),
+                Some(_) => bail!("The key field {} must have cardinality 1 and not be nullable", name.to_string()),
+                None => bail!("The key field {} is not in the schema", name.to_string()),
+            }
+        }
+        None => None,
+    };
+
+    let mut generator = Generator::new(seed);
+    let timestamp = Timestamp::parse(&generator.value(&Kind::Timestamp)?.to_string())?;
+    let mut lines = Vec::new();
+
+    for i in 1..=count {
+        let blob = generator.blob(&schema, invalid)?;
+
+        if !rsf {
+            lines.push(blob::to_json(&blob)?);
+            continue;
+        }
+
+        let key = match key.as_ref().and_then(|name| blob.get(name)) {
+            Some(Value::Untyped(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => i.to_string(),
+        };
+        let entry = Entry {
+            scope: Scope::User,
+            key,
+            timestamp: timestamp.clone(),
+            hashes: vec![Hash::new(Alg::Sha2256, blob.hash())],
+        };
+
+        lines.push(Command::AddItem(blob).to_string());
+        lines.push(Command::AppendEntry(entry).to_string());
+    }
+
+    Ok(lines)
+}
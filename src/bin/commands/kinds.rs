@@ -0,0 +1,51 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use failure::Error;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs;
+
+use sac::catalogue::CATALOGUE;
+use sac::value::custom;
+
+/// Describes every built-in datatype, either as a JSON array or as text.
+pub fn list(json: bool) -> Result<String, Error> {
+    if json {
+        return Ok(serde_json::to_string(CATALOGUE)?);
+    }
+
+    let entries: Vec<String> = CATALOGUE
+        .iter()
+        .map(|datatype| {
+            format!(
+                "{}\n    {}\n    Examples: {}\n    CSV: {}\n    JSON: {}\n    Grammar: {}",
+                datatype.name,
+                datatype.description,
+                datatype.examples.join(", "),
+                datatype.csv,
+                datatype.json,
+                datatype.grammar
+            )
+        })
+        .collect();
+
+    Ok(entries.join("\n\n"))
+}
+
+/// Registers the datatypes defined in a JSON object of datatype names to
+/// regular expressions such as `{"postcode": "[A-Z]{1,2}[0-9][A-Z0-9]? [0-9][A-Z]{2}"}`.
+pub fn register(path: &str) -> Result<(), Error> {
+    let raw = fs::read_to_string(path).map_err(|e| format_err!("{}: {}", path, e))?;
+    let patterns: BTreeMap<String, String> =
+        serde_json::from_str(&raw).map_err(|e| format_err!("{}: {}", path, e))?;
+
+    for (name, pattern) in patterns {
+        custom::register_pattern(&name, &pattern)?;
+    }
+
+    Ok(())
+}
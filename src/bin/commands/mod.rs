@@ -4,13 +4,34 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+pub mod gen;
+pub mod kinds;
+pub mod record;
+pub mod register;
+pub mod schema;
 pub mod value;
 
+use sac::field::Fieldname;
+use sac::schema::Schema;
+use sac::value::lenient::Fix;
 use sac::{blob, digest};
 use failure::Error;
 
-pub fn item_canon(raw: &str) -> Result<String, Error> {
-    blob::from_json(raw).and_then(|blob| blob::to_json(&blob))
+/// Canonicalises the blob. With a schema, the values of its fields are
+/// repaired leniently and the fixes applied are returned as well.
+pub fn item_canon(
+    raw: &str,
+    normalise_text: &[Fieldname],
+    lenient: Option<&Schema>,
+) -> Result<(String, Vec<(Fieldname, Fix)>), Error> {
+    let mut blob = blob::from_json(raw)?;
+    let fixes = match lenient {
+        Some(schema) => blob.repair(schema)?,
+        None => vec![],
+    };
+    blob.normalise_text(normalise_text)?;
+
+    Ok((blob::to_json(&blob)?, fixes))
 }
 
 pub fn item_hash(raw: &str, force_flag: bool) -> Result<String, Error> {
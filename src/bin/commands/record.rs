@@ -0,0 +1,92 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use failure::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::str::FromStr;
+
+use sac::blob::{self, Blob};
+use sac::field::Fieldname;
+use sac::spatial::SpatialIndex;
+use sac::value::point::Point;
+use sac::value::polygon::Polygon;
+use sac::value::Parse;
+
+/// A spatial query over a set of records.
+pub enum Query {
+    /// Records with a point within the given radius in metres.
+    Near(Point, f64),
+    /// Records with a polygon containing the given point.
+    Containing(Point),
+    /// Records with a point inside the given polygon.
+    Within(Polygon),
+}
+
+/// Reads a set of blobs as newline-delimited JSON from the given path or
+/// from stdin when the path is `-`.
+pub fn read_blobs(path: &str) -> Result<Vec<Blob>, Error> {
+    let reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+
+    let mut blobs = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            blobs.push(blob::from_json(&line)?);
+        }
+    }
+
+    Ok(blobs)
+}
+
+/// Parses a distance such as `5km`, `300m` or `300` (metres) into metres.
+pub fn parse_distance(s: &str) -> Result<f64, Error> {
+    let (number, factor) = if let Some(km) = s.strip_suffix("km") {
+        (km, 1000.0)
+    } else if let Some(m) = s.strip_suffix('m') {
+        (m, 1.0)
+    } else {
+        (s, 1.0)
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n >= 0.0 => Ok(n * factor),
+        _ => bail!("Invalid distance {}. Valid examples: 5km, 300m", s),
+    }
+}
+
+pub fn query(raw_point: Option<&str>, radius: Option<&str>, within: Option<&str>) -> Result<Query, Error> {
+    match (raw_point, radius, within) {
+        (Some(p), Some(r), None) => {
+            let point = Point::parse(p)?;
+            Ok(Query::Near(point, parse_distance(r)?))
+        }
+        (Some(p), None, None) => Ok(Query::Containing(Point::parse(p)?)),
+        (None, None, Some(p)) => Ok(Query::Within(Polygon::parse(p)?)),
+        _ => bail!("Expected either --point with an optional --radius or --within"),
+    }
+}
+
+pub fn near(blobs: &[Blob], field: &str, query: &Query) -> Result<Vec<String>, Error> {
+    let field = Fieldname::from_str(field)?;
+    let index = SpatialIndex::new(blobs, &field);
+
+    let found: Vec<&Blob> = match *query {
+        Query::Near(ref point, radius) => index
+            .near(point, radius)?
+            .into_iter()
+            .map(|(blob, _)| blob)
+            .collect(),
+        Query::Containing(ref point) => index.containing(point)?,
+        Query::Within(ref polygon) => index.within(polygon)?,
+    };
+
+    found.into_iter().map(blob::to_json).collect()
+}
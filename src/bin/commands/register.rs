@@ -0,0 +1,121 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use failure::Error;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use sac::blob;
+use sac::register::{self, Register};
+use sac::rsf::{Command, Scope};
+use sac::store::{Access, Store, SyncPolicy};
+use sac::value::timestamp::Timestamp;
+use sac::value::Parse;
+
+/// Loads a set of RSF files keyed by register name. Registers without a
+/// `name` system entry are named after the file stem.
+pub fn load(paths: &[&str]) -> Result<BTreeMap<String, Register>, Error> {
+    let mut registers = BTreeMap::new();
+
+    for path in paths {
+        let raw = fs::read_to_string(path).map_err(|e| format_err!("{}: {}", path, e))?;
+        let register = Register::from_rsf(&raw).map_err(|e| format_err!("{}: {}", path, e))?;
+        let name = match register.name() {
+            Some(name) => name.to_owned(),
+            None => Path::new(path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_string()),
+        };
+
+        registers.insert(name, register);
+    }
+
+    Ok(registers)
+}
+
+/// Returns a line per dangling link.
+pub fn check_links(paths: &[&str]) -> Result<Vec<String>, Error> {
+    let registers = load(paths)?;
+    let dangling = register::check_links(&registers)?;
+
+    Ok(dangling
+        .iter()
+        .map(|d| {
+            format!(
+                "{} entry {} ({}) {}: {} — {}",
+                d.register,
+                d.entry,
+                d.key,
+                d.field.to_string(),
+                d.value,
+                d.reason
+            )
+        })
+        .collect())
+}
+
+/// Creates an empty register in the given directory.
+pub fn init(path: &str) -> Result<(), Error> {
+    Store::init(Path::new(path))?;
+
+    Ok(())
+}
+
+/// Opens a local register reporting on stderr any partially written entry
+/// found at the end of the log.
+fn open(path: &str, access: Access, timeout: Duration) -> Result<Store, Error> {
+    let store = Store::open_with(Path::new(path), access, timeout)?;
+
+    if let Some(recovery) = store.recovery() {
+        eprintln!("{}: {}", path, recovery);
+    }
+
+    Ok(store)
+}
+
+/// Appends an entry for the given JSON items and returns its entry number.
+/// The timestamp defaults to the current time.
+pub fn append(
+    path: &str,
+    scope: Scope,
+    key: &str,
+    timestamp: Option<&str>,
+    items: &[&str],
+    sync: SyncPolicy,
+    timeout: Duration,
+) -> Result<usize, Error> {
+    let timestamp = match timestamp {
+        Some(s) => Timestamp::parse(s).map_err(|e| format_err!("{} {}", s, e))?,
+        None => Timestamp::now(),
+    };
+    let blobs = items
+        .iter()
+        .map(|raw| blob::from_json(raw))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut store = open(path, Access::Write, timeout)?;
+    store.set_sync(sync);
+
+    Ok(store.append(scope, key, timestamp, blobs)?)
+}
+
+/// Returns the entries as RSF lines, preceded by the items they add when
+/// `items` is set so the output is a complete register.
+pub fn log(path: &str, items: bool, timeout: Duration) -> Result<Vec<String>, Error> {
+    let store = open(path, Access::Read, timeout)?;
+
+    if items {
+        return Ok(store.to_rsf()?.iter().map(|c| c.to_string()).collect());
+    }
+
+    Ok(store
+        .entries()
+        .iter()
+        .map(|entry| Command::AppendEntry(entry.clone()).to_string())
+        .collect())
+}
@@ -0,0 +1,27 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use failure::Error;
+use std::fs::{self, File};
+
+use sac::infer;
+use sac::schema::Schema;
+
+/// Loads a schema from a JSON file with an array of field definitions.
+pub fn load(path: &str) -> Result<Schema, Error> {
+    let raw = fs::read_to_string(path).map_err(|e| format_err!("{}: {}", path, e))?;
+
+    Schema::from_json(&raw).map_err(|e| format_err!("{}: {}", path, e))
+}
+
+/// Proposes a schema as a JSON array of field definitions from a sample of
+/// the rows of a CSV file.
+pub fn infer(path: &str, sample: usize) -> Result<String, Error> {
+    let file = File::open(path).map_err(|e| format_err!("{}: {}", path, e))?;
+    let schema = infer::schema(file, sample).map_err(|e| format_err!("{}: {}", path, e))?;
+
+    Ok(schema.to_json()?)
+}
@@ -4,16 +4,83 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
-use failure::Fail;
+use std::fs;
+
+use sac::infer;
 use sac::kind::Kind;
-use sac::value::Value;
-
-pub fn check(raw: &str, kind: Kind) -> Result<String, String> {
-    Value::parse(raw, kind).map(|x| x.to_string()).map_err(|e| {
-        if let Some(cause) = e.cause() {
-            cause.to_string()
-        } else {
-            e.to_string()
-        }
-    })
+use sac::register::Register;
+use sac::value::curie::{Curie, PrefixMap};
+use sac::value::diagnostic::{Diagnose, Diagnostic};
+use sac::value::lenient::{self, Repaired};
+use sac::value::text::{Text, TextError};
+use sac::value::url::Url;
+use sac::value::{Parse, Value};
+
+pub fn check(raw: &str, kind: Kind) -> Result<String, Diagnostic> {
+    Value::parse(raw, kind)
+        .map(|x| x.to_string())
+        .map_err(|e| e.diagnose(raw))
+}
+
+/// Parses the value leniently, returning the canonical value and the fixes
+/// applied.
+pub fn fix(raw: &str, kind: Kind) -> Result<Repaired, Diagnostic> {
+    lenient::parse(raw, kind).map_err(|e| e.diagnose(raw))
+}
+
+/// Returns the name of every kind the value parses as, from most to least
+/// specific.
+pub fn infer(raw: &str) -> Vec<String> {
+    infer::kinds(raw).iter().map(|kind| kind.name().to_owned()).collect()
+}
+
+pub fn render(raw: &str, format: &str) -> Result<String, String> {
+    let text = Text::parse(raw).map_err(|e| match e {
+        TextError::List(errors) => errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        e => e.to_string(),
+    })?;
+
+    match format {
+        "html" => Ok(text.to_html()),
+        "plain" => Ok(text.to_plain()),
+        _ => Err(format!("Unknown format {}", format)),
+    }
+}
+
+/// Reads a prefix map from a file. The format is chosen by the file
+/// extension: `.toml` for a TOML table, `.rsf` for a register of prefixes and
+/// JSON for anything else.
+pub fn prefix_map(path: &str) -> Result<PrefixMap, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+    if path.ends_with(".toml") {
+        PrefixMap::from_toml(&raw).map_err(|e| e.to_string())
+    } else if path.ends_with(".rsf") {
+        let register = Register::from_rsf(&raw).map_err(|e| e.to_string())?;
+        PrefixMap::from_register(&register).map_err(|e| e.to_string())
+    } else {
+        PrefixMap::from_json(&raw).map_err(|e| e.to_string())
+    }
+}
+
+pub fn expand(raw: &str, prefixes: &PrefixMap) -> Result<String, String> {
+    let curie = Curie::parse(raw).map_err(|e| e.to_string())?;
+
+    prefixes
+        .expand(&curie)
+        .map(|url| url.to_string())
+        .map_err(|e| e.to_string())
+}
+
+pub fn compact(raw: &str, prefixes: &PrefixMap) -> Result<String, String> {
+    let url = Url::parse(raw).map_err(|e| e.to_string())?;
+
+    prefixes
+        .compact(&url)
+        .map(|curie| curie.to_string())
+        .ok_or_else(|| format!("No prefix matches {}", url))
 }
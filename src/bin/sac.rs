@@ -10,16 +10,37 @@ extern crate env_logger;
 #[macro_use]
 extern crate failure;
 extern crate log;
+#[macro_use]
+extern crate serde_json;
 
 extern crate sac;
 mod commands;
+use std::env;
 use std::process;
+use std::time::Duration;
 
 use clap::{App, Arg, SubCommand};
 
+use sac::catalogue;
+use sac::field::Fieldname;
 use sac::kind::Kind;
+use sac::rsf::Scope;
+use sac::store::SyncPolicy;
+use sac::value::custom;
 
 fn main() {
+    // Datatypes defined in the file named by SAC_DATATYPES are accepted as
+    // types too.
+    if let Some(path) = env::var_os("SAC_DATATYPES") {
+        commands::kinds::register(&path.to_string_lossy()).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1)
+        });
+    }
+    let custom_types = custom::names();
+    let mut types = catalogue::names();
+    types.extend(custom_types.iter().map(|name| name.as_str()));
+
     let matches = App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
@@ -36,6 +57,27 @@ fn main() {
                                 .help("The blob as JSON")
                                 .required(true)
                                 .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("normalise-text")
+                                .help("Normalise the Markdown of the given fields")
+                                .long("normalise-text")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .use_delimiter(true),
+                        )
+                        .arg(
+                            Arg::with_name("lenient")
+                                .help("Repair well-known variants of the values of the fields in the schema")
+                                .long("lenient")
+                                .requires("schema"),
+                        )
+                        .arg(
+                            Arg::with_name("schema")
+                                .help("A JSON file with the field definitions")
+                                .long("schema")
+                                .takes_value(true),
                         ),
                 )
                 .subcommand(
@@ -54,6 +96,221 @@ fn main() {
                         ),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("gen")
+                .about("Generate random blobs for a schema")
+                .arg(
+                    Arg::with_name("schema")
+                        .help("A JSON file with the field definitions")
+                        .long("schema")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .help("The number of blobs to generate")
+                        .long("count")
+                        .takes_value(true)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("The seed of the generator. The same seed gives the same blobs")
+                        .long("seed")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("The output format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["ndjson", "rsf"])
+                        .default_value("ndjson"),
+                )
+                .arg(
+                    Arg::with_name("key")
+                        .help("The field that keys the RSF entries. Entries are numbered by default")
+                        .long("key")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("invalid")
+                        .help("The percentage of values to make deliberately invalid")
+                        .long("invalid")
+                        .takes_value(true)
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("kinds")
+                .about("Describe the built-in datatypes")
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the catalogue as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("record")
+                .about("Query sets of records")
+                .subcommand(
+                    SubCommand::with_name("near")
+                        .about("Find records by location")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Newline-delimited JSON blobs. Reads stdin by default")
+                                .default_value("-")
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("field")
+                                .help("The geometry field to query")
+                                .long("field")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("point")
+                                .help("The WKT point to search from")
+                                .long("point")
+                                .takes_value(true)
+                                .conflicts_with("within"),
+                        )
+                        .arg(
+                            Arg::with_name("radius")
+                                .help("The search radius (e.g. 5km, 300m)")
+                                .long("radius")
+                                .takes_value(true)
+                                .requires("point"),
+                        )
+                        .arg(
+                            Arg::with_name("within")
+                                .help("The WKT polygon the record points must be within")
+                                .long("within")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("register")
+                .about("Operate on registers")
+                .subcommand(
+                    SubCommand::with_name("init")
+                        .about("Create an empty register in a directory")
+                        .arg(
+                            Arg::with_name("path")
+                                .help("The register directory")
+                                .required(true)
+                                .index(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("append")
+                        .about("Append an entry to a register")
+                        .arg(
+                            Arg::with_name("path")
+                                .help("The register directory")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("items")
+                                .help("The items of the entry as JSON")
+                                .multiple(true)
+                                .index(2),
+                        )
+                        .arg(
+                            Arg::with_name("key")
+                                .help("The record key")
+                                .long("key")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("scope")
+                                .help("The entry scope")
+                                .long("scope")
+                                .takes_value(true)
+                                .possible_values(&["user", "system"])
+                                .default_value("user"),
+                        )
+                        .arg(
+                            Arg::with_name("timestamp")
+                                .help("The entry timestamp. Defaults to now")
+                                .long("timestamp")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("sync")
+                                .help("Whether to sync the entry to disk before returning")
+                                .long("sync")
+                                .takes_value(true)
+                                .possible_values(&["always", "never"])
+                                .default_value("always"),
+                        )
+                        .arg(
+                            Arg::with_name("timeout")
+                                .help("The seconds to wait for other processes to release the register")
+                                .long("timeout")
+                                .takes_value(true)
+                                .default_value("10"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("log")
+                        .about("Print the entries of a register as RSF")
+                        .arg(
+                            Arg::with_name("path")
+                                .help("The register directory")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("items")
+                                .help("Add the items before the entries that refer to them")
+                                .long("items"),
+                        )
+                        .arg(
+                            Arg::with_name("timeout")
+                                .help("The seconds to wait for other processes to release the register")
+                                .long("timeout")
+                                .takes_value(true)
+                                .default_value("10"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("check-links")
+                        .about("Check every link resolves to a live record")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The RSF files of the linked registers")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Operate on schemas")
+                .subcommand(
+                    SubCommand::with_name("infer")
+                        .about("Propose a schema from a CSV file")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The CSV file with a header of field names")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("sample")
+                                .help("The number of rows to infer from")
+                                .long("sample")
+                                .takes_value(true)
+                                .default_value("1000"),
+                        ),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("value")
                 .about("Operate on values")
@@ -73,23 +330,129 @@ fn main() {
                                 .short("t")
                                 .takes_value(true)
                                 .required(true)
-                                .possible_values(&[
-                                    "bool",
-                                    "curie",
-                                    "datetime",
-                                    "hash",
-                                    "inapplicable",
-                                    "integer",
-                                    "period",
-                                    "point",
-                                    "polygon",
-                                    "string",
-                                    "text",
-                                    "timestamp",
-                                    "unknown",
-                                    "untyped",
-                                    "url",
-                                ]),
+                                .possible_values(&types),
+                        )
+                        .arg(
+                            Arg::with_name("values")
+                                .help("The allowed values for the enum type")
+                                .long("values")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .use_delimiter(true)
+                                .required_if("type", "enum"),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .help("The output format")
+                                .long("output")
+                                .short("o")
+                                .takes_value(true)
+                                .default_value("text")
+                                .possible_values(&["text", "json"]),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("fix")
+                        .about("Repair a value to its canonical form")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The value to be repaired")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("type")
+                                .help("The type the value is expected to be")
+                                .long("type")
+                                .short("t")
+                                .takes_value(true)
+                                .required(true)
+                                .possible_values(&types),
+                        )
+                        .arg(
+                            Arg::with_name("values")
+                                .help("The allowed values for the enum type")
+                                .long("values")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .use_delimiter(true)
+                                .required_if("type", "enum"),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .help("The output format")
+                                .long("output")
+                                .short("o")
+                                .takes_value(true)
+                                .default_value("text")
+                                .possible_values(&["text", "json"]),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("infer")
+                        .about("List the types a value is valid for, most specific first")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The value to be inferred")
+                                .required(true)
+                                .index(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("render")
+                        .about("Render a text value")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The text to be rendered")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .help("The output format")
+                                .long("format")
+                                .short("f")
+                                .takes_value(true)
+                                .default_value("html")
+                                .possible_values(&["html", "plain"]),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("expand")
+                        .about("Expand a curie to a url")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The curie to be expanded")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("prefixes")
+                                .help("A JSON, TOML or RSF file mapping prefixes to base urls")
+                                .long("prefixes")
+                                .short("p")
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("compact")
+                        .about("Compact a url to a curie")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("The url to be compacted")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("prefixes")
+                                .help("A JSON, TOML or RSF file mapping prefixes to base urls")
+                                .long("prefixes")
+                                .short("p")
+                                .takes_value(true)
+                                .required(true),
                         ),
                 ),
         )
@@ -99,9 +462,29 @@ fn main() {
         ("blob", Some(cmd_matches)) => match cmd_matches.subcommand() {
             ("canon", Some(sub_matches)) => {
                 let raw = sub_matches.value_of("input").unwrap();
+                let normalise_text = if sub_matches.is_present("normalise-text") {
+                    values_t!(sub_matches, "normalise-text", Fieldname).unwrap_or_else(|e| e.exit())
+                } else {
+                    vec![]
+                };
 
-                match commands::item_canon(raw) {
-                    Ok(json) => println!("{}", json),
+                let schema = if sub_matches.is_present("lenient") {
+                    let path = sub_matches.value_of("schema").unwrap();
+                    Some(commands::schema::load(path).unwrap_or_else(|err| {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }))
+                } else {
+                    None
+                };
+
+                match commands::item_canon(raw, &normalise_text, schema.as_ref()) {
+                    Ok((json, fixes)) => {
+                        for (field, fix) in fixes {
+                            eprintln!("{}: {}", field.to_string(), fix);
+                        }
+                        println!("{}", json)
+                    }
                     Err(err) => {
                         eprintln!("{}", err);
                         process::exit(1)
@@ -123,13 +506,224 @@ fn main() {
 
             _ => unimplemented!(),
         },
+        ("gen", Some(sub_matches)) => {
+            let schema = sub_matches.value_of("schema").unwrap();
+            let count = value_t!(sub_matches, "count", usize).unwrap_or_else(|e| e.exit());
+            let seed = value_t!(sub_matches, "seed", u64).unwrap_or_else(|e| e.exit());
+            let invalid = value_t!(sub_matches, "invalid", u8).unwrap_or_else(|e| e.exit());
+            let rsf = sub_matches.value_of("format") == Some("rsf");
+
+            if invalid > 100 {
+                eprintln!("The invalid percentage must be between 0 and 100");
+                process::exit(1)
+            }
+
+            match commands::gen::run(schema, count, seed, rsf, sub_matches.value_of("key"), invalid) {
+                Ok(lines) => for line in lines {
+                    println!("{}", line)
+                },
+                Err(err) => {
+                    eprintln!("{}", err);
+                    process::exit(1)
+                }
+            }
+        }
+        ("kinds", Some(sub_matches)) => match commands::kinds::list(sub_matches.is_present("json")) {
+            Ok(out) => println!("{}", out),
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1)
+            }
+        },
+        ("record", Some(cmd_matches)) => match cmd_matches.subcommand() {
+            ("near", Some(sub_matches)) => {
+                let input = sub_matches.value_of("input").unwrap();
+                let field = sub_matches.value_of("field").unwrap();
+                let result = commands::record::query(
+                    sub_matches.value_of("point"),
+                    sub_matches.value_of("radius"),
+                    sub_matches.value_of("within"),
+                ).and_then(|query| {
+                    let blobs = commands::record::read_blobs(input)?;
+                    commands::record::near(&blobs, field, &query)
+                });
+
+                match result {
+                    Ok(lines) => for line in lines {
+                        println!("{}", line)
+                    },
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            _ => process::exit(127),
+        },
+        ("register", Some(cmd_matches)) => match cmd_matches.subcommand() {
+            ("init", Some(sub_matches)) => {
+                let path = sub_matches.value_of("path").unwrap();
+
+                if let Err(err) = commands::register::init(path) {
+                    eprintln!("{}", err);
+                    process::exit(1)
+                }
+            }
+            ("append", Some(sub_matches)) => {
+                let path = sub_matches.value_of("path").unwrap();
+                let key = sub_matches.value_of("key").unwrap();
+                let scope = match sub_matches.value_of("scope") {
+                    Some("system") => Scope::System,
+                    _ => Scope::User,
+                };
+                let sync = match sub_matches.value_of("sync") {
+                    Some("never") => SyncPolicy::Never,
+                    _ => SyncPolicy::Always,
+                };
+                let items: Vec<&str> = sub_matches.values_of("items").map(|v| v.collect()).unwrap_or_default();
+                let timestamp = sub_matches.value_of("timestamp");
+                let timeout = value_t!(sub_matches, "timeout", u64).unwrap_or_else(|e| e.exit());
+
+                match commands::register::append(path, scope, key, timestamp, &items, sync, Duration::from_secs(timeout)) {
+                    Ok(number) => println!("{}", number),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            ("log", Some(sub_matches)) => {
+                let path = sub_matches.value_of("path").unwrap();
+                let timeout = value_t!(sub_matches, "timeout", u64).unwrap_or_else(|e| e.exit());
+
+                match commands::register::log(path, sub_matches.is_present("items"), Duration::from_secs(timeout)) {
+                    Ok(lines) => for line in lines {
+                        println!("{}", line)
+                    },
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            ("check-links", Some(sub_matches)) => {
+                let paths: Vec<&str> = sub_matches.values_of("input").unwrap().collect();
+
+                match commands::register::check_links(&paths) {
+                    Ok(ref lines) if lines.is_empty() => (),
+                    Ok(lines) => {
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                        process::exit(1)
+                    }
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            _ => process::exit(127),
+        },
+        ("schema", Some(cmd_matches)) => match cmd_matches.subcommand() {
+            ("infer", Some(sub_matches)) => {
+                let input = sub_matches.value_of("input").unwrap();
+                let sample = value_t!(sub_matches, "sample", usize).unwrap_or_else(|e| e.exit());
+
+                match commands::schema::infer(input, sample) {
+                    Ok(json) => println!("{}", json),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            _ => process::exit(127),
+        },
         ("value", Some(value_matches)) => match value_matches.subcommand() {
             ("check", Some(sub_matches)) => {
                 let raw = sub_matches.value_of("input").unwrap();
-                let kind = value_t!(sub_matches, "type", Kind).unwrap();
+                let kind = match value_t!(sub_matches, "type", Kind).unwrap() {
+                    Kind::Enum(_) => Kind::Enum(values_t!(sub_matches, "values", String).unwrap()),
+                    kind => kind,
+                };
+
+                let json = sub_matches.value_of("output") == Some("json");
 
                 match commands::value::check(raw, kind.clone()) {
+                    Ok(ref v) if json => println!("{}", json!({ "value": v })),
                     Ok(v) => println!("The value {} is a valid {}", v, kind),
+                    Err(ref diagnostic) if json => {
+                        println!("{}", serde_json::to_string(diagnostic).unwrap());
+                        process::exit(1)
+                    }
+                    Err(diagnostic) => {
+                        eprint!("{}", diagnostic.render());
+                        process::exit(1)
+                    }
+                }
+            }
+            ("fix", Some(sub_matches)) => {
+                let raw = sub_matches.value_of("input").unwrap();
+                let kind = match value_t!(sub_matches, "type", Kind).unwrap() {
+                    Kind::Enum(_) => Kind::Enum(values_t!(sub_matches, "values", String).unwrap()),
+                    kind => kind,
+                };
+
+                let json = sub_matches.value_of("output") == Some("json");
+
+                match commands::value::fix(raw, kind) {
+                    Ok(ref repaired) if json => {
+                        println!("{}", json!({ "value": repaired.value.to_string(), "fixes": repaired.fixes }))
+                    }
+                    Ok(repaired) => {
+                        for fix in repaired.fixes {
+                            eprintln!("{}", fix);
+                        }
+                        println!("{}", repaired.value)
+                    }
+                    Err(ref diagnostic) if json => {
+                        println!("{}", serde_json::to_string(diagnostic).unwrap());
+                        process::exit(1)
+                    }
+                    Err(diagnostic) => {
+                        eprint!("{}", diagnostic.render());
+                        process::exit(1)
+                    }
+                }
+            }
+            ("infer", Some(sub_matches)) => {
+                let raw = sub_matches.value_of("input").unwrap();
+
+                for name in commands::value::infer(raw) {
+                    println!("{}", name);
+                }
+            }
+            ("render", Some(sub_matches)) => {
+                let raw = sub_matches.value_of("input").unwrap();
+                let format = sub_matches.value_of("format").unwrap();
+
+                match commands::value::render(raw, format) {
+                    Ok(v) => print!("{}", v),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1)
+                    }
+                }
+            }
+            (name @ "expand", Some(sub_matches)) | (name @ "compact", Some(sub_matches)) => {
+                let raw = sub_matches.value_of("input").unwrap();
+                let prefixes = sub_matches.value_of("prefixes").unwrap();
+                let result = commands::value::prefix_map(prefixes).and_then(|map| {
+                    if name == "expand" {
+                        commands::value::expand(raw, &map)
+                    } else {
+                        commands::value::compact(raw, &map)
+                    }
+                });
+
+                match result {
+                    Ok(v) => println!("{}", v),
                     Err(err) => {
                         eprintln!("{}", err);
                         process::exit(1)
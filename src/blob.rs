@@ -17,6 +17,9 @@ use digest;
 use serde::ser::{Serialize, SerializeMap, Serializer};
 
 use field::Fieldname;
+use schema::{Field, Schema};
+use value::lenient::{self, Fix};
+use value::text::{Policy, Text, TextError};
 use value::Value;
 
 type Nub = BTreeMap<Fieldname, Value>;
@@ -55,14 +58,19 @@ pub fn from_json(s: &str) -> Result<Blob, Error> {
 
 /// Represents a Blob resource.
 ///
+/// Blobs are equal when they have the same fields with equal values, so they
+/// can be deduplicated with a `HashSet` regardless of how the JSON was
+/// written.
+///
 /// # Examples
 ///
 /// ```
 /// let raw = r#"{"foo": "abc", "bar": "xyz"}"#;
 /// let blob = sac::blob::from_json(raw).unwrap();
 /// assert_eq!(blob.hash(), "5dd4fe3b0de91882dae86b223ca531b5c8f2335d9ee3fd0ab18dfdc2871d0c61");
+/// assert_eq!(blob, sac::blob::from_json(r#"{"bar":"xyz","foo":"abc"}"#).unwrap());
 /// ```
-#[derive(Debug, Deserialize, Default)]
+#[derive(Clone, Debug, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Blob(Nub);
 impl Blob {
     pub fn new() -> Self {
@@ -74,6 +82,81 @@ impl Blob {
         self.0.insert(k, v);
     }
 
+    pub fn get(&self, k: &Fieldname) -> Option<&Value> {
+        self.0.get(k)
+    }
+
+    /// Normalises the Markdown of the given fields so editorial no-ops don't
+    /// change the hash of the blob. Fields are parsed as `Text` with a
+    /// permissive policy; missing fields are ignored.
+    ///
+    /// ```
+    /// let mut a = sac::blob::from_json(r#"{"note": "__bold__"}"#).unwrap();
+    /// let b = sac::blob::from_json(r#"{"note": "**bold**"}"#).unwrap();
+    /// a.normalise_text(&["note".parse().unwrap()]).unwrap();
+    /// assert_eq!(a.hash(), b.hash());
+    /// ```
+    pub fn normalise_text(&mut self, fields: &[Fieldname]) -> Result<(), TextError> {
+        let policy = Policy::permissive();
+
+        for field in fields {
+            let normalised = match self.0.get(field) {
+                Some(Value::Text(t)) => t.normalise(),
+                Some(Value::Untyped(s)) | Some(Value::String(s)) => {
+                    Text::parse_with(s, &policy)?.normalise()
+                }
+                _ => continue,
+            };
+
+            self.0.insert(field.clone(), Value::Text(normalised));
+        }
+
+        Ok(())
+    }
+
+    /// Repairs the values of the fields in the schema with lenient parsing.
+    /// Repaired values stay strings in their canonical form; fields missing
+    /// from the schema and empty values of nullable fields are left alone.
+    /// Returns the fixes applied per field.
+    ///
+    /// ```
+    /// use sac::schema::Schema;
+    /// let schema = Schema::from_json(r#"[{"field": "start-date", "datatype": "datetime"}]"#).unwrap();
+    /// let mut blob = sac::blob::from_json(r#"{"start-date": "10/01/2018"}"#).unwrap();
+    /// blob.repair(&schema).unwrap();
+    /// assert_eq!(sac::blob::to_json(&blob).unwrap(), r#"{"start-date":"2018-01-10"}"#);
+    /// ```
+    pub fn repair(&mut self, schema: &Schema) -> Result<Vec<(Fieldname, Fix)>, Error> {
+        let mut fixes = Vec::new();
+
+        for (name, value) in &mut self.0 {
+            let field = match schema.get(name) {
+                Some(field) => field,
+                None => continue,
+            };
+            let repaired = match *value {
+                Value::Untyped(ref s) => repair(field, s, &mut fixes)?,
+                Value::List(ref values) => {
+                    let mut repaired = Vec::new();
+
+                    for value in values {
+                        repaired.push(match *value {
+                            Value::Untyped(ref s) => repair(field, s, &mut fixes)?,
+                            ref value => value.clone(),
+                        });
+                    }
+
+                    Value::List(repaired)
+                }
+                _ => continue,
+            };
+
+            *value = repaired;
+        }
+
+        Ok(fixes)
+    }
+
     pub fn nub(&self) -> Nub {
         self.0.clone()
     }
@@ -90,18 +173,6 @@ impl Blob {
     }
 }
 
-// TODO: https://doc.rust-lang.org/std/hash/trait.Hash.html
-// pub trait Hash {
-//     fn hash<H>(&self, state: &mut H)
-//     where
-//         H: Hasher;
-
-//     fn hash_slice<H>(data: &[Self], state: &mut H)
-//     where
-//         H: Hasher,
-//     { ... }
-// }
-
 impl FromStr for Blob {
     type Err = Error;
     fn from_str(s: &str) -> Result<Blob, Self::Err> {
@@ -155,6 +226,23 @@ impl Serialize for Blob {
 //     }
 // }
 
+fn repair(field: &Field, s: &str, fixes: &mut Vec<(Fieldname, Fix)>) -> Result<Value, Error> {
+    if s.is_empty() && field.nullable {
+        return Ok(Value::Untyped(String::new()));
+    }
+
+    let name = &field.name;
+    let repaired = lenient::parse(s, field.datatype.clone()).map_err(|e| format_err!("{}: {}", name.to_string(), e))?;
+
+    if repaired.fixes.is_empty() {
+        return Ok(Value::Untyped(s.to_owned()));
+    }
+
+    fixes.extend(repaired.fixes.into_iter().map(|fix| (name.clone(), fix)));
+
+    Ok(Value::Untyped(repaired.value.to_string()))
+}
+
 fn uppercase_hex(s: &str) -> String {
     lazy_static! {
         static ref RE: Regex = Regex::new(r#"\\u([a-f0-9]{4})"#).unwrap();
@@ -181,4 +269,16 @@ mod tests {
         assert_eq!(uppercase_hex("abc\\u001F"), "abc\\u001F".to_string());
         assert_eq!(uppercase_hex("abc\\ucafe"), "abc\\uCAFE".to_string());
     }
+
+    #[test]
+    fn repair_keeps_empty_nullable_values() {
+        let schema = Schema::from_json(
+            r#"[{"field": "end-date", "datatype": "datetime", "nullable": "true"}, {"field": "start-date", "datatype": "datetime"}]"#,
+        ).unwrap();
+        let mut nullable = from_json(r#"{"end-date": ""}"#).unwrap();
+        let mut required = from_json(r#"{"start-date": ""}"#).unwrap();
+
+        assert!(nullable.repair(&schema).unwrap().is_empty());
+        assert!(required.repair(&schema).is_err());
+    }
 }
@@ -0,0 +1,251 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! The catalogue of built-in datatypes.
+//!
+//! This is the single list of the datatypes `Kind::from_str` accepts. Each
+//! entry documents the datatype with canonical examples, how values are
+//! encoded in CSV and JSON and the grammar of the canonical form.
+//!
+//! ```
+//! use sac::catalogue;
+//! let datatype = catalogue::get("timestamp").unwrap();
+//! assert_eq!(datatype.examples, &["2018-06-07T08:09:10Z"]);
+//! ```
+
+use kind::Kind;
+
+/// A built-in datatype.
+#[derive(Debug, Serialize)]
+pub struct Datatype {
+    pub name: &'static str,
+    #[serde(skip)]
+    pub kind: Kind,
+    pub description: &'static str,
+    /// Values in canonical form.
+    pub examples: &'static [&'static str],
+    pub csv: &'static str,
+    pub json: &'static str,
+    pub grammar: &'static str,
+}
+
+/// The built-in datatypes in lexicographical order.
+pub const CATALOGUE: &[Datatype] = &[
+    Datatype {
+        name: "bool",
+        kind: Kind::Bool,
+        description: "Boolean.",
+        examples: &["true", "false"],
+        csv: "true or false",
+        json: "boolean",
+        grammar: "true | false",
+    },
+    Datatype {
+        name: "curie",
+        kind: Kind::Curie,
+        description: "Compact URI referencing a record, or a facet of records, by prefix.",
+        examples: &["country:GB", "example:type=bar"],
+        csv: "string",
+        json: "string",
+        grammar: "prefix \":\" reference; prefix = [a-z][a-z0-9-]+; reference = RFC 3987 irelative-ref",
+    },
+    Datatype {
+        name: "datetime",
+        kind: Kind::Datetime,
+        description: "UTC ISO 8601 date time in any accuracy from year to second.",
+        examples: &["2018", "2018-01", "2018-01-10", "2016-10-11T12:13:14Z"],
+        csv: "string",
+        json: "string",
+        grammar: "YYYY[-MM[-DD[Thh[:mm[:ss]]Z]]]",
+    },
+    Datatype {
+        name: "decimal",
+        kind: Kind::Decimal,
+        description: "Exact decimal number of arbitrary precision.",
+        examples: &["-12.5", "0.015"],
+        csv: "string",
+        json: "string, to keep its precision",
+        grammar: "0 | -?(0|[1-9][0-9]*)(\\.[0-9]*[1-9])?",
+    },
+    Datatype {
+        name: "enum",
+        kind: Kind::Enum(Vec::new()),
+        description: "String from a controlled vocabulary given by the field or by the keys of a register.",
+        examples: &["open", "closed"],
+        csv: "string",
+        json: "string",
+        grammar: "one of the allowed values",
+    },
+    Datatype {
+        name: "hash",
+        kind: Kind::Hash,
+        description: "Hash qualified with its algorithm.",
+        examples: &["sha-256:ecd26bd54edf231ecbfbe361c97e0f720068f562e26c32696e777b6ed494cf73"],
+        csv: "string",
+        json: "string",
+        grammar: "sha-256:[0-9a-f]+",
+    },
+    Datatype {
+        name: "inapplicable",
+        kind: Kind::Inapplicable,
+        description: "Value that does not apply to the record.",
+        examples: &["N/A"],
+        csv: "N/A",
+        json: "{\"type\": \"inapplicable\"}",
+        grammar: "N/A | NA",
+    },
+    Datatype {
+        name: "integer",
+        kind: Kind::Integer,
+        description: "Decimal integer of arbitrary size.",
+        examples: &["-12", "9223372036854775808"],
+        csv: "string",
+        json: "number when it fits in an i64, string otherwise",
+        grammar: "0 | -?[1-9][0-9]*",
+    },
+    Datatype {
+        name: "langstring",
+        kind: Kind::LangString,
+        description: "String in one or more languages keyed by BCP 47 tag.",
+        examples: &["{\"cy\":\"Cymru\",\"en\":\"Wales\"}"],
+        csv: "JSON object",
+        json: "object sorted by tag",
+        grammar: "JSON object of BCP 47 tags to strings",
+    },
+    Datatype {
+        name: "period",
+        kind: Kind::Period,
+        description: "ISO 8601 duration or interval.",
+        examples: &["P1Y2M", "PT10H", "2018-01-01/2018-12-31", "2018-01-01/P1Y"],
+        csv: "string",
+        json: "string",
+        grammar: "duration | datetime/datetime | datetime/duration | duration/datetime",
+    },
+    Datatype {
+        name: "point",
+        kind: Kind::Point,
+        description: "WKT point, optionally with an EWKT SRID.",
+        examples: &["POINT (10 25)", "SRID=4326;POINT (-0.1 51.5)"],
+        csv: "string",
+        json: "string",
+        grammar: "[SRID=n;](POINT (x y) | POINTZ (x y z))",
+    },
+    Datatype {
+        name: "polygon",
+        kind: Kind::Polygon,
+        description: "WKT polygon, optionally with an EWKT SRID.",
+        examples: &["POLYGON ((0 0, 1 0, 1 1, 0 0))"],
+        csv: "string",
+        json: "string",
+        grammar: "[SRID=n;](POLYGON | POLYGONZ) (ring[, ring]*); ring = (coordinates[, coordinates]*)",
+    },
+    Datatype {
+        name: "string",
+        kind: Kind::String,
+        description: "UTF-8 string.",
+        examples: &["foo"],
+        csv: "string",
+        json: "string",
+        grammar: "any UTF-8 string",
+    },
+    Datatype {
+        name: "text",
+        kind: Kind::Text,
+        description: "CommonMark restricted to paragraphs, headers, bold, italics, links and inline code.",
+        examples: &["foo *bar*"],
+        csv: "string",
+        json: "string",
+        grammar: "CommonMark without HTML",
+    },
+    Datatype {
+        name: "timestamp",
+        kind: Kind::Timestamp,
+        description: "RFC 3339 UTC timestamp.",
+        examples: &["2018-06-07T08:09:10Z"],
+        csv: "string",
+        json: "string",
+        grammar: "YYYY-MM-DDThh:mm:ssZ",
+    },
+    Datatype {
+        name: "unknown",
+        kind: Kind::Unknown,
+        description: "Applicable value that is missing.",
+        examples: &["null"],
+        csv: "empty cell",
+        json: "null",
+        grammar: "null",
+    },
+    Datatype {
+        name: "untyped",
+        kind: Kind::Untyped,
+        description: "Value without a datatype.",
+        examples: &["foo"],
+        csv: "string",
+        json: "string",
+        grammar: "any UTF-8 string",
+    },
+    Datatype {
+        name: "url",
+        kind: Kind::Url,
+        description: "Absolute http or https URL normalised as per RFC 3986.",
+        examples: &["https://example.org/foo"],
+        csv: "string",
+        json: "string",
+        grammar: "RFC 3986 absolute URI",
+    },
+];
+
+/// Finds a built-in datatype by name.
+pub fn get(name: &str) -> Option<&'static Datatype> {
+    CATALOGUE.iter().find(|datatype| datatype.name == name)
+}
+
+/// The names of the built-in datatypes.
+pub fn names() -> Vec<&'static str> {
+    CATALOGUE.iter().map(|datatype| datatype.name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use value::Value;
+
+    #[test]
+    fn examples_are_canonical() {
+        for datatype in CATALOGUE {
+            let kind = match datatype.kind {
+                Kind::Enum(_) => Kind::Enum(datatype.examples.iter().map(|s| s.to_string()).collect()),
+                ref kind => kind.clone(),
+            };
+
+            for example in datatype.examples {
+                let value = Value::parse(example, kind.clone());
+
+                assert_eq!(
+                    value.map(|v| v.to_string()).ok().as_deref(),
+                    Some(*example),
+                    "{} example {}",
+                    datatype.name,
+                    example
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn names_match_kinds() {
+        for datatype in CATALOGUE {
+            assert_eq!(datatype.kind.name(), datatype.name);
+            assert_eq!(Kind::from_str(datatype.name).unwrap(), datatype.kind);
+        }
+
+        assert_eq!(
+            format!("{:?}", Kind::from_str("list")),
+            r#"Err(Unknown("list"))"#
+        );
+    }
+}
@@ -15,6 +15,20 @@ pub fn to_hex(xs: &[u8]) -> String {
     xs.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
+/// CRC-32 (IEEE 802.3) checksum to detect torn or corrupted records.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & 0u32.wrapping_sub(crc & 1));
+        }
+    }
+
+    !crc
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -29,4 +43,10 @@ mod tests {
         assert_eq!(d.as_ref(), &bs[..]);
         assert_eq!(&to_hex(d.as_ref()), hash);
     }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
 }
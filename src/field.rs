@@ -21,7 +21,7 @@ pub enum FieldError {
 
 // The spec requires a fieldname to be [a-z-]. The constructor `from_str`
 // ensures this constraint is met.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub struct Fieldname(String);
 
 impl FromStr for Fieldname {
@@ -0,0 +1,440 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Deterministic generation of random values and blobs to use as test data.
+//!
+//! A generator is seeded so the same seed always produces the same sequence
+//! of values. Values are valid for their kind unless deliberately made
+//! invalid, and blobs follow the cardinality, nullability and numeric
+//! constraints of their schema.
+//!
+//! ```
+//! use sac::gen::Generator;
+//! use sac::kind::Kind;
+//! let mut a = Generator::new(42);
+//! let mut b = Generator::new(42);
+//! assert_eq!(a.value(&Kind::Point).unwrap(), b.value(&Kind::Point).unwrap());
+//! ```
+
+use serde_json;
+
+use blob::Blob;
+use kind::Kind;
+use schema::{Cardinality, Field, Schema};
+use value::Value;
+
+#[derive(Debug, Fail)]
+pub enum GenError {
+    #[fail(display = "Can't generate values of the {} datatype", _0)]
+    Unsupported(String),
+    #[fail(display = "Can't generate enum values without a list of allowed values")]
+    EmptyEnum,
+    #[fail(display = "Can't generate a value for {} within its constraints", _0)]
+    Unsatisfiable(String),
+}
+
+const WORDS: &[&str] = &[
+    "alder", "birch", "cedar", "elm", "fir", "hazel", "juniper", "larch", "maple", "oak", "pine",
+    "rowan", "spruce", "willow", "yew",
+];
+
+const PREFIXES: &[&str] = &["country", "example", "local-authority-eng", "territory"];
+
+/// The number of attempts to find a value within the field constraints.
+const ATTEMPTS: usize = 100;
+
+/// A seeded source of random values based on SplitMix64.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, n)`. `n` must be positive.
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+
+    /// A number in `[lo, hi]`.
+    fn between(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = (i128::from(hi) - i128::from(lo) + 1) as u128;
+        let offset = if span > u128::from(u64::MAX) {
+            self.next_u64()
+        } else {
+            self.below(span as u64)
+        };
+
+        (i128::from(lo) + i128::from(offset)) as i64
+    }
+
+    /// Whether an event with the given percentage of chance happens.
+    fn chance(&mut self, percent: u8) -> bool {
+        self.below(100) < u64::from(percent)
+    }
+
+    fn pick<'a, T>(&mut self, xs: &'a [T]) -> &'a T {
+        &xs[self.below(xs.len() as u64) as usize]
+    }
+
+    fn words(&mut self, n: u64) -> String {
+        let n = 1 + self.below(n);
+        let words: Vec<&str> = (0..n).map(|_| *self.pick(WORDS)).collect();
+
+        words.join(" ")
+    }
+
+    fn digits(&mut self, n: usize, radix: u32) -> String {
+        (0..n)
+            .map(|_| std::char::from_digit(self.below(u64::from(radix)) as u32, radix).unwrap())
+            .collect()
+    }
+
+    /// A coordinate in `[-bound, bound]` with four decimals.
+    fn coordinate(&mut self, bound: i64) -> String {
+        let n = self.between(-bound * 10_000, bound * 10_000);
+        let sign = if n < 0 { "-" } else { "" };
+
+        format!("{}{}.{:04}", sign, n.abs() / 10_000, n.abs() % 10_000)
+    }
+
+    /// A date with a random precision, from year to day.
+    fn date(&mut self, year: i64) -> String {
+        match self.below(3) {
+            0 => format!("{}", year),
+            1 => format!("{}-{:02}", year, self.between(1, 12)),
+            _ => format!(
+                "{}-{:02}-{:02}",
+                year,
+                self.between(1, 12),
+                self.between(1, 28)
+            ),
+        }
+    }
+
+    fn timestamp(&mut self) -> String {
+        format!(
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.between(1900, 2099),
+            self.between(1, 12),
+            self.between(1, 28),
+            self.between(0, 23),
+            self.between(0, 59),
+            self.between(0, 59)
+        )
+    }
+
+    fn duration(&mut self) -> String {
+        match self.below(3) {
+            0 => format!("P{}Y{}M", self.between(1, 10), self.between(1, 11)),
+            1 => format!("P{}D", self.between(1, 30)),
+            _ => format!("PT{}H", self.between(1, 23)),
+        }
+    }
+
+    /// A valid value of the given kind. Lists have between one and three
+    /// items.
+    pub fn value(&mut self, kind: &Kind) -> Result<Value, GenError> {
+        if let Kind::List(ref inner) = *kind {
+            let n = 1 + self.below(3);
+
+            return (0..n)
+                .map(|_| self.value(inner))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List);
+        }
+
+        let raw = self.raw(kind)?;
+
+        Ok(Value::parse(&raw, kind.clone()).expect("generated values are valid"))
+    }
+
+    fn raw(&mut self, kind: &Kind) -> Result<String, GenError> {
+        let raw = match *kind {
+            Kind::Bool => self.pick(&["true", "false"]).to_string(),
+            Kind::Curie => format!(
+                "{}:{}",
+                self.pick(PREFIXES),
+                self.digits(2, 36).to_uppercase()
+            ),
+            Kind::Custom(ref name) => return Err(GenError::Unsupported(name.clone())),
+            Kind::Datetime => match self.below(4) {
+                0 => self.timestamp(),
+                _ => {
+                    let year = self.between(1900, 2099);
+                    self.date(year)
+                }
+            },
+            Kind::Decimal => format!("{}.{}", self.between(-1000, 1000), self.digits(2, 10)),
+            Kind::Enum(ref values) if values.is_empty() => return Err(GenError::EmptyEnum),
+            Kind::Enum(ref values) => self.pick(values).clone(),
+            Kind::Hash => format!("sha-256:{}", self.digits(64, 16)),
+            Kind::Inapplicable => "N/A".to_owned(),
+            Kind::Integer => self.between(-1000, 1000).to_string(),
+            Kind::LangString => serde_json::json!({"cy": self.words(3), "en": self.words(3)}).to_string(),
+            Kind::List(_) => unreachable!("lists are generated item by item"),
+            Kind::Period => {
+                let year = self.between(1900, 2049);
+                match self.below(3) {
+                    0 => self.duration(),
+                    1 => {
+                        let start = self.date(year);
+                        let end = year + self.between(1, 50);
+                        let end = self.date(end);
+                        format!("{}/{}", start, end)
+                    }
+                    _ => format!("{}/{}", self.date(year), self.duration()),
+                }
+            }
+            Kind::Point => format!("POINT ({} {})", self.coordinate(180), self.coordinate(90)),
+            Kind::Polygon => {
+                let (x, y, size) = (self.between(-170, 169), self.between(-80, 79), self.between(1, 10));
+                format!(
+                    "POLYGON (({x} {y}, {x1} {y}, {x1} {y1}, {x} {y1}, {x} {y}))",
+                    x = x,
+                    y = y,
+                    x1 = x + size,
+                    y1 = y + size
+                )
+            }
+            Kind::String | Kind::Untyped => self.words(4),
+            Kind::Text => format!("{} *{}*", self.words(4), self.pick(WORDS)),
+            Kind::Timestamp => self.timestamp(),
+            Kind::Unknown => "null".to_owned(),
+            Kind::Url => format!("https://{}.example.org/{}", self.pick(WORDS), self.pick(WORDS)),
+        };
+
+        Ok(raw)
+    }
+
+    /// A deliberately invalid value of the given kind, or `None` when every
+    /// string is a valid value of the kind.
+    pub fn invalid(&mut self, kind: &Kind) -> Option<String> {
+        let candidates: Vec<String> = match *kind {
+            Kind::Bool => vec!["yes".into(), "True".into(), "1".into()],
+            Kind::Curie => vec!["Country:GB".into(), "country GB".into(), ":GB".into()],
+            Kind::Datetime => vec!["2018-13".into(), "2018-1-1".into(), "10/01/2018".into()],
+            Kind::Decimal => vec!["1,5".into(), "1.".into(), "1e3".into()],
+            Kind::Enum(ref values) => vec![format!("{}x", values.concat())],
+            Kind::Hash => vec!["sha-256:xyz".into(), "md5:ab".into(), "ab".into()],
+            Kind::Inapplicable => vec!["none".into(), "n/a".into()],
+            Kind::Integer => vec!["+1".into(), "007".into(), "1.5".into()],
+            Kind::LangString => vec![r#"{"en": 1}"#.into(), "Wales".into()],
+            Kind::List(ref inner) => return self.invalid(inner),
+            Kind::Period => vec!["P1X".into(), "1Y".into(), "P".into()],
+            Kind::Point => vec!["POINT (1)".into(), "point (1 2)".into(), "POINT (a b)".into()],
+            Kind::Polygon => vec!["POLYGON ((0 0, 1 1))".into(), "POLYGON (0 0)".into()],
+            Kind::Text => vec!["<b>foo</b>".into()],
+            Kind::Timestamp => vec![
+                "2018-01-10T09:30:00".into(),
+                "2018-01-10 09:30:00Z".into(),
+                "2018-01-10".into(),
+            ],
+            Kind::Unknown => vec!["nil".into(), "NULL".into()],
+            Kind::Url => vec!["ftp://example.org".into(), "example.org".into()],
+            Kind::Custom(_) | Kind::String | Kind::Untyped => vec![],
+        };
+        let candidates: Vec<String> = candidates
+            .into_iter()
+            .filter(|s| Value::parse(s, kind.clone()).is_err())
+            .collect();
+
+        if candidates.is_empty() {
+            None
+        } else {
+            Some(self.pick(&candidates).clone())
+        }
+    }
+
+    /// A valid value for the field. Fields with cardinality `n` get a list
+    /// and numbers stay within the `min`, `max` and `scale` constraints.
+    pub fn field(&mut self, field: &Field) -> Result<Value, GenError> {
+        match field.cardinality {
+            Cardinality::One => self.constrained(field),
+            Cardinality::Many => {
+                let n = 1 + self.below(3);
+
+                (0..n)
+                    .map(|_| self.constrained(field))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::List)
+            }
+        }
+    }
+
+    fn constrained(&mut self, field: &Field) -> Result<Value, GenError> {
+        let (lo, hi) = bounds(field);
+
+        for _ in 0..ATTEMPTS {
+            let raw = match field.datatype {
+                Kind::Integer => self.between(lo, hi).to_string(),
+                Kind::Decimal => {
+                    let scale = field.scale.unwrap_or_else(|| self.below(4) as usize);
+                    let n = self.between(lo, hi);
+
+                    if scale == 0 {
+                        n.to_string()
+                    } else {
+                        format!("{}.{}", n, self.digits(scale, 10))
+                    }
+                }
+                ref kind => return self.value(kind),
+            };
+
+            if let Ok(value) = field.parse(&raw) {
+                return Ok(value);
+            }
+        }
+
+        Err(GenError::Unsatisfiable(field.name.to_string()))
+    }
+
+    /// A blob for the schema. Nullable fields are sometimes left out and
+    /// roughly `invalid` percent of the values are deliberately invalid.
+    /// Values are untyped canonical strings as found in RSF items.
+    pub fn blob(&mut self, schema: &Schema, invalid: u8) -> Result<Blob, GenError> {
+        let mut blob = Blob::new();
+
+        for field in schema.fields() {
+            if field.nullable && self.chance(10) {
+                continue;
+            }
+
+            let broken = if invalid > 0 && self.chance(invalid) {
+                self.invalid(&field.datatype)
+            } else {
+                None
+            };
+            let value = match (broken, field.cardinality) {
+                (Some(s), Cardinality::One) => Value::Untyped(s),
+                (Some(s), Cardinality::Many) => Value::List(vec![Value::Untyped(s)]),
+                (None, _) => untyped(self.field(field)?),
+            };
+
+            blob.insert(field.name.clone(), value);
+        }
+
+        Ok(blob)
+    }
+}
+
+/// The integer range to draw numbers from. Numbers out of the field bounds
+/// are discarded afterwards.
+fn bounds(field: &Field) -> (i64, i64) {
+    let min = field.min.as_ref().and_then(|d| d.trunc().to_i64());
+    let max = field.max.as_ref().and_then(|d| d.trunc().to_i64());
+
+    match (min, max) {
+        (Some(lo), Some(hi)) => (lo, hi.max(lo)),
+        (Some(lo), None) => (lo, lo.saturating_add(1000)),
+        (None, Some(hi)) => (hi.saturating_sub(1000), hi),
+        (None, None) => (-1000, 1000),
+    }
+}
+
+/// Turns a value into the form found in blobs. Language strings and
+/// inapplicable values are JSON objects and stay typed.
+fn untyped(value: Value) -> Value {
+    match value {
+        Value::List(xs) => Value::List(xs.into_iter().map(untyped).collect()),
+        Value::LangString(_) | Value::Inapplicable | Value::Unknown => value,
+        value => Value::Untyped(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use catalogue::CATALOGUE;
+
+    fn kinds() -> Vec<Kind> {
+        CATALOGUE
+            .iter()
+            .map(|datatype| match datatype.kind {
+                Kind::Enum(_) => Kind::Enum(vec!["open".into(), "closed".into()]),
+                ref kind => kind.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_are_valid() {
+        let mut gen = Generator::new(7);
+
+        for kind in kinds() {
+            for _ in 0..200 {
+                let value = gen.value(&kind).unwrap();
+
+                assert!(
+                    Value::parse(&value.to_string(), kind.clone()).is_ok(),
+                    "{} {}",
+                    kind,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_invalid() {
+        let mut gen = Generator::new(7);
+
+        for kind in kinds() {
+            match gen.invalid(&kind) {
+                Some(s) => assert!(Value::parse(&s, kind.clone()).is_err(), "{} {}", kind, s),
+                None => assert!(kind == Kind::String || kind == Kind::Untyped, "{}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_blobs() {
+        let schema = Schema::from_json(
+            r#"[
+              {"field": "name", "datatype": "string"},
+              {"field": "rate", "datatype": "decimal", "min": "0", "max": "1.5", "scale": "2"},
+              {"field": "area", "datatype": "polygon", "cardinality": "n", "nullable": "true"}
+            ]"#,
+        ).unwrap();
+        let blobs = |seed| {
+            let mut gen = Generator::new(seed);
+            (0..50).map(|_| gen.blob(&schema, 0).unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(blobs(42), blobs(42));
+        assert_ne!(blobs(42), blobs(43));
+
+        for blob in blobs(42) {
+            let rate = blob.get(&"rate".parse().unwrap()).unwrap();
+            let field = schema.get(&"rate".parse().unwrap()).unwrap();
+
+            assert!(field.parse(&rate.to_string()).is_ok(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_constraints() {
+        let schema = Schema::from_json(r#"[{"field": "n", "datatype": "integer", "min": "0.2", "max": "0.8"}]"#).unwrap();
+        let actual = Generator::new(1).blob(&schema, 0);
+
+        assert_eq!(
+            format!("{:?}", actual),
+            r#"Err(Unsatisfiable("n"))"#
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Datatype inference for untyped values and CSV columns.
+
+use csv;
+use std::io::Read;
+use std::str::FromStr;
+
+use field::{FieldError, Fieldname};
+use kind::Kind;
+use schema::{Cardinality, Field, Schema};
+use value::text::Text;
+use value::{Parse, Value};
+
+#[derive(Debug, Fail)]
+pub enum InferError {
+    #[fail(display = "Invalid CSV. {}", _0)]
+    InvalidCsv(#[cause] csv::Error),
+    #[fail(display = "Invalid column name {}", name)]
+    InvalidColumn {
+        name: String,
+        #[cause]
+        cause: FieldError,
+    },
+}
+
+impl From<csv::Error> for InferError {
+    fn from(err: csv::Error) -> InferError {
+        InferError::InvalidCsv(err)
+    }
+}
+
+/// The separator between the values of a CSV cell with cardinality n.
+pub const SEPARATOR: char = ';';
+
+/// The inferable kinds from most to least specific. Enumerations and untyped
+/// values are never inferred.
+const KINDS: &[Kind] = &[
+    Kind::Unknown,
+    Kind::Inapplicable,
+    Kind::Bool,
+    Kind::Integer,
+    Kind::Decimal,
+    Kind::Timestamp,
+    Kind::Datetime,
+    Kind::Period,
+    Kind::Hash,
+    Kind::Point,
+    Kind::Polygon,
+    Kind::Url,
+    Kind::Curie,
+    Kind::LangString,
+    Kind::Text,
+    Kind::String,
+];
+
+/// Returns every kind the value parses as, from most to least specific.
+///
+/// ```
+/// use sac::infer;
+/// use sac::kind::Kind;
+/// assert_eq!(
+///     infer::kinds("2018-06-07T08:09:10Z"),
+///     vec![Kind::Timestamp, Kind::Datetime, Kind::Text, Kind::String]
+/// );
+/// ```
+pub fn kinds(s: &str) -> Vec<Kind> {
+    KINDS
+        .iter()
+        .filter(|kind| Value::parse(s, (*kind).clone()).is_ok())
+        .cloned()
+        .collect()
+}
+
+/// Proposes a schema from the first `sample` rows of a CSV with a header of
+/// field names.
+///
+/// Each field takes the most specific kind every value in the column parses
+/// as. Empty cells make the field nullable and `N/A` cells are ignored. A
+/// column is given cardinality n when splitting its cells by `;` yields a
+/// more specific kind than the cells as a whole. Columns with Markdown are
+/// inferred as text and the rest fall back to string.
+///
+/// ```
+/// use sac::infer;
+/// let csv = "id,parents,start-date\n1,2;3,2018\n2,1,\n";
+/// let schema = infer::schema(csv.as_bytes(), 100).unwrap();
+/// assert_eq!(
+///     schema.to_json().unwrap(),
+///     concat!(
+///         r#"[{"cardinality":"1","datatype":"integer","field":"id"},"#,
+///         r#"{"cardinality":"n","datatype":"integer","field":"parents"},"#,
+///         r#"{"cardinality":"1","datatype":"integer","field":"start-date","nullable":"true"}]"#
+///     )
+/// );
+/// ```
+pub fn schema<R: Read>(reader: R, sample: usize) -> Result<Schema, InferError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut columns = Vec::new();
+
+    for header in reader.headers()? {
+        let name = Fieldname::from_str(header).map_err(|cause| InferError::InvalidColumn {
+            name: header.to_owned(),
+            cause,
+        })?;
+        columns.push((name, Column::default()));
+    }
+
+    for record in reader.records().take(sample) {
+        let record = record?;
+
+        for (&mut (_, ref mut column), cell) in columns.iter_mut().zip(record.iter()) {
+            column.add(cell);
+        }
+    }
+
+    let mut schema = Schema::new();
+
+    for (name, column) in columns {
+        schema.insert(column.field(name));
+    }
+
+    Ok(schema)
+}
+
+/// The kinds every value seen so far in a column parses as.
+#[derive(Debug, Default)]
+struct Column {
+    cells: Option<Vec<Kind>>,
+    parts: Option<Vec<Kind>>,
+    separated: bool,
+    nullable: bool,
+    markup: bool,
+}
+
+impl Column {
+    fn add(&mut self, cell: &str) {
+        if cell.is_empty() {
+            self.nullable = true;
+            return;
+        }
+
+        if Value::parse(cell, Kind::Inapplicable).is_ok() {
+            return;
+        }
+
+        narrow(&mut self.cells, &kinds(cell));
+
+        for part in cell.split(SEPARATOR) {
+            narrow(&mut self.parts, &kinds(part));
+        }
+
+        self.separated |= cell.contains(SEPARATOR);
+        self.markup |= Text::parse(cell).map(|t| t.to_plain().trim_end() != cell).unwrap_or(false);
+    }
+
+    fn field(self, name: Fieldname) -> Field {
+        let cells = best(&self.cells, self.markup);
+        let parts = best(&self.parts, self.markup);
+        let mut field = if self.separated && rank(&parts) < rank(&cells) {
+            let mut field = Field::new(name, parts);
+            field.cardinality = Cardinality::Many;
+            field
+        } else {
+            Field::new(name, cells)
+        };
+
+        field.nullable = self.nullable;
+        field
+    }
+}
+
+/// Keeps the candidates that are also in the given kinds.
+fn narrow(candidates: &mut Option<Vec<Kind>>, kinds: &[Kind]) {
+    match *candidates {
+        Some(ref mut xs) => xs.retain(|x| kinds.contains(x)),
+        None => *candidates = Some(kinds.to_vec()),
+    }
+}
+
+/// The most specific candidate for a field. Missing values don't make a
+/// datatype and text is only chosen when the values have Markdown.
+fn best(candidates: &Option<Vec<Kind>>, markup: bool) -> Kind {
+    candidates
+        .iter()
+        .flatten()
+        .find(|kind| match **kind {
+            Kind::Unknown | Kind::Inapplicable => false,
+            Kind::Text => markup,
+            _ => true,
+        })
+        .cloned()
+        .unwrap_or(Kind::String)
+}
+
+fn rank(kind: &Kind) -> usize {
+    KINDS.iter().position(|k| k == kind).unwrap_or_else(|| KINDS.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(s: &str) -> Vec<String> {
+        kinds(s).iter().map(|k| k.name().to_owned()).collect()
+    }
+
+    #[test]
+    fn kinds_by_specificity() {
+        assert_eq!(names("2018"), vec!["integer", "decimal", "datetime", "text", "string"]);
+        assert_eq!(names("-1.50"), vec!["decimal", "text", "string"]);
+        assert_eq!(names("true"), vec!["bool", "text", "string"]);
+        assert_eq!(names("N/A"), vec!["inapplicable", "text", "string"]);
+        assert_eq!(names("https://example.org"), vec!["url", "curie", "text", "string"]);
+        assert_eq!(names("<b>x</b>"), vec!["string"]);
+    }
+
+    #[test]
+    fn infer_schema() {
+        let csv = "name,note,location,status\n\
+                   Foo,**bold**,POINT (1 2),N/A\n\
+                   Bar,plain,SRID=4326;POINT (-0.1 51.5),true\n\
+                   Baz,,POINT (3 4),false\n";
+        let schema = schema(csv.as_bytes(), 10).unwrap();
+        let field = |name: &str| schema.get(&Fieldname::from_str(name).unwrap()).unwrap();
+
+        assert_eq!(field("name").datatype, Kind::String);
+        assert_eq!(field("note").datatype, Kind::Text);
+        assert!(field("note").nullable);
+        assert_eq!(field("location").datatype, Kind::Point);
+        assert_eq!(field("location").cardinality, Cardinality::One);
+        assert_eq!(field("status").datatype, Kind::Bool);
+        assert!(!field("status").nullable);
+    }
+
+    #[test]
+    fn infer_schema_from_sample() {
+        let csv = "count\n1\n2\nthree\n";
+
+        assert_eq!(schema(csv.as_bytes(), 2).unwrap().fields().next().unwrap().datatype, Kind::Integer);
+        assert_eq!(schema(csv.as_bytes(), 3).unwrap().fields().next().unwrap().datatype, Kind::String);
+    }
+
+    #[test]
+    fn invalid_column() {
+        let actual = schema("Name\nfoo\n".as_bytes(), 10).map(|_| ());
+
+        assert_eq!(
+            format!("{:?}", actual),
+            r#"Err(InvalidColumn { name: "Name", cause: InvalidFieldname { name: "Name" } })"#
+        );
+    }
+}
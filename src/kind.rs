@@ -1,15 +1,30 @@
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
+use catalogue;
+use value::custom;
+
+#[derive(Debug, Fail)]
+pub enum KindError {
+    #[fail(display = "Unknown datatype {}", _0)]
+    Unknown(String),
+}
+
 // TODO: Is "kind" better than "datatype"?
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Kind {
     Bool,
     Curie,
+    /// A datatype registered at runtime. See `value::custom`.
+    Custom(String),
     Datetime,
+    Decimal,
+    /// A string from a controlled vocabulary.
+    Enum(Vec<String>),
     Hash,
     Inapplicable,
     Integer,
+    LangString,
     List(Box<Kind>),
     Period,
     Point,
@@ -28,9 +43,11 @@ impl Display for Kind {
             // Bool,
             // Curie,
             // Datetime,
+            // Decimal,
             // Hash,
             // Inapplicable,
             // Integer,
+            // LangString,
             // List(Box<Kind>),
             // Period,
             // Point,
@@ -41,6 +58,8 @@ impl Display for Kind {
             // Unknown,
             // Untyped,
             // Url,
+            Kind::Custom(ref name) => Display::fmt(name, formatter),
+            Kind::Enum(_) => Display::fmt("Enum", formatter),
             ref x => Display::fmt(&format!("{:?}", x), formatter)
             // Value::Inapplicable => Display::fmt("N/A", formatter),
             // Value::Untyped(ref v) => Display::fmt(v, formatter),
@@ -48,27 +67,49 @@ impl Display for Kind {
     }
 }
 
+impl Kind {
+    /// The datatype name as used in field definitions and the command line.
+    /// Lists are named after the kind of their values.
+    pub fn name(&self) -> &str {
+        match *self {
+            Kind::Bool => "bool",
+            Kind::Curie => "curie",
+            Kind::Custom(ref name) => name,
+            Kind::Datetime => "datetime",
+            Kind::Decimal => "decimal",
+            Kind::Enum(_) => "enum",
+            Kind::Hash => "hash",
+            Kind::Inapplicable => "inapplicable",
+            Kind::Integer => "integer",
+            Kind::LangString => "langstring",
+            Kind::List(ref kind) => kind.name(),
+            Kind::Period => "period",
+            Kind::Point => "point",
+            Kind::Polygon => "polygon",
+            Kind::String => "string",
+            Kind::Text => "text",
+            Kind::Timestamp => "timestamp",
+            Kind::Unknown => "unknown",
+            Kind::Untyped => "untyped",
+            Kind::Url => "url",
+        }
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(*self, Kind::Custom(_))
+    }
+}
+
 impl FromStr for Kind {
-    type Err = &'static str;
+    type Err = KindError;
 
+    /// Parses the name of a datatype in the catalogue or registered at
+    /// runtime.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "bool" => Ok(Kind::Bool),
-            "curie" => Ok(Kind::Curie),
-            "datetime" => Ok(Kind::Datetime),
-            "hash" => Ok(Kind::Hash),
-            "inapplicable" => Ok(Kind::Inapplicable),
-            "integer" => Ok(Kind::Integer),
-            "period" => Ok(Kind::Period),
-            "point" => Ok(Kind::Point),
-            "polygon" => Ok(Kind::Polygon),
-            "string" => Ok(Kind::String),
-            "text" => Ok(Kind::Text),
-            "timestamp" => Ok(Kind::Timestamp),
-            "unknown" => Ok(Kind::Unknown),
-            "untyped" => Ok(Kind::Untyped),
-            "url" => Ok(Kind::Url),
-            _ => unreachable!(),
+        match catalogue::get(s) {
+            Some(datatype) => Ok(datatype.kind.clone()),
+            None if custom::is_registered(s) => Ok(Kind::Custom(s.to_owned())),
+            None => Err(KindError::Unknown(s.to_owned())),
         }
     }
 }
@@ -6,9 +6,13 @@
 
 //! Sac library
 
+extern crate csv;
+
 #[macro_use]
 extern crate failure;
 
+extern crate fs2;
+
 #[macro_use]
 extern crate lazy_static;
 
@@ -28,11 +32,21 @@ extern crate serde_derive;
 
 extern crate serde_json;
 
+extern crate toml;
+
 extern crate url;
 
 pub mod digest;
 
 pub mod blob;
+pub mod catalogue;
 pub mod kind;
 pub mod value;
 pub mod field;
+pub mod gen;
+pub mod infer;
+pub mod spatial;
+pub mod rsf;
+pub mod register;
+pub mod schema;
+pub mod store;
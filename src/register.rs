@@ -0,0 +1,363 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Register loaded from RSF and referential integrity across registers.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use blob::Blob;
+use field::Fieldname;
+use kind::Kind;
+use rsf::{self, Command, Entry, RsfError, Scope};
+use schema::{Field, Schema, SchemaError};
+use value::curie::{Curie, CurieError};
+use value::{Parse, Value};
+
+#[derive(Debug, Fail)]
+pub enum RegisterError {
+    #[fail(display = "Invalid RSF. {}", _0)]
+    InvalidRsf(#[cause] RsfError),
+    #[fail(display = "Entry {} refers to the missing item {}", entry, hash)]
+    MissingItem { entry: usize, hash: String },
+    #[fail(display = "Invalid field definition {}. {}", key, cause)]
+    InvalidField {
+        key: String,
+        #[cause]
+        cause: SchemaError,
+    },
+}
+
+impl From<RsfError> for RegisterError {
+    fn from(err: RsfError) -> RegisterError {
+        RegisterError::InvalidRsf(err)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum LinkError {
+    #[fail(display = "Unknown register {}", _0)]
+    UnknownRegister(String),
+    #[fail(display = "No record {} in register {}", key, register)]
+    UnknownRecord { register: String, key: String },
+    #[fail(display = "Record {} in register {} has ended", key, register)]
+    EndedRecord { register: String, key: String },
+    #[fail(display = "Invalid curie")]
+    InvalidCurie(#[cause] CurieError),
+}
+
+/// A register as an ordered log of entries and the items they refer to.
+#[derive(Debug, Default)]
+pub struct Register {
+    items: HashMap<String, Blob>,
+    entries: Vec<Entry>,
+    /// The latest entry number (starting at 1) per scope and key.
+    latest: HashMap<(Scope, String), usize>,
+}
+
+impl Register {
+    pub fn new() -> Self {
+        Register {
+            items: HashMap::new(),
+            entries: Vec::new(),
+            latest: HashMap::new(),
+        }
+    }
+
+    /// Loads a register from an RSF document. Every entry must refer to items
+    /// added before it.
+    pub fn from_rsf(s: &str) -> Result<Self, RegisterError> {
+        let mut register = Register::new();
+
+        for command in rsf::parse(s)? {
+            match command {
+                Command::AddItem(blob) => {
+                    register.items.insert(blob.id(), blob);
+                }
+                Command::AppendEntry(entry) => {
+                    if let Some(hash) = entry
+                        .hashes
+                        .iter()
+                        .find(|h| !register.items.contains_key(&h.to_string()))
+                    {
+                        return Err(RegisterError::MissingItem {
+                            entry: register.entries.len() + 1,
+                            hash: hash.to_string(),
+                        });
+                    }
+
+                    register.entries.push(entry);
+                    let entry = &register.entries[register.entries.len() - 1];
+                    register
+                        .latest
+                        .insert((entry.scope, entry.key.clone()), register.entries.len());
+                }
+                Command::AssertRootHash(_) => (),
+            }
+        }
+
+        Ok(register)
+    }
+
+    /// The register name as declared by the `name` system entry.
+    pub fn name(&self) -> Option<&str> {
+        let name = Fieldname::from_str("name").expect("valid fieldname");
+
+        self.latest(Scope::System, "name")
+            .and_then(|(_, entry)| self.items(entry).into_iter().next())
+            .and_then(|blob| match blob.get(&name) {
+                Some(Value::Untyped(s)) | Some(Value::String(s)) => Some(s.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Entries with their entry number (starting at 1) for the given scope.
+    pub fn entries(&self, scope: Scope) -> Vec<(usize, &Entry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|&(_, e)| e.scope == scope)
+            .map(|(i, e)| (i + 1, e))
+            .collect()
+    }
+
+    /// The items an entry refers to.
+    pub fn items(&self, entry: &Entry) -> Vec<&Blob> {
+        entry
+            .hashes
+            .iter()
+            .filter_map(|h| self.items.get(&h.to_string()))
+            .collect()
+    }
+
+    /// The latest user entry for the given key.
+    pub fn record(&self, key: &str) -> Option<(usize, &Entry)> {
+        self.latest(Scope::User, key)
+    }
+
+    /// Whether the record exists and none of its items has an `end-date`.
+    pub fn is_live(&self, key: &str) -> bool {
+        let end_date = Fieldname::from_str("end-date").expect("valid fieldname");
+
+        self.record(key).is_some_and(|(_, entry)| {
+            !entry.hashes.is_empty() && self.items(entry).iter().all(|blob| blob.get(&end_date).is_none())
+        })
+    }
+
+    /// The keys of the live records in entry order.
+    pub fn keys(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+
+        self.entries(Scope::User)
+            .into_iter()
+            .filter(|&(_, entry)| seen.insert(&entry.key))
+            .filter(|&(_, entry)| self.is_live(&entry.key))
+            .map(|(_, entry)| entry.key.clone())
+            .collect()
+    }
+
+    /// The schema declared by the `field:<name>` system entries.
+    pub fn schema(&self) -> Result<Schema, RegisterError> {
+        let mut latest = BTreeMap::new();
+        for (_, entry) in self.entries(Scope::System) {
+            if entry.key.starts_with("field:") {
+                latest.insert(entry.key.clone(), entry);
+            }
+        }
+
+        let mut schema = Schema::new();
+        for (key, entry) in latest {
+            for blob in self.items(entry) {
+                let field = Field::from_blob(blob)
+                    .map_err(|cause| RegisterError::InvalidField { key: key.clone(), cause })?;
+                schema.insert(field);
+            }
+        }
+
+        Ok(schema)
+    }
+
+    fn latest(&self, scope: Scope, key: &str) -> Option<(usize, &Entry)> {
+        self.latest
+            .get(&(scope, key.to_owned()))
+            .map(|&n| (n, &self.entries[n - 1]))
+    }
+}
+
+/// A link-typed value that doesn't resolve to a live record.
+#[derive(Debug)]
+pub struct DanglingLink {
+    pub register: String,
+    pub entry: usize,
+    pub key: String,
+    pub field: Fieldname,
+    pub value: String,
+    pub reason: LinkError,
+}
+
+/// Checks every link-typed value in the user entries of the given registers
+/// resolves to a live record. Registers are keyed by name.
+///
+/// A field with a `register` links to a record key in that register. A field
+/// with the `curie` datatype links to the record in the register named by
+/// the CURIE prefix.
+pub fn check_links(registers: &BTreeMap<String, Register>) -> Result<Vec<DanglingLink>, RegisterError> {
+    let mut dangling = Vec::new();
+
+    for (name, register) in registers {
+        let schema = register.schema()?;
+        let links: Vec<&Field> = schema.fields().filter(|f| f.is_link()).collect();
+
+        for (number, entry) in register.entries(Scope::User) {
+            for blob in register.items(entry) {
+                for field in &links {
+                    for value in link_values(blob.get(&field.name)) {
+                        if let Err(reason) = resolve(registers, field, value) {
+                            dangling.push(DanglingLink {
+                                register: name.clone(),
+                                entry: number,
+                                key: entry.key.clone(),
+                                field: field.name.clone(),
+                                value: value.to_owned(),
+                                reason,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(dangling)
+}
+
+fn link_values(value: Option<&Value>) -> Vec<&str> {
+    match value {
+        Some(Value::Untyped(s)) | Some(Value::String(s)) => vec![s],
+        Some(Value::List(xs)) => xs.iter().flat_map(|x| link_values(Some(x))).collect(),
+        _ => vec![],
+    }
+}
+
+fn resolve(registers: &BTreeMap<String, Register>, field: &Field, value: &str) -> Result<(), LinkError> {
+    let (name, key) = if let Kind::Curie = field.datatype {
+        let curie = Curie::parse(value).map_err(LinkError::InvalidCurie)?;
+        (curie.prefix().to_string(), curie.reference().to_string())
+    } else if let Some(ref name) = field.register {
+        (name.clone(), value.to_owned())
+    } else {
+        return Ok(());
+    };
+
+    let register = registers
+        .get(&name)
+        .ok_or_else(|| LinkError::UnknownRegister(name.clone()))?;
+
+    if register.record(&key).is_none() {
+        Err(LinkError::UnknownRecord { register: name, key })
+    } else if !register.is_live(&key) {
+        Err(LinkError::EndedRecord { register: name, key })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blob;
+
+    fn rsf(entries: &[(&str, &str, &str)]) -> String {
+        entries
+            .iter()
+            .map(|&(scope, key, json)| {
+                let blob = blob::from_json(json).unwrap();
+                format!(
+                    "add-item\t{}\nappend-entry\t{}\t{}\t2018-01-01T00:00:00Z\t{}\n",
+                    blob::to_json(&blob).unwrap(),
+                    scope,
+                    key,
+                    blob.id()
+                )
+            })
+            .collect()
+    }
+
+    fn registers() -> BTreeMap<String, Register> {
+        let country = rsf(&[
+            ("system", "name", r#"{"name":"country"}"#),
+            ("user", "GB", r#"{"country":"GB"}"#),
+            ("user", "CS", r#"{"country":"CS","end-date":"1992"}"#),
+        ]);
+        let school = rsf(&[
+            ("system", "name", r#"{"name":"school"}"#),
+            ("system", "field:country", r#"{"datatype":"string","field":"country","register":"country"}"#),
+            ("system", "field:parent", r#"{"cardinality":"n","datatype":"curie","field":"parent"}"#),
+            ("user", "1", r#"{"country":"GB","parent":["country:GB"]}"#),
+            ("user", "2", r#"{"country":"FR","parent":["country:CS","nope:X"]}"#),
+        ]);
+
+        vec![country, school]
+            .into_iter()
+            .map(|s| Register::from_rsf(&s).unwrap())
+            .map(|r| (r.name().unwrap().to_owned(), r))
+            .collect()
+    }
+
+    #[test]
+    fn loads_records() {
+        let registers = registers();
+        let country = &registers["country"];
+
+        assert_eq!(country.entries(Scope::User).len(), 2);
+        assert!(country.is_live("GB"));
+        assert!(!country.is_live("CS"));
+        assert!(!country.is_live("FR"));
+        assert_eq!(registers["school"].schema().unwrap().len(), 2);
+        assert_eq!(country.keys(), vec!["GB"]);
+    }
+
+    #[test]
+    fn binds_enum_vocabulary() {
+        use schema::Schema;
+
+        let mut schema = Schema::from_json(r#"[{"field": "country", "datatype": "enum", "register": "country"}]"#).unwrap();
+        schema.bind(&registers()).unwrap();
+        let field = schema.get(&Fieldname::from_str("country").unwrap()).unwrap();
+
+        assert!(field.parse("GB").is_ok());
+        assert!(field.parse("CS").is_err());
+    }
+
+    #[test]
+    fn fails_with_missing_item() {
+        let rsf = "append-entry\tuser\tGB\t2018-01-01T00:00:00Z\tsha-256:abc";
+        let actual = Register::from_rsf(rsf);
+
+        assert_eq!(
+            format!("{:?}", actual),
+            r#"Err(MissingItem { entry: 1, hash: "sha-256:abc" })"#
+        );
+    }
+
+    #[test]
+    fn reports_dangling_links() {
+        let actual: Vec<String> = check_links(&registers())
+            .unwrap()
+            .iter()
+            .map(|d| format!("{} {} {} {}: {}", d.register, d.entry, d.field.to_string(), d.value, d.reason))
+            .collect();
+
+        assert_eq!(
+            actual,
+            vec![
+                "school 5 country FR: No record FR in register country",
+                "school 5 parent country:CS: Record CS in register country has ended",
+                "school 5 parent nope:X: Unknown register nope",
+            ]
+        );
+    }
+}
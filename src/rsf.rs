@@ -0,0 +1,237 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Registers Serialisation Format (RSF).
+//!
+//! An RSF file is a sequence of commands, one per line, with arguments
+//! separated by tabs:
+//!
+//! ```text
+//! add-item <item>
+//! append-entry <scope> <key> <timestamp> <hash>[;<hash>...]
+//! assert-root-hash <hash>
+//! ```
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use blob::{self, Blob};
+use value::hash::{Hash, HashError};
+use value::timestamp::{Timestamp, TimestampError};
+use value::Parse;
+
+#[derive(Debug, Fail)]
+pub enum RsfError {
+    #[fail(display = "Line {}: unknown command {}", line, command)]
+    UnknownCommand { line: usize, command: String },
+    #[fail(display = "Line {}: wrong number of arguments for {}", line, command)]
+    WrongArity { line: usize, command: String },
+    #[fail(display = "Line {}: invalid item. {}", line, reason)]
+    InvalidItem { line: usize, reason: String },
+    #[fail(display = "Line {}: invalid entry scope {}. Expected user or system", line, scope)]
+    InvalidScope { line: usize, scope: String },
+    #[fail(display = "Line {}: invalid timestamp", line)]
+    InvalidTimestamp {
+        line: usize,
+        #[cause]
+        cause: TimestampError,
+    },
+    #[fail(display = "Line {}: invalid hash", line)]
+    InvalidHash {
+        line: usize,
+        #[cause]
+        cause: HashError,
+    },
+}
+
+/// The scope of an entry. System entries describe the register itself (name,
+/// fields) and user entries hold its records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    System,
+    User,
+}
+
+impl Display for Scope {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Scope::System => Display::fmt("system", formatter),
+            Scope::User => Display::fmt("user", formatter),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub scope: Scope,
+    pub key: String,
+    pub timestamp: Timestamp,
+    pub hashes: Vec<Hash>,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    AddItem(Blob),
+    AppendEntry(Entry),
+    AssertRootHash(Hash),
+}
+
+impl Display for Command {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Command::AddItem(ref blob) => {
+                let json = blob::to_json(blob).map_err(|_| fmt::Error)?;
+                write!(formatter, "add-item\t{}", json)
+            }
+            Command::AppendEntry(ref entry) => {
+                let hashes: Vec<String> = entry.hashes.iter().map(|h| h.to_string()).collect();
+                write!(
+                    formatter,
+                    "append-entry\t{}\t{}\t{}\t{}",
+                    entry.scope,
+                    entry.key,
+                    entry.timestamp,
+                    hashes.join(";")
+                )
+            }
+            Command::AssertRootHash(ref hash) => write!(formatter, "assert-root-hash\t{}", hash),
+        }
+    }
+}
+
+/// Parses a whole RSF document. Empty lines are ignored.
+pub fn parse(s: &str) -> Result<Vec<Command>, RsfError> {
+    s.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_command(line, i + 1))
+        .collect()
+}
+
+/// Parses a single RSF command. The line number is used for error reporting.
+///
+/// Entries without a scope (`append-entry <key> <timestamp> <hashes>`) are
+/// legacy user entries.
+pub fn parse_command(line: &str, number: usize) -> Result<Command, RsfError> {
+    let parts: Vec<&str> = line.trim_end_matches(&['\r', '\n'][..]).split('\t').collect();
+    let wrong_arity = || RsfError::WrongArity {
+        line: number,
+        command: parts[0].to_owned(),
+    };
+
+    match parts[0] {
+        "add-item" => {
+            if parts.len() != 2 {
+                return Err(wrong_arity());
+            }
+            let blob = Blob::from_str(parts[1]).map_err(|err| RsfError::InvalidItem {
+                line: number,
+                reason: err.to_string(),
+            })?;
+
+            Ok(Command::AddItem(blob))
+        }
+        "append-entry" => {
+            let (scope, rest) = match parts.len() {
+                5 => (parse_scope(parts[1], number)?, &parts[2..]),
+                4 => (Scope::User, &parts[1..]),
+                _ => return Err(wrong_arity()),
+            };
+            let timestamp = Timestamp::parse(rest[1]).map_err(|cause| RsfError::InvalidTimestamp {
+                line: number,
+                cause,
+            })?;
+            let hashes = rest[2]
+                .split(';')
+                .filter(|h| !h.is_empty())
+                .map(|h| parse_hash(h, number))
+                .collect::<Result<Vec<Hash>, RsfError>>()?;
+
+            Ok(Command::AppendEntry(Entry {
+                scope,
+                key: rest[0].to_owned(),
+                timestamp,
+                hashes,
+            }))
+        }
+        "assert-root-hash" => {
+            if parts.len() != 2 {
+                return Err(wrong_arity());
+            }
+
+            Ok(Command::AssertRootHash(parse_hash(parts[1], number)?))
+        }
+        command => Err(RsfError::UnknownCommand {
+            line: number,
+            command: command.to_owned(),
+        }),
+    }
+}
+
+fn parse_scope(s: &str, line: usize) -> Result<Scope, RsfError> {
+    match s {
+        "system" => Ok(Scope::System),
+        "user" => Ok(Scope::User),
+        _ => Err(RsfError::InvalidScope {
+            line,
+            scope: s.to_owned(),
+        }),
+    }
+}
+
+fn parse_hash(s: &str, line: usize) -> Result<Hash, RsfError> {
+    Hash::parse(s).map_err(|cause| RsfError::InvalidHash { line, cause })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_entries() {
+        let rsf = "add-item\t{\"name\":\"country\"}\n\
+                   append-entry\tsystem\tname\t2018-01-01T00:00:00Z\tsha-256:abc\n\
+                   \n\
+                   append-entry\tGB\t2018-01-01T00:00:00Z\tsha-256:abc;sha-256:def\n";
+        let commands = parse(rsf).unwrap();
+
+        assert_eq!(commands.len(), 3);
+        match commands[2] {
+            Command::AppendEntry(ref entry) => {
+                assert_eq!(entry.scope, Scope::User);
+                assert_eq!(entry.key, "GB");
+                assert_eq!(entry.hashes.len(), 2);
+            }
+            ref c => panic!("Unexpected command {:?}", c),
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let line = "append-entry\tuser\tGB\t2018-01-01T00:00:00Z\tsha-256:abc";
+        let command = parse_command(line, 1).unwrap();
+
+        assert_eq!(command.to_string(), line);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let rsf = "add-item\t{\"name\":\"country\"}\nappend-entry\tfoo\tGB\t2018-01-01T00:00:00Z\tsha-256:abc";
+        let actual = parse(rsf).map_err(|e| e.to_string());
+
+        assert_eq!(
+            actual.unwrap_err(),
+            "Line 2: invalid entry scope foo. Expected user or system"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        assert!(parse_command("delete-item\tfoo", 1).is_err());
+        assert!(parse_command("add-item", 1).is_err());
+        assert!(parse_command("append-entry\tGB", 1).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Schema of a register as a set of field definitions.
+
+use serde_json;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use blob::{self, Blob};
+use field::{FieldError, Fieldname};
+use kind::Kind;
+use register::Register;
+use value::decimal::Decimal;
+use value::enumeration::EnumError;
+use value::{Parse, Value, ValueError};
+
+#[derive(Debug, Fail)]
+pub enum SchemaError {
+    #[fail(display = "Field definition is missing the {} attribute", _0)]
+    MissingAttribute(String),
+    #[fail(display = "Invalid field name")]
+    InvalidFieldname(#[cause] FieldError),
+    #[fail(display = "Unknown datatype {}", _0)]
+    UnknownDatatype(String),
+    #[fail(display = "Invalid cardinality {}. Expected 1 or n", _0)]
+    InvalidCardinality(String),
+    #[fail(display = "Unknown register {}", _0)]
+    UnknownRegister(String),
+    #[fail(display = "The enum field {} has no allowed values", _0)]
+    EmptyEnum(String),
+    #[fail(display = "Invalid {} constraint {}", attribute, value)]
+    InvalidConstraint { attribute: String, value: String },
+    #[fail(display = "Invalid schema. {}", _0)]
+    InvalidJson(String),
+}
+
+impl From<FieldError> for SchemaError {
+    fn from(err: FieldError) -> SchemaError {
+        SchemaError::InvalidFieldname(err)
+    }
+}
+
+/// The number of values a field holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cardinality {
+    One,
+    Many,
+}
+
+impl Display for Cardinality {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cardinality::One => Display::fmt("1", formatter),
+            Cardinality::Many => Display::fmt("n", formatter),
+        }
+    }
+}
+
+impl FromStr for Cardinality {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(Cardinality::One),
+            "n" => Ok(Cardinality::Many),
+            _ => Err(SchemaError::InvalidCardinality(s.to_owned())),
+        }
+    }
+}
+
+/// A field definition as found in the field register or in the system
+/// entries of a register.
+///
+/// A field with a `register` links to the records of that register by key.
+/// An `enum` field takes its allowed values from an inline `values` list or
+/// from the keys of the records in its `register`. Numeric fields can be
+/// constrained with inclusive `min` and `max` bounds and decimal fields with
+/// a maximum `scale` (digits after the point). A `nullable` field may be left
+/// empty.
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: Fieldname,
+    pub datatype: Kind,
+    pub cardinality: Cardinality,
+    pub nullable: bool,
+    pub register: Option<String>,
+    pub text: Option<String>,
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+    pub scale: Option<usize>,
+}
+
+impl Field {
+    pub fn new(name: Fieldname, datatype: Kind) -> Self {
+        Field {
+            name,
+            datatype,
+            cardinality: Cardinality::One,
+            nullable: false,
+            register: None,
+            text: None,
+            min: None,
+            max: None,
+            scale: None,
+        }
+    }
+
+    /// Builds a field from a field definition item such as
+    /// `{"cardinality":"1","datatype":"string","field":"country","register":"country"}`.
+    pub fn from_blob(blob: &Blob) -> Result<Self, SchemaError> {
+        let name = required(blob, "field")?;
+        let datatype = required(blob, "datatype")?;
+        let cardinality = attribute(blob, "cardinality").unwrap_or("1");
+        let register = attribute(blob, "register").map(|s| s.to_owned());
+        let datatype = match Kind::from_str(datatype) {
+            Ok(Kind::Enum(_)) => match (list_attribute(blob, "values"), &register) {
+                (Some(ref values), _) if values.is_empty() => return Err(SchemaError::EmptyEnum(name.to_owned())),
+                (Some(values), _) => Kind::Enum(values),
+                (None, &Some(_)) => Kind::Enum(vec![]),
+                (None, &None) => return Err(SchemaError::MissingAttribute("values".to_owned())),
+            },
+            Ok(kind) => kind,
+            Err(_) => return Err(SchemaError::UnknownDatatype(datatype.to_owned())),
+        };
+
+        Ok(Field {
+            name: Fieldname::from_str(name)?,
+            datatype,
+            cardinality: Cardinality::from_str(cardinality)?,
+            nullable: constraint(blob, "nullable", |s| s.parse().ok())?.unwrap_or(false),
+            register,
+            text: attribute(blob, "text").map(|s| s.to_owned()),
+            min: constraint(blob, "min", |s| Decimal::parse(s).ok())?,
+            max: constraint(blob, "max", |s| Decimal::parse(s).ok())?,
+            scale: constraint(blob, "scale", |s| s.parse().ok())?,
+        })
+    }
+
+    /// Builds the field definition item for the field. Attributes with
+    /// default values are left out.
+    ///
+    /// ```
+    /// use sac::blob;
+    /// use sac::kind::Kind;
+    /// use sac::schema::Field;
+    /// let mut field = Field::new("name".parse().unwrap(), Kind::String);
+    /// field.nullable = true;
+    /// assert_eq!(
+    ///     blob::to_json(&field.to_blob()).unwrap(),
+    ///     r#"{"cardinality":"1","datatype":"string","field":"name","nullable":"true"}"#
+    /// );
+    /// ```
+    pub fn to_blob(&self) -> Blob {
+        let mut blob = Blob::new();
+        let mut set = |name: &str, value: Value| {
+            blob.insert(Fieldname::from_str(name).expect("valid attribute name"), value)
+        };
+
+        set("field", Value::String(self.name.to_string()));
+        set("datatype", Value::String(self.datatype.name().to_owned()));
+        set("cardinality", Value::String(self.cardinality.to_string()));
+
+        if self.nullable {
+            set("nullable", Value::String("true".to_owned()));
+        }
+        if let Kind::Enum(ref values) = self.datatype {
+            if self.register.is_none() {
+                set("values", Value::List(values.iter().map(|v| Value::String(v.clone())).collect()));
+            }
+        }
+        if let Some(ref register) = self.register {
+            set("register", Value::String(register.clone()));
+        }
+        if let Some(ref text) = self.text {
+            set("text", Value::String(text.clone()));
+        }
+        if let Some(ref min) = self.min {
+            set("min", Value::String(min.to_string()));
+        }
+        if let Some(ref max) = self.max {
+            set("max", Value::String(max.to_string()));
+        }
+        if let Some(scale) = self.scale {
+            set("scale", Value::String(scale.to_string()));
+        }
+
+        blob
+    }
+
+    /// Parses a value of the field datatype and checks the field constraints.
+    /// An empty value is accepted as is when the field is nullable.
+    ///
+    /// ```
+    /// use sac::kind::Kind;
+    /// use sac::schema::Field;
+    /// use sac::value::Parse;
+    /// use sac::value::decimal::Decimal;
+    /// let mut field = Field::new("rate".parse().unwrap(), Kind::Decimal);
+    /// field.max = Some(Decimal::from(100));
+    /// field.scale = Some(2);
+    /// assert!(field.parse("99.5").is_ok());
+    /// assert!(field.parse("99.555").is_err());
+    /// ```
+    pub fn parse(&self, s: &str) -> Result<Value, ValueError> {
+        if s.is_empty() && self.nullable {
+            return Ok(Value::Untyped(String::new()));
+        }
+
+        if let (Kind::Enum(values), Some(register)) = (&self.datatype, &self.register) {
+            if values.is_empty() {
+                return Err(EnumError::Unbound(register.clone()).into());
+            }
+        }
+
+        let value = Value::parse(s, self.datatype.clone())?;
+        self.check(&value)?;
+
+        Ok(value)
+    }
+
+    /// Checks the value conforms to the field constraints.
+    pub fn check(&self, value: &Value) -> Result<(), ValueError> {
+        match *value {
+            Value::Decimal(ref d) => d.check(self.min.as_ref(), self.max.as_ref(), self.scale)?,
+            Value::Integer(ref i) => i.check(self.min.as_ref(), self.max.as_ref())?,
+            Value::List(ref xs) => for x in xs {
+                self.check(x)?;
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Whether the field values are references to records in other
+    /// registers, either because the field links to a register or because
+    /// its values are CURIEs.
+    pub fn is_link(&self) -> bool {
+        match self.datatype {
+            Kind::Curie => true,
+            _ => self.register.is_some(),
+        }
+    }
+}
+
+/// A set of field definitions keyed by field name.
+///
+/// ```
+/// use sac::schema::Schema;
+/// let schema = Schema::from_json(r#"[
+///   {"field": "country", "datatype": "string", "register": "country"},
+///   {"field": "name", "datatype": "string"}
+/// ]"#).unwrap();
+/// let field = schema.get(&"country".parse().unwrap()).unwrap();
+/// assert!(field.is_link());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Schema(BTreeMap<Fieldname, Field>);
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema(BTreeMap::new())
+    }
+
+    /// Loads a schema from a JSON array of field definitions.
+    pub fn from_json(s: &str) -> Result<Self, SchemaError> {
+        let blobs: Vec<Blob> =
+            serde_json::from_str(s).map_err(|err| SchemaError::InvalidJson(err.to_string()))?;
+        let mut schema = Schema::new();
+
+        for blob in &blobs {
+            schema.insert(Field::from_blob(blob)?);
+        }
+
+        Ok(schema)
+    }
+
+    /// Serialises the schema as a JSON array of field definitions sorted by
+    /// field name.
+    pub fn to_json(&self) -> Result<String, SchemaError> {
+        let blobs: Vec<String> = self
+            .fields()
+            .map(|field| blob::to_json(&field.to_blob()).map_err(|err| SchemaError::InvalidJson(err.to_string())))
+            .collect::<Result<_, _>>()?;
+
+        Ok(format!("[{}]", blobs.join(",")))
+    }
+
+    /// Fills the allowed values of the `enum` fields backed by a register
+    /// with the keys of its live records. A register without live records
+    /// is an error as the field would reject every value.
+    pub fn bind(&mut self, registers: &BTreeMap<String, Register>) -> Result<(), SchemaError> {
+        for field in self.0.values_mut() {
+            if let (&mut Kind::Enum(ref mut values), Some(ref name)) = (&mut field.datatype, &field.register) {
+                let register = registers
+                    .get(name)
+                    .ok_or_else(|| SchemaError::UnknownRegister(name.clone()))?;
+                *values = register.keys();
+
+                if values.is_empty() {
+                    return Err(SchemaError::EmptyEnum(field.name.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, field: Field) -> Option<Field> {
+        self.0.insert(field.name.clone(), field)
+    }
+
+    pub fn get(&self, name: &Fieldname) -> Option<&Field> {
+        self.0.get(name)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.0.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn attribute<'a>(blob: &'a Blob, name: &str) -> Option<&'a str> {
+    let name = Fieldname::from_str(name).ok()?;
+
+    match blob.get(&name) {
+        Some(Value::Untyped(s)) | Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn constraint<T, F>(blob: &Blob, name: &str, parse: F) -> Result<Option<T>, SchemaError>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    match attribute(blob, name) {
+        None => Ok(None),
+        Some(s) => parse(s).map(Some).ok_or_else(|| SchemaError::InvalidConstraint {
+            attribute: name.to_owned(),
+            value: s.to_owned(),
+        }),
+    }
+}
+
+fn list_attribute(blob: &Blob, name: &str) -> Option<Vec<String>> {
+    let name = Fieldname::from_str(name).ok()?;
+
+    match blob.get(&name) {
+        Some(Value::List(xs)) => Some(xs.iter().map(|x| x.to_string()).collect()),
+        _ => None,
+    }
+}
+
+fn required<'a>(blob: &'a Blob, name: &str) -> Result<&'a str, SchemaError> {
+    attribute(blob, name).ok_or_else(|| SchemaError::MissingAttribute(name.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::Fail;
+
+    #[test]
+    fn field_from_blob() {
+        let blob = blob::from_json(
+            r#"{"cardinality":"n","datatype":"curie","field":"parent","text":"The parent."}"#,
+        ).unwrap();
+        let field = Field::from_blob(&blob).unwrap();
+
+        assert_eq!(field.name, Fieldname::from_str("parent").unwrap());
+        assert_eq!(field.cardinality, Cardinality::Many);
+        assert_eq!(field.text, Some("The parent.".to_string()));
+        assert!(field.is_link());
+    }
+
+    #[test]
+    fn field_defaults_to_cardinality_one() {
+        let blob = blob::from_json(r#"{"datatype":"string","field":"name"}"#).unwrap();
+        let field = Field::from_blob(&blob).unwrap();
+
+        assert_eq!(field.cardinality, Cardinality::One);
+        assert!(!field.is_link());
+    }
+
+    #[test]
+    fn schema_roundtrip() {
+        let raw = r#"[{"cardinality":"n","datatype":"enum","field":"status","nullable":"true","values":["open","closed"]},{"cardinality":"1","datatype":"decimal","field":"rate","max":"100","scale":"2"}]"#;
+        let schema = Schema::from_json(raw).unwrap();
+        let status = schema.get(&Fieldname::from_str("status").unwrap()).unwrap();
+
+        assert!(status.nullable);
+        assert_eq!(
+            schema.to_json().unwrap(),
+            r#"[{"cardinality":"1","datatype":"decimal","field":"rate","max":"100","scale":"2"},{"cardinality":"n","datatype":"enum","field":"status","nullable":"true","values":["open","closed"]}]"#
+        );
+    }
+
+    #[test]
+    fn field_errors() {
+        let missing = blob::from_json(r#"{"field":"name"}"#).unwrap();
+        let unknown = blob::from_json(r#"{"datatype":"float","field":"name"}"#).unwrap();
+
+        assert_eq!(
+            format!("{:?}", Field::from_blob(&missing)),
+            r#"Err(MissingAttribute("datatype"))"#
+        );
+        assert_eq!(
+            format!("{:?}", Field::from_blob(&unknown)),
+            r#"Err(UnknownDatatype("float"))"#
+        );
+    }
+
+    #[test]
+    fn field_constraints() {
+        let blob = blob::from_json(
+            r#"{"datatype":"decimal","field":"rate","max":"100","min":"0","scale":"2"}"#,
+        ).unwrap();
+        let field = Field::from_blob(&blob).unwrap();
+
+        assert_eq!(format!("{:?}", field.parse("12.50")), r#"Ok(Decimal("12.5"))"#);
+        assert!(field.parse("-0.01").is_err());
+        assert!(field.parse("100.001").is_err());
+
+        let blob = blob::from_json(r#"{"datatype":"integer","field":"year","min":"1800"}"#).unwrap();
+        let field = Field::from_blob(&blob).unwrap();
+
+        assert!(field.parse("2018").is_ok());
+        assert!(field.parse("1799").is_err());
+
+        let invalid = blob::from_json(r#"{"datatype":"decimal","field":"rate","scale":"two"}"#).unwrap();
+        assert_eq!(
+            format!("{:?}", Field::from_blob(&invalid)),
+            r#"Err(InvalidConstraint { attribute: "scale", value: "two" })"#
+        );
+    }
+
+    #[test]
+    fn nullable_field() {
+        let mut field = Field::new("end-date".parse().unwrap(), Kind::Datetime);
+
+        assert!(field.parse("").is_err());
+
+        field.nullable = true;
+        assert_eq!(format!("{:?}", field.parse("")), r#"Ok(Untyped(""))"#);
+        assert!(field.parse("nope").is_err());
+    }
+
+    #[test]
+    fn enum_field() {
+        let blob = blob::from_json(r#"{"datatype":"enum","field":"status","values":["open","closed"]}"#).unwrap();
+        let field = Field::from_blob(&blob).unwrap();
+
+        assert!(field.parse("open").is_ok());
+        assert_eq!(
+            field.parse("opne").map_err(|e| e.cause().unwrap().to_string()),
+            Err("Unexpected value opne. Allowed values: open, closed. Did you mean open?".to_string())
+        );
+
+        let missing = blob::from_json(r#"{"datatype":"enum","field":"status"}"#).unwrap();
+        assert!(Field::from_blob(&missing).is_err());
+
+        let empty = blob::from_json(r#"{"datatype":"enum","field":"status","values":[]}"#).unwrap();
+        assert_eq!(format!("{:?}", Field::from_blob(&empty)), r#"Err(EmptyEnum("status"))"#);
+
+        let unbound = blob::from_json(r#"{"datatype":"enum","field":"status","register":"status"}"#).unwrap();
+        assert_eq!(
+            Field::from_blob(&unbound).unwrap().parse("open").map_err(|e| e.cause().unwrap().to_string()),
+            Err("Unbound enumeration. The allowed values come from the status register.".to_string())
+        );
+    }
+}
@@ -0,0 +1,392 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Spatial queries over the geometry fields of a set of blobs.
+//!
+//! Geometries are indexed in memory with an R-tree bulk loaded using the
+//! Sort-Tile-Recursive algorithm. Every geometry is indexed in WGS84 so
+//! points and polygons expressed in British National Grid can be mixed with
+//! WGS84 ones. Geometries without SRID are assumed to be WGS84.
+//!
+//! Note that queries do not wrap around the antimeridian.
+
+use std::cmp::Ordering;
+
+use blob::Blob;
+use field::Fieldname;
+use kind::Kind;
+use value::crs::EARTH_RADIUS;
+use value::point::{Coord2, Point, PointError};
+use value::polygon::{Polygon, PolygonError};
+use value::Value;
+
+const NODE_CAPACITY: usize = 16;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bbox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bbox {
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Bbox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    pub fn point(x: f64, y: f64) -> Self {
+        Bbox::new(x, y, x, y)
+    }
+
+    pub fn intersects(&self, other: &Bbox) -> bool {
+        self.min_x <= other.max_x && other.min_x <= self.max_x && self.min_y <= other.max_y
+            && other.min_y <= self.max_y
+    }
+
+    pub fn union(&self, other: &Bbox) -> Bbox {
+        Bbox::new(
+            self.min_x.min(other.min_x),
+            self.min_y.min(other.min_y),
+            self.max_x.max(other.max_x),
+            self.max_y.max(other.max_y),
+        )
+    }
+
+    fn center(&self) -> (f64, f64) {
+        ((self.min_x + self.max_x) / 2.0, (self.min_y + self.max_y) / 2.0)
+    }
+}
+
+enum Node<T> {
+    Leaf(Vec<(Bbox, T)>),
+    Branch(Vec<(Bbox, Node<T>)>),
+}
+
+/// A static R-tree.
+pub struct RTree<T> {
+    root: Option<Node<T>>,
+    len: usize,
+}
+
+impl<T> RTree<T> {
+    /// Builds the tree from the given entries using Sort-Tile-Recursive packing.
+    pub fn bulk_load(entries: Vec<(Bbox, T)>) -> Self {
+        let len = entries.len();
+
+        if entries.is_empty() {
+            return RTree { root: None, len };
+        }
+
+        let mut level: Vec<(Bbox, Node<T>)> = pack(entries)
+            .into_iter()
+            .map(|group| (envelope(&group), Node::Leaf(group)))
+            .collect();
+
+        while level.len() > 1 {
+            level = pack(level)
+                .into_iter()
+                .map(|group| (envelope(&group), Node::Branch(group)))
+                .collect();
+        }
+
+        RTree {
+            root: level.pop().map(|(_, node)| node),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns every entry whose bounding box intersects the given one.
+    pub fn search(&self, bbox: &Bbox) -> Vec<&T> {
+        let mut result = Vec::new();
+        let mut stack: Vec<&Node<T>> = self.root.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            match *node {
+                Node::Leaf(ref entries) => result.extend(
+                    entries
+                        .iter()
+                        .filter(|(b, _)| b.intersects(bbox))
+                        .map(|(_, t)| t),
+                ),
+                Node::Branch(ref children) => stack.extend(
+                    children
+                        .iter()
+                        .filter(|(b, _)| b.intersects(bbox))
+                        .map(|(_, n)| n),
+                ),
+            }
+        }
+
+        result
+    }
+}
+
+fn envelope<U>(entries: &[(Bbox, U)]) -> Bbox {
+    entries
+        .iter()
+        .skip(1)
+        .fold(entries[0].0, |acc, (b, _)| acc.union(b))
+}
+
+fn by_center<U>(axis: fn(&Bbox) -> f64) -> impl Fn(&(Bbox, U), &(Bbox, U)) -> Ordering {
+    move |a, b| {
+        axis(&a.0)
+            .partial_cmp(&axis(&b.0))
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+fn chunks<U>(mut entries: Vec<U>, size: usize) -> Vec<Vec<U>> {
+    let mut result = Vec::new();
+
+    while entries.len() > size {
+        let rest = entries.split_off(size);
+        result.push(entries);
+        entries = rest;
+    }
+    result.push(entries);
+
+    result
+}
+
+fn pack<U>(mut entries: Vec<(Bbox, U)>) -> Vec<Vec<(Bbox, U)>> {
+    let leaves = (entries.len() + NODE_CAPACITY - 1) / NODE_CAPACITY;
+    let slices = (leaves as f64).sqrt().ceil() as usize;
+
+    entries.sort_by(by_center(|b| b.center().0));
+
+    chunks(entries, slices * NODE_CAPACITY)
+        .into_iter()
+        .flat_map(|mut slice| {
+            slice.sort_by(by_center(|b| b.center().1));
+            chunks(slice, NODE_CAPACITY)
+        })
+        .collect()
+}
+
+/// A geometry expressed in WGS84.
+#[derive(Clone, Debug)]
+pub enum Geometry {
+    Point(f64, f64),
+    Polygon(Polygon),
+}
+
+impl Geometry {
+    /// Extracts a geometry from a value. Untyped values are parsed as either
+    /// a point or a polygon.
+    pub fn from_value(value: &Value) -> Option<Geometry> {
+        match *value {
+            Value::Point(ref p) => Geometry::from_point(p).ok(),
+            Value::Polygon(ref p) => Geometry::from_polygon(p).ok(),
+            Value::Untyped(ref s) => Value::parse(s, Kind::Point)
+                .or_else(|_| Value::parse(s, Kind::Polygon))
+                .ok()
+                .and_then(|v| Geometry::from_value(&v)),
+            _ => None,
+        }
+    }
+
+    pub fn from_point(point: &Point) -> Result<Geometry, PointError> {
+        let (x, y) = point.lon_lat()?;
+
+        Ok(Geometry::Point(x, y))
+    }
+
+    pub fn from_polygon(polygon: &Polygon) -> Result<Geometry, PolygonError> {
+        Ok(Geometry::Polygon(polygon.lon_lat()?))
+    }
+
+    pub fn bbox(&self) -> Bbox {
+        match *self {
+            Geometry::Point(x, y) => Bbox::point(x, y),
+            Geometry::Polygon(ref p) => {
+                let (min_x, min_y, max_x, max_y) = p.bounds();
+                Bbox::new(min_x, min_y, max_x, max_y)
+            }
+        }
+    }
+}
+
+/// Spatial index over a geometry field of a set of blobs. Blobs where the
+/// field is missing or not a geometry are not indexed.
+///
+/// ```
+/// use sac::blob;
+/// use sac::spatial::SpatialIndex;
+/// use sac::value::Parse;
+/// use sac::value::point::Point;
+///
+/// let blobs = vec![
+///     blob::from_json(r#"{"name": "a", "location": "POINT (-0.1246 51.5007)"}"#).unwrap(),
+///     blob::from_json(r#"{"name": "b", "location": "POINT (-2.5879 51.4545)"}"#).unwrap(),
+/// ];
+/// let index = SpatialIndex::new(&blobs, &"location".parse().unwrap());
+/// let here = Point::parse("POINT (-0.1278 51.5074)").unwrap();
+///
+/// assert_eq!(index.near(&here, 5000.0).unwrap().len(), 1);
+/// ```
+pub struct SpatialIndex<'a> {
+    tree: RTree<(&'a Blob, Geometry)>,
+}
+
+impl<'a> SpatialIndex<'a> {
+    pub fn new(blobs: &'a [Blob], field: &Fieldname) -> Self {
+        let entries = blobs
+            .iter()
+            .filter_map(|blob| {
+                blob.get(field)
+                    .and_then(Geometry::from_value)
+                    .map(|geometry| (geometry.bbox(), (blob, geometry)))
+            })
+            .collect();
+
+        SpatialIndex {
+            tree: RTree::bulk_load(entries),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Finds the blobs with a point within the given radius (metres) of the
+    /// given point, sorted by geodesic distance.
+    pub fn near(&self, point: &Point, radius: f64) -> Result<Vec<(&'a Blob, f64)>, PointError> {
+        let (x, y) = point.lon_lat()?;
+        let dy = (radius / EARTH_RADIUS).to_degrees();
+        let dx = if y.abs() + dy >= 90.0 {
+            180.0
+        } else {
+            dy / y.to_radians().cos()
+        };
+        let bbox = Bbox::new(x - dx, y - dy, x + dx, y + dy);
+
+        let mut result = Vec::new();
+        for &(blob, ref geometry) in self.tree.search(&bbox) {
+            if let Geometry::Point(px, py) = *geometry {
+                let other = Point::new(Coord2::new(px, py));
+                let distance = point.distance(&other).or_else(|_| point.haversine(&other))?;
+
+                if distance <= radius {
+                    result.push((blob, distance));
+                }
+            }
+        }
+
+        result.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+        Ok(result)
+    }
+
+    /// Finds the blobs with a point inside the given polygon.
+    pub fn within(&self, polygon: &Polygon) -> Result<Vec<&'a Blob>, PolygonError> {
+        let polygon = polygon.lon_lat()?;
+        let (min_x, min_y, max_x, max_y) = polygon.bounds();
+        let bbox = Bbox::new(min_x, min_y, max_x, max_y);
+
+        Ok(self.tree
+            .search(&bbox)
+            .into_iter()
+            .filter_map(|&(blob, ref geometry)| match *geometry {
+                Geometry::Point(x, y) if polygon.contains(x, y) => Some(blob),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Finds the blobs with a polygon containing the given point.
+    pub fn containing(&self, point: &Point) -> Result<Vec<&'a Blob>, PointError> {
+        let (x, y) = point.lon_lat()?;
+
+        Ok(self.tree
+            .search(&Bbox::point(x, y))
+            .into_iter()
+            .filter_map(|&(blob, ref geometry)| match *geometry {
+                Geometry::Polygon(ref p) if p.contains(x, y) => Some(blob),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use value::Parse;
+
+    #[test]
+    fn search_rtree() {
+        let entries: Vec<_> = (0..1000)
+            .map(|i| {
+                let (x, y) = ((i % 40) as f64, (i / 40) as f64);
+                (Bbox::point(x, y), i)
+            })
+            .collect();
+        let tree = RTree::bulk_load(entries);
+
+        let mut found: Vec<_> = tree.search(&Bbox::new(0.5, 0.5, 2.5, 1.5))
+            .into_iter()
+            .cloned()
+            .collect();
+        found.sort();
+
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(found, vec![41, 42]);
+    }
+
+    #[test]
+    fn search_empty_rtree() {
+        let tree: RTree<()> = RTree::bulk_load(vec![]);
+
+        assert!(tree.search(&Bbox::point(0.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn index_queries() {
+        let blobs = vec![
+            Blob::from_str(r#"{"geo": "SRID=27700;POINT (530268 179640)"}"#).unwrap(),
+            Blob::from_str(r#"{"geo": "POINT (-0.1 51.6)"}"#).unwrap(),
+            Blob::from_str(r#"{"geo": "POLYGON ((-1 51, 1 51, 1 52, -1 52, -1 51))"}"#).unwrap(),
+            Blob::from_str(r#"{"geo": "not a geometry"}"#).unwrap(),
+            Blob::from_str(r#"{"other": "POINT (0 0)"}"#).unwrap(),
+        ];
+        let index = SpatialIndex::new(&blobs, &"geo".parse().unwrap());
+        let here = Point::parse("POINT (-0.1246 51.5007)").unwrap();
+
+        assert_eq!(index.len(), 3);
+
+        let near = index.near(&here, 1000.0).unwrap();
+        assert_eq!(near.len(), 1);
+        assert!(near[0].1 < 10.0);
+
+        assert_eq!(index.near(&here, 15_000.0).unwrap().len(), 2);
+        assert_eq!(index.containing(&here).unwrap().len(), 1);
+
+        let area = Polygon::parse("POLYGON ((-0.2 51.55, 0 51.55, 0 51.65, -0.2 51.65, -0.2 51.55))")
+            .unwrap();
+        assert_eq!(index.within(&area).unwrap().len(), 1);
+    }
+}
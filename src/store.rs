@@ -0,0 +1,1028 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Register kept in a local directory.
+//!
+//! The directory holds an append-only log of entries, the items the entries
+//! refer to addressed by their hash and an index from record key to its
+//! latest entry:
+//!
+//! ```text
+//! <register>/
+//!   entries.log     # One framed RSF append-entry command per entry.
+//!   items/<hash>    # Canonical JSON of each item.
+//!   index.json      # Latest entry number per key, rebuilt when stale.
+//! ```
+//!
+//! Each entry in the log is a record made of the length of its payload and
+//! the CRC-32 of the payload, both little-endian `u32`, followed by the
+//! payload. Items are written before the entry that refers to them so the
+//! log never points to a missing item.
+//!
+//! An append interrupted by a crash leaves at most a partial record at the
+//! end of the log. Opening the register truncates it and reports what was
+//! discarded. Any other damage to the log is an error.
+//!
+//! A register is opened either for writing, holding an exclusive lock on the
+//! `lock` file, or for reading, holding a shared one. The lock is held until
+//! the store is dropped so processes sharing a register never see each
+//! other's appends half done. The lock file records the PID of the writer
+//! holding the lock to name it when another process times out. Readers are
+//! not named.
+
+use fs2::{self, FileExt};
+use serde_json;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryInto;
+use std::fmt::{self, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use blob::{self, Blob};
+use digest;
+use rsf::{self, Command, Entry, RsfError, Scope};
+use value::hash::{Alg, Hash};
+use value::timestamp::Timestamp;
+
+const ENTRIES: &str = "entries.log";
+const ITEMS: &str = "items";
+const INDEX: &str = "index.json";
+const LOCK: &str = "lock";
+
+/// How long `Store::open` waits for another process to release the lock.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a contended lock is tried again.
+const RETRY: Duration = Duration::from_millis(20);
+
+/// The size of a record header: the payload length and its checksum.
+const HEADER: usize = 8;
+
+#[derive(Debug, Fail)]
+pub enum StoreError {
+    #[fail(display = "{}: {}", path, cause)]
+    Io {
+        path: String,
+        #[cause]
+        cause: io::Error,
+    },
+    #[fail(display = "{} is already a register", _0)]
+    AlreadyExists(String),
+    #[fail(display = "{} is not a register", _0)]
+    NotARegister(String),
+    #[fail(display = "Invalid entry log. {}", _0)]
+    InvalidLog(#[cause] RsfError),
+    #[fail(display = "The entry log is corrupt at byte {}", _0)]
+    CorruptLog(usize),
+    #[fail(display = "Line {}: unexpected {} in the entry log. Only append-entry is allowed", line, command)]
+    UnexpectedCommand { line: usize, command: String },
+    #[fail(display = "Invalid key {:?}. Keys can't be empty or have tabs or line breaks", _0)]
+    InvalidKey(String),
+    #[fail(display = "Missing item {}", _0)]
+    MissingItem(String),
+    #[fail(display = "Corrupt item {}. {}", hash, reason)]
+    CorruptItem { hash: String, reason: String },
+    #[fail(display = "Timed out after {:?} waiting for {} locked by {}", timeout, path, holder)]
+    Locked {
+        path: String,
+        holder: String,
+        timeout: Duration,
+    },
+    #[fail(display = "{} is open for reading only", _0)]
+    ReadOnly(String),
+}
+
+impl From<RsfError> for StoreError {
+    fn from(err: RsfError) -> StoreError {
+        StoreError::InvalidLog(err)
+    }
+}
+
+/// Whether a store can append entries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    /// Shared with other readers.
+    Read,
+    /// Exclusive.
+    Write,
+}
+
+/// When appends are flushed to disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncPolicy {
+    /// Items, the entry log and the directories are synced before an append
+    /// returns so the entry survives a power cut.
+    Always,
+    /// Syncing is left to the operating system. The latest entries can be
+    /// lost on a power cut but the log is never corrupted.
+    Never,
+}
+
+/// The partial record found at the end of the entry log on open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovery {
+    /// The byte where the partial record started.
+    pub offset: usize,
+    /// The number of bytes discarded.
+    pub discarded: usize,
+    /// Whether the log was truncated. Readers leave the partial record in
+    /// place and only ignore it.
+    pub truncated: bool,
+}
+
+impl Display for Recovery {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {} bytes of a partially written entry at byte {}",
+            if self.truncated { "Discarded" } else { "Ignoring" },
+            self.discarded,
+            self.offset
+        )
+    }
+}
+
+/// The latest entry number (starting at 1) per key and scope as of the given
+/// number of entries.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Index {
+    entries: usize,
+    user: BTreeMap<String, usize>,
+    system: BTreeMap<String, usize>,
+}
+
+impl Index {
+    fn build(entries: &[Entry]) -> Self {
+        let mut index = Index::default();
+        for entry in entries {
+            index.push(entry);
+        }
+
+        index
+    }
+
+    fn push(&mut self, entry: &Entry) {
+        self.entries += 1;
+
+        let keys = match entry.scope {
+            Scope::User => &mut self.user,
+            Scope::System => &mut self.system,
+        };
+        keys.insert(entry.key.clone(), self.entries);
+    }
+}
+
+/// A register in a local directory.
+///
+/// ```
+/// use sac::blob;
+/// use sac::rsf::Scope;
+/// use sac::store::Store;
+/// use sac::value::Parse;
+/// use sac::value::timestamp::Timestamp;
+///
+/// let path = std::env::temp_dir().join(format!("sac-doc-{}", std::process::id()));
+/// let mut store = Store::init(&path).unwrap();
+/// let blob = blob::from_json(r#"{"country":"GB","name":"United Kingdom"}"#).unwrap();
+/// let timestamp = Timestamp::parse("2018-01-01T00:00:00Z").unwrap();
+/// assert_eq!(store.append(Scope::User, "GB", timestamp, vec![blob]).unwrap(), 1);
+///
+/// drop(store);
+/// let store = Store::open(&path).unwrap();
+/// assert_eq!(store.latest(Scope::User, "GB").map(|(n, _)| n), Some(1));
+/// # std::fs::remove_dir_all(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    entries: Vec<Entry>,
+    index: Index,
+    /// The length of the valid part of the entry log.
+    len: usize,
+    sync: SyncPolicy,
+    recovery: Option<Recovery>,
+    access: Access,
+    /// The locked lock file.
+    lock: File,
+}
+
+impl Store {
+    /// Creates an empty register in the given directory, creating the
+    /// directory if needed. The store is open for writing.
+    pub fn init(path: &Path) -> Result<Self, StoreError> {
+        if path.join(ENTRIES).exists() {
+            return Err(StoreError::AlreadyExists(path.display().to_string()));
+        }
+
+        let items = path.join(ITEMS);
+        fs::create_dir_all(&items).map_err(|cause| io_error(path, cause))?;
+        let lock = lock(path, Access::Write, DEFAULT_TIMEOUT)?;
+
+        // Another process may have created it while this one waited.
+        if path.join(ENTRIES).exists() {
+            return Err(StoreError::AlreadyExists(path.display().to_string()));
+        }
+
+        let entries = path.join(ENTRIES);
+        File::create(&entries)
+            .and_then(|file| file.sync_all())
+            .map_err(|cause| io_error(&entries, cause))?;
+        sync_dir(&items)
+            .and_then(|_| sync_dir(path))
+            .map_err(|cause| io_error(path, cause))?;
+
+        let store = Store {
+            path: path.to_owned(),
+            entries: Vec::new(),
+            index: Index::default(),
+            len: 0,
+            sync: SyncPolicy::Always,
+            recovery: None,
+            access: Access::Write,
+            lock,
+        };
+        store.write_index()?;
+
+        Ok(store)
+    }
+
+    /// Opens the register in the given directory for writing, waiting up to
+    /// `DEFAULT_TIMEOUT` for other processes to release it.
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        Store::open_with(path, Access::Write, DEFAULT_TIMEOUT)
+    }
+
+    /// Opens the register in the given directory, waiting up to `timeout`
+    /// for the lock.
+    ///
+    /// A partial record at the end of the entry log is reported by
+    /// `recovery` and, when writing, truncated. The index is rebuilt from
+    /// the entry log and, when writing, the index file is rewritten if it is
+    /// missing or out of date.
+    pub fn open_with(path: &Path, access: Access, timeout: Duration) -> Result<Self, StoreError> {
+        let log = path.join(ENTRIES);
+        if !log.is_file() {
+            return Err(StoreError::NotARegister(path.display().to_string()));
+        }
+
+        let lock = lock(path, access, timeout)?;
+        let bytes = fs::read(&log).map_err(|cause| io_error(&log, cause))?;
+        let (records, len) = unframe(&bytes)?;
+        let mut entries = Vec::new();
+        for (offset, payload) in records {
+            let line = str::from_utf8(payload).map_err(|_| StoreError::CorruptLog(offset))?;
+
+            match rsf::parse_command(line, entries.len() + 1)? {
+                Command::AppendEntry(entry) => entries.push(entry),
+                _ => {
+                    return Err(StoreError::UnexpectedCommand {
+                        line: entries.len() + 1,
+                        command: line.split('\t').next().unwrap_or(line).to_owned(),
+                    })
+                }
+            }
+        }
+
+        let recovery = if len == bytes.len() {
+            None
+        } else {
+            if access == Access::Write {
+                OpenOptions::new()
+                    .write(true)
+                    .open(&log)
+                    .and_then(|file| {
+                        file.set_len(len as u64)?;
+                        file.sync_all()
+                    })
+                    .map_err(|cause| io_error(&log, cause))?;
+            }
+
+            Some(Recovery {
+                offset: len,
+                discarded: bytes.len() - len,
+                truncated: access == Access::Write,
+            })
+        };
+
+        // The log is already in memory so the index is rebuilt from it rather
+        // than trusting a file that may be damaged.
+        let index = Index::build(&entries);
+        let stale = fs::read_to_string(path.join(INDEX))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<Index>(&raw).ok())
+            .map_or(true, |stored| stored != index);
+        let store = Store {
+            path: path.to_owned(),
+            index,
+            entries,
+            len,
+            sync: SyncPolicy::Always,
+            recovery,
+            access,
+            lock,
+        };
+
+        if stale && access == Access::Write {
+            store.write_index()?;
+        }
+
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sets when appends are flushed to disk. Defaults to `Always`.
+    pub fn set_sync(&mut self, sync: SyncPolicy) {
+        self.sync = sync;
+    }
+
+    /// The partial record found when the register was opened, if any.
+    pub fn recovery(&self) -> Option<&Recovery> {
+        self.recovery.as_ref()
+    }
+
+    /// The entries in log order. Entry numbers start at 1.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The latest entry for the given key with its entry number.
+    pub fn latest(&self, scope: Scope, key: &str) -> Option<(usize, &Entry)> {
+        let keys = match scope {
+            Scope::User => &self.index.user,
+            Scope::System => &self.index.system,
+        };
+
+        keys.get(key).map(|&n| (n, &self.entries[n - 1]))
+    }
+
+    /// Reads the item with the given hash, checking its content matches it.
+    pub fn item(&self, hash: &Hash) -> Result<Blob, StoreError> {
+        let path = self.item_path(hash.bytes());
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(StoreError::MissingItem(hash.to_string()))
+            }
+            Err(cause) => return Err(io_error(&path, cause)),
+        };
+        let corrupt = |reason: String| StoreError::CorruptItem {
+            hash: hash.to_string(),
+            reason,
+        };
+        let blob = blob::from_json(&raw).map_err(|err| corrupt(err.to_string()))?;
+
+        if blob.hash() != hash.bytes() {
+            return Err(corrupt(format!("The content hashes to {}", blob.id())));
+        }
+
+        Ok(blob)
+    }
+
+    /// Appends an entry for the given items and returns its entry number.
+    /// Items already in the register are not written again.
+    ///
+    /// The entry is written as a single record. If writing it fails the log
+    /// is truncated back to its previous length.
+    pub fn append(
+        &mut self,
+        scope: Scope,
+        key: &str,
+        timestamp: Timestamp,
+        blobs: Vec<Blob>,
+    ) -> Result<usize, StoreError> {
+        if self.access == Access::Read {
+            return Err(StoreError::ReadOnly(self.path.display().to_string()));
+        }
+
+        if key.is_empty() || key.contains(['\t', '\n', '\r']) {
+            return Err(StoreError::InvalidKey(key.to_owned()));
+        }
+
+        let sync = self.sync == SyncPolicy::Always;
+        let mut hashes = Vec::new();
+        for blob in blobs {
+            let hash = blob.hash();
+            let path = self.item_path(&hash);
+
+            if !path.exists() {
+                let json = blob::to_json(&blob).expect("blobs serialise as JSON");
+                write_atomically(&path, json.as_bytes(), sync)?;
+            }
+
+            hashes.push(Hash::new(Alg::Sha2256, hash));
+        }
+
+        if sync {
+            let items = self.path.join(ITEMS);
+            sync_dir(&items).map_err(|cause| io_error(&items, cause))?;
+        }
+
+        let entry = Entry {
+            scope,
+            key: key.to_owned(),
+            timestamp,
+            hashes,
+        };
+        let record = frame(Command::AppendEntry(entry.clone()).to_string().as_bytes());
+        let log = self.path.join(ENTRIES);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .map_err(|cause| io_error(&log, cause))?;
+        let written = file
+            .write_all(&record)
+            .and_then(|_| if sync { file.sync_data() } else { Ok(()) });
+
+        if let Err(cause) = written {
+            let _ = file.set_len(self.len as u64);
+            return Err(io_error(&log, cause));
+        }
+
+        self.len += record.len();
+        self.index.push(&entry);
+        self.entries.push(entry);
+        self.write_index()?;
+
+        Ok(self.entries.len())
+    }
+
+    /// The register as RSF commands. Each item is added right before the
+    /// first entry that refers to it.
+    pub fn to_rsf(&self) -> Result<Vec<Command>, StoreError> {
+        let mut commands = Vec::new();
+        let mut added = HashSet::new();
+
+        for entry in &self.entries {
+            for hash in &entry.hashes {
+                if added.insert(hash) {
+                    commands.push(Command::AddItem(self.item(hash)?));
+                }
+            }
+            commands.push(Command::AppendEntry(entry.clone()));
+        }
+
+        Ok(commands)
+    }
+
+    fn item_path(&self, hash: &str) -> PathBuf {
+        self.path.join(ITEMS).join(hash)
+    }
+
+    /// Replaces the index in one step so readers never see half of it. The
+    /// index is never synced as it can be rebuilt from the log.
+    fn write_index(&self) -> Result<(), StoreError> {
+        let json = serde_json::to_string(&self.index).expect("the index serialises as JSON");
+
+        write_atomically(&self.path.join(INDEX), json.as_bytes(), false)
+    }
+}
+
+impl Drop for Store {
+    /// Clears the PID of a writer and releases the lock. Closing the file
+    /// would release it too.
+    fn drop(&mut self) {
+        if self.access == Access::Write {
+            let _ = self.lock.set_len(0);
+        }
+        let _ = FileExt::unlock(&self.lock);
+    }
+}
+
+/// The payload of a log record with the offset of the record.
+type Record<'a> = (usize, &'a [u8]);
+
+/// Frames the payload as a log record.
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&digest::crc32(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+
+    record
+}
+
+/// Splits the log into the payloads of its records with their offsets and
+/// returns the length of the valid part of the log.
+///
+/// Only the last record can be torn: an incomplete header, a record
+/// overrunning the log that could be an entry cut short, or zeros only, as
+/// some file systems leave after a crash. Any other bad record, including a
+/// complete last record failing its checksum, is corruption.
+fn unframe(log: &[u8]) -> Result<(Vec<Record<'_>>, usize), StoreError> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset < log.len() {
+        let rest = &log[offset..];
+
+        if let Some(payload) = record(rest) {
+            records.push((offset, payload));
+            offset += HEADER + payload.len();
+            continue;
+        }
+
+        if rest.len() < HEADER || rest.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        if HEADER + len > rest.len() && is_torn(&rest[HEADER..]) {
+            break;
+        }
+
+        return Err(StoreError::CorruptLog(offset));
+    }
+
+    Ok((records, offset))
+}
+
+/// The payload of the record at the start of the bytes if it is complete
+/// and passes its checksum.
+fn record(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.len() < HEADER {
+        return None;
+    }
+
+    let len = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(bytes[4..HEADER].try_into().unwrap());
+
+    bytes
+        .get(HEADER..HEADER + len)
+        .filter(|payload| len > 0 && digest::crc32(payload) == crc)
+}
+
+/// Whether the bytes after the header of a record overrunning the log are
+/// zeros or the start of an entry with no record after them. A corrupt
+/// length in front of other records or a log in another format is not torn.
+fn is_torn(partial: &[u8]) -> bool {
+    let prefix = b"append-entry\t";
+    let n = partial.len().min(prefix.len());
+
+    partial.iter().all(|&b| b == 0)
+        || (partial[..n] == prefix[..n] && !(1..partial.len()).any(|i| record(&partial[i..]).is_some()))
+}
+
+/// Writes the file in full or not at all by renaming a temporary file.
+fn write_atomically(path: &Path, bytes: &[u8], sync: bool) -> Result<(), StoreError> {
+    let tmp = path.with_extension("tmp");
+
+    File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            if sync {
+                file.sync_all()?;
+            }
+            Ok(())
+        })
+        .map_err(|cause| io_error(&tmp, cause))?;
+
+    fs::rename(&tmp, path).map_err(|cause| io_error(path, cause))
+}
+
+/// Makes the files created or renamed in the directory durable.
+fn sync_dir(path: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        File::open(path)?.sync_all()
+    } else {
+        Ok(())
+    }
+}
+
+/// Takes the lock of the register, retrying until the timeout, and records
+/// the PID of this process in the lock file.
+fn lock(path: &Path, access: Access, timeout: Duration) -> Result<File, StoreError> {
+    let lock_path = path.join(LOCK);
+    let create = || {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+    };
+    // Readers don't write to the lock file so they work in read-only
+    // directories once it exists.
+    let mut file = match access {
+        Access::Read => File::open(&lock_path).or_else(|err| match err.kind() {
+            io::ErrorKind::NotFound => create(),
+            _ => Err(err),
+        }),
+        Access::Write => create(),
+    }
+    .map_err(|cause| io_error(&lock_path, cause))?;
+    let start = Instant::now();
+
+    loop {
+        let locked = match access {
+            Access::Read => FileExt::try_lock_shared(&file),
+            Access::Write => FileExt::try_lock_exclusive(&file),
+        };
+
+        match locked {
+            Ok(()) => break,
+            Err(ref err) if err.kind() == fs2::lock_contended_error().kind() && start.elapsed() < timeout => {
+                thread::sleep(RETRY)
+            }
+            Err(ref err) if err.kind() == fs2::lock_contended_error().kind() => {
+                // A writer would refuse a shared lock too.
+                let holder = if access == Access::Write && FileExt::try_lock_shared(&file).is_ok() {
+                    let _ = FileExt::unlock(&file);
+                    "readers".to_owned()
+                } else {
+                    fs::read_to_string(&lock_path)
+                        .ok()
+                        .and_then(|s| s.trim().parse::<u32>().ok())
+                        .map(|pid| format!("process {}", pid))
+                        .unwrap_or_else(|| "another process".to_owned())
+                };
+
+                return Err(StoreError::Locked {
+                    path: path.display().to_string(),
+                    holder,
+                    timeout,
+                });
+            }
+            Err(cause) => return Err(io_error(&lock_path, cause)),
+        }
+    }
+
+    if access == Access::Write {
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(format!("{}\n", process::id()).as_bytes()))
+            .map_err(|cause| io_error(&lock_path, cause))?;
+    }
+
+    Ok(file)
+}
+
+fn io_error(path: &Path, cause: io::Error) -> StoreError {
+    StoreError::Io {
+        path: path.display().to_string(),
+        cause,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::sync::mpsc;
+    use value::Parse;
+
+    /// A fresh directory for the test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("sac-store-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn append(store: &mut Store, key: &str, json: &str) -> usize {
+        let timestamp = Timestamp::parse("2018-01-01T00:00:00Z").unwrap();
+        let blob = blob::from_json(json).unwrap();
+
+        store.append(Scope::User, key, timestamp, vec![blob]).unwrap()
+    }
+
+    /// Appends three entries and returns where each record ends, starting
+    /// with the empty log.
+    fn populate(dir: &TempDir) -> Vec<usize> {
+        let mut store = Store::init(&dir.0).unwrap();
+        store.set_sync(SyncPolicy::Never);
+        let mut ends = vec![0];
+
+        for &(key, json) in &[
+            ("GB", r#"{"name":"Britain"}"#),
+            ("FR", r#"{"name":"France"}"#),
+            ("GB", r#"{"name":"United Kingdom"}"#),
+        ] {
+            append(&mut store, key, json);
+            ends.push(store.len);
+        }
+
+        ends
+    }
+
+    #[test]
+    fn append_and_reopen() {
+        let dir = TempDir::new("reopen");
+        let mut store = Store::init(&dir.0).unwrap();
+
+        assert_eq!(append(&mut store, "GB", r#"{"name":"Britain"}"#), 1);
+        assert_eq!(append(&mut store, "FR", r#"{"name":"France"}"#), 2);
+        assert_eq!(append(&mut store, "GB", r#"{"name":"United Kingdom"}"#), 3);
+
+        drop(store);
+        let store = Store::open(&dir.0).unwrap();
+        let (n, entry) = store.latest(Scope::User, "GB").unwrap();
+
+        assert_eq!(n, 3);
+        assert_eq!(
+            blob::to_json(&store.item(&entry.hashes[0]).unwrap()).unwrap(),
+            r#"{"name":"United Kingdom"}"#
+        );
+        assert_eq!(store.entries().len(), 3);
+        assert!(store.latest(Scope::System, "GB").is_none());
+    }
+
+    #[test]
+    fn rebuild_stale_index() {
+        let dir = TempDir::new("stale");
+        let mut store = Store::init(&dir.0).unwrap();
+        append(&mut store, "GB", r#"{"name":"Britain"}"#);
+        drop(store);
+
+        for stale in &[
+            "{}",
+            r#"{"entries":1,"user":{"GB":0},"system":{}}"#,
+            r#"{"entries":1,"user":{"GB":5},"system":{}}"#,
+        ] {
+            fs::write(dir.0.join(INDEX), stale).unwrap();
+            let store = Store::open(&dir.0).unwrap();
+
+            assert_eq!(store.latest(Scope::User, "GB").map(|(n, _)| n), Some(1));
+            assert_eq!(
+                fs::read_to_string(dir.0.join(INDEX)).unwrap(),
+                r#"{"entries":1,"user":{"GB":1},"system":{}}"#
+            );
+        }
+    }
+
+    #[test]
+    fn items_are_shared() {
+        let dir = TempDir::new("shared");
+        let mut store = Store::init(&dir.0).unwrap();
+        append(&mut store, "GB", r#"{"name":"Britain"}"#);
+        append(&mut store, "UK", r#"{"name":"Britain"}"#);
+
+        assert_eq!(fs::read_dir(dir.0.join(ITEMS)).unwrap().count(), 1);
+        assert_eq!(store.to_rsf().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn detect_corrupt_items() {
+        let dir = TempDir::new("corrupt");
+        let mut store = Store::init(&dir.0).unwrap();
+        append(&mut store, "GB", r#"{"name":"Britain"}"#);
+        let hash = store.entries()[0].hashes[0].clone();
+        fs::write(store.item_path(hash.bytes()), r#"{"name":"France"}"#).unwrap();
+
+        let actual = store.item(&hash).map_err(|e| e.to_string());
+
+        assert!(actual.unwrap_err().starts_with("Corrupt item sha-256:"));
+    }
+
+    #[test]
+    fn refuse_existing_and_missing() {
+        let dir = TempDir::new("existing");
+        Store::init(&dir.0).unwrap();
+
+        assert!(Store::init(&dir.0).is_err());
+        assert!(Store::open(&dir.0.join("missing")).is_err());
+        assert_eq!(
+            Store::open(&dir.0).unwrap().append(
+                Scope::User,
+                "a\tb",
+                Timestamp::parse("2018-01-01T00:00:00Z").unwrap(),
+                vec![]
+            ).map_err(|e| e.to_string()),
+            Err(r#"Invalid key "a\tb". Keys can't be empty or have tabs or line breaks"#.to_owned())
+        );
+    }
+
+    #[test]
+    fn recover_from_truncation_at_every_offset() {
+        let dir = TempDir::new("truncation");
+        let ends = populate(&dir);
+        let log = dir.0.join(ENTRIES);
+        let bytes = fs::read(&log).unwrap();
+
+        for cut in 0..=bytes.len() {
+            fs::write(&log, &bytes[..cut]).unwrap();
+
+            let mut store = Store::open(&dir.0).unwrap();
+            let complete = ends.iter().filter(|&&end| end > 0 && end <= cut).count();
+            let valid = ends[complete];
+            let recovery = if cut == valid {
+                None
+            } else {
+                Some(Recovery {
+                    offset: valid,
+                    discarded: cut - valid,
+                    truncated: true,
+                })
+            };
+
+            assert_eq!(store.entries().len(), complete, "cut at {}", cut);
+            assert_eq!(store.recovery(), recovery.as_ref(), "cut at {}", cut);
+            assert_eq!(fs::metadata(&log).unwrap().len() as usize, valid, "cut at {}", cut);
+
+            store.set_sync(SyncPolicy::Never);
+            append(&mut store, "DE", r#"{"name":"Germany"}"#);
+
+            drop(store);
+            let store = Store::open(&dir.0).unwrap();
+            assert_eq!(store.entries().len(), complete + 1, "cut at {}", cut);
+            assert!(store.recovery().is_none());
+        }
+    }
+
+    #[test]
+    fn discard_torn_tail() {
+        let dir = TempDir::new("torn");
+        let ends = populate(&dir);
+        let log = dir.0.join(ENTRIES);
+        let bytes = fs::read(&log).unwrap();
+
+        let torn = &bytes[..bytes.len() - 1];
+        fs::write(&log, torn).unwrap();
+        let reader = Store::open_with(&dir.0, Access::Read, DEFAULT_TIMEOUT).unwrap();
+
+        assert_eq!(reader.entries().len(), 2);
+        assert_eq!(
+            reader.recovery().map(|r| r.to_string()),
+            Some(format!(
+                "Ignoring {} bytes of a partially written entry at byte {}",
+                ends[3] - ends[2] - 1,
+                ends[2]
+            ))
+        );
+        assert_eq!(fs::read(&log).unwrap(), torn);
+
+        drop(reader);
+        let store = Store::open(&dir.0).unwrap();
+
+        assert_eq!(store.entries().len(), 2);
+        assert_eq!(
+            store.recovery().map(|r| r.to_string()),
+            Some(format!(
+                "Discarded {} bytes of a partially written entry at byte {}",
+                ends[3] - ends[2] - 1,
+                ends[2]
+            ))
+        );
+
+        let mut zeroed = bytes.clone();
+        zeroed.extend_from_slice(&[0; 100]);
+        fs::write(&log, &zeroed).unwrap();
+        drop(store);
+        let store = Store::open(&dir.0).unwrap();
+
+        assert_eq!(store.entries().len(), 3);
+        assert_eq!(
+            store.recovery(),
+            Some(&Recovery {
+                offset: ends[3],
+                discarded: 100,
+                truncated: true,
+            })
+        );
+    }
+
+    #[test]
+    fn refuse_corruption_before_the_tail() {
+        let dir = TempDir::new("corrupt-log");
+        let ends = populate(&dir);
+        let log = dir.0.join(ENTRIES);
+        let bytes = fs::read(&log).unwrap();
+        let corrupt = |at: usize| {
+            let mut corrupt = bytes.clone();
+            corrupt[at] ^= 1;
+            corrupt
+        };
+        let text = b"append-entry\tuser\tGB\t2018-01-01T00:00:00Z\tsha-256:abc\n".to_vec();
+
+        for &(ref corrupt, offset) in &[
+            (corrupt(HEADER + 1), 0),
+            (corrupt(3), 0),
+            (corrupt(bytes.len() - 1), ends[2]),
+            (text, 0),
+        ] {
+            fs::write(&log, corrupt).unwrap();
+
+            let actual = Store::open(&dir.0).map(|_| ()).map_err(|e| e.to_string());
+
+            assert_eq!(actual, Err(format!("The entry log is corrupt at byte {}", offset)));
+            assert_eq!(&fs::read(&log).unwrap(), corrupt);
+        }
+    }
+
+    #[test]
+    fn refuse_other_commands() {
+        let dir = TempDir::new("other-command");
+        populate(&dir);
+        let log = dir.0.join(ENTRIES);
+        let mut bytes = fs::read(&log).unwrap();
+        bytes.extend(frame(b"assert-root-hash\tsha-256:abc"));
+        fs::write(&log, &bytes).unwrap();
+
+        let actual = Store::open(&dir.0).map(|_| ()).map_err(|e| e.to_string());
+
+        assert_eq!(
+            actual,
+            Err("Line 4: unexpected assert-root-hash in the entry log. Only append-entry is allowed".to_owned())
+        );
+    }
+
+    #[test]
+    fn writers_exclude_everyone() {
+        let dir = TempDir::new("lock-write");
+        let store = Store::init(&dir.0).unwrap();
+        let timeout = Duration::from_millis(50);
+
+        for &access in &[Access::Write, Access::Read] {
+            let actual = Store::open_with(&dir.0, access, timeout)
+                .map(|_| ())
+                .map_err(|e| e.to_string());
+
+            assert_eq!(
+                actual,
+                Err(format!(
+                    "Timed out after 50ms waiting for {} locked by process {}",
+                    dir.0.display(),
+                    process::id()
+                ))
+            );
+        }
+
+        drop(store);
+        assert!(Store::open_with(&dir.0, Access::Write, timeout).is_ok());
+    }
+
+    #[test]
+    fn readers_share() {
+        let dir = TempDir::new("lock-read");
+        drop(Store::init(&dir.0).unwrap());
+        let timeout = Duration::from_millis(50);
+
+        let a = Store::open_with(&dir.0, Access::Read, timeout).unwrap();
+        let mut b = Store::open_with(&dir.0, Access::Read, timeout).unwrap();
+
+        assert_eq!(fs::read(dir.0.join(LOCK)).unwrap(), b"");
+
+        assert_eq!(
+            Store::open_with(&dir.0, Access::Write, timeout)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(format!(
+                "Timed out after 50ms waiting for {} locked by readers",
+                dir.0.display()
+            ))
+        );
+        assert_eq!(
+            b.append(
+                Scope::User,
+                "GB",
+                Timestamp::parse("2018-01-01T00:00:00Z").unwrap(),
+                vec![]
+            ).map_err(|e| e.to_string()),
+            Err(format!("{} is open for reading only", dir.0.display()))
+        );
+
+        drop(a);
+        drop(b);
+        assert!(Store::open_with(&dir.0, Access::Write, timeout).is_ok());
+    }
+
+    #[test]
+    fn wait_for_the_writer() {
+        let dir = TempDir::new("lock-wait");
+        let mut store = Store::init(&dir.0).unwrap();
+        let (locked, ready) = mpsc::channel();
+
+        let writer = thread::spawn(move || {
+            locked.send(()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            append(&mut store, "GB", r#"{"name":"Britain"}"#);
+        });
+        ready.recv().unwrap();
+
+        let reader = Store::open_with(&dir.0, Access::Read, DEFAULT_TIMEOUT).unwrap();
+
+        assert_eq!(reader.entries().len(), 1);
+        writer.join().unwrap();
+    }
+}
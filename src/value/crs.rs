@@ -0,0 +1,511 @@
+// Copyright 2018 Arnau Siches
+
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Coordinate reference systems.
+//!
+//! Geometries can optionally declare the spatial reference system they are
+//! expressed in via an SRID as done by EWKT (Extended Well-Known Text):
+//!
+//! ```text
+//! SRID=4326;POINT (-0.1246 51.5007)
+//! SRID=27700;POINT (530268 179640)
+//! ```
+
+use regex::Regex;
+use std::f64::consts::PI;
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+
+use super::diagnostic::{Diagnose, Diagnostic, Expected, Scanner};
+
+#[derive(Debug, Fail)]
+pub enum CrsError {
+    #[fail(display = "Invalid SRID. Expected a positive integer such as SRID=4326.")]
+    InvalidSrid,
+    #[fail(display = "Missing SRID. The geometry must declare its reference system such as SRID=4326.")]
+    MissingSrid,
+    #[fail(display = "Coordinate ({} {}) is out of bounds for EPSG:{}.", x, y, srid)]
+    OutOfBounds { x: f64, y: f64, srid: u32 },
+    #[fail(display = "Unsupported transformation from EPSG:{} to EPSG:{}.", from, to)]
+    UnsupportedTransform { from: u32, to: u32 },
+    #[fail(display = "Geodesic distance failed to converge. Points may be nearly antipodal.")]
+    NoConvergence,
+}
+
+/// A Spatial Reference System Identifier as defined by the EPSG registry.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Srid(u32);
+
+impl Srid {
+    /// WGS 84. Coordinates are expressed as `longitude latitude`.
+    pub const WGS84: Srid = Srid(4326);
+
+    /// OSGB 1936 / British National Grid. Coordinates are expressed as
+    /// `easting northing`.
+    pub const BNG: Srid = Srid(27700);
+
+    /// Codes start at 1.
+    pub fn new(code: u32) -> Result<Self, CrsError> {
+        if code > 0 {
+            Ok(Srid(code))
+        } else {
+            Err(CrsError::InvalidSrid)
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        self.0
+    }
+
+    /// Checks the given coordinate is within the valid bounds of the
+    /// reference system. Only EPSG:4326 and the extent of the EPSG:27700 grid
+    /// are checked; any other system is accepted as is.
+    pub fn check(&self, x: f64, y: f64) -> Result<(), CrsError> {
+        let within = match *self {
+            Srid::WGS84 => (-180.0..=180.0).contains(&x) && (-90.0..=90.0).contains(&y),
+            Srid::BNG => (0.0..=700_000.0).contains(&x) && (0.0..=1_300_000.0).contains(&y),
+            _ => true,
+        };
+
+        if within {
+            Ok(())
+        } else {
+            Err(CrsError::OutOfBounds {
+                x,
+                y,
+                srid: self.0,
+            })
+        }
+    }
+}
+
+impl Debug for Srid {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Srid({})", self.0)
+    }
+}
+
+impl Display for Srid {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0, formatter)
+    }
+}
+
+impl FromStr for Srid {
+    type Err = CrsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u32>()
+            .map_err(|_| CrsError::InvalidSrid)
+            .and_then(Srid::new)
+    }
+}
+
+/// Splits an EWKT string into its SRID, if any, and the WKT remainder.
+///
+/// ```
+/// use sac::value::crs::{split_ewkt, Srid};
+/// let (srid, wkt) = split_ewkt("SRID=4326;POINT (0 0)").unwrap();
+/// assert_eq!(srid, Some(Srid::WGS84));
+/// assert_eq!(wkt, "POINT (0 0)");
+/// ```
+pub fn split_ewkt(s: &str) -> Result<(Option<Srid>, &str), CrsError> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^SRID=([^;]*);").unwrap();
+    }
+
+    match RE.captures(s) {
+        Some(caps) => {
+            let srid = caps[1].parse::<Srid>()?;
+            let rest = &s[caps.get(0).unwrap().end()..];
+
+            Ok((Some(srid), rest))
+        }
+        None => Ok((None, s)),
+    }
+}
+
+/// Transforms a coordinate between two reference systems.
+///
+/// Only EPSG:4326 and EPSG:27700 are supported. The transformation uses the
+/// seven-parameter Helmert transformation published by the Ordnance Survey
+/// which is accurate to about 5 metres. Use OSTN15 when better accuracy is
+/// required.
+pub fn transform(x: f64, y: f64, from: Srid, to: Srid) -> Result<(f64, f64), CrsError> {
+    from.check(x, y)?;
+
+    match (from, to) {
+        (a, b) if a == b => Ok((x, y)),
+        (Srid::WGS84, Srid::BNG) => {
+            let (lat, lon) = helmert(y.to_radians(), x.to_radians(), &WGS84, &AIRY1830, &TO_OSGB36);
+            Ok(to_grid(lat, lon))
+        }
+        (Srid::BNG, Srid::WGS84) => {
+            let (lat, lon) = from_grid(x, y);
+            let (lat, lon) = helmert(lat, lon, &AIRY1830, &WGS84, &TO_OSGB36.inverse());
+            Ok((lon.to_degrees(), lat.to_degrees()))
+        }
+        (a, b) => Err(CrsError::UnsupportedTransform {
+            from: a.code(),
+            to: b.code(),
+        }),
+    }
+}
+
+/// Mean Earth radius in metres as defined by the IUGG.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great-circle distance in metres between two WGS84 coordinates expressed as
+/// `longitude latitude` degrees. It assumes a spherical Earth so expect
+/// errors up to 0.5%.
+pub fn haversine(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    let (phi1, phi2) = (y1.to_radians(), y2.to_radians());
+    let dphi = (y2 - y1).to_radians();
+    let dlambda = (x2 - x1).to_radians();
+
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+/// Geodesic distance in metres between two WGS84 coordinates expressed as
+/// `longitude latitude` degrees using the Vincenty inverse formula on the
+/// WGS84 ellipsoid.
+pub fn vincenty(x1: f64, y1: f64, x2: f64, y2: f64) -> Result<f64, CrsError> {
+    let (a, b) = (WGS84.a, WGS84.b);
+    let f = (a - b) / a;
+    let l = (x2 - x1).to_radians();
+    let u1 = ((1.0 - f) * y1.to_radians().tan()).atan();
+    let u2 = ((1.0 - f) * y2.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+            .sqrt();
+
+        if sin_sigma == 0.0 {
+            // Coincident points.
+            return Ok(0.0);
+        }
+
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        let cos_2sigma_m = if cos2_alpha == 0.0 {
+            // Equatorial line.
+            0.0
+        } else {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+        };
+        let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+        let prev = lambda;
+        lambda = l
+            + (1.0 - c) * f * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+        if (lambda - prev).abs() < 1e-12 {
+            let u2 = cos2_alpha * (a * a - b * b) / (b * b);
+            let aa = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+            let bb = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+            let delta_sigma = bb * sin_sigma
+                * (cos_2sigma_m
+                    + bb / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - bb / 6.0 * cos_2sigma_m * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+
+            return Ok(b * aa * (sigma - delta_sigma));
+        }
+    }
+
+    Err(CrsError::NoConvergence)
+}
+
+struct Ellipsoid {
+    a: f64,
+    b: f64,
+}
+
+impl Ellipsoid {
+    fn e2(&self) -> f64 {
+        1.0 - (self.b * self.b) / (self.a * self.a)
+    }
+}
+
+const WGS84: Ellipsoid = Ellipsoid {
+    a: 6_378_137.0,
+    b: 6_356_752.314_245,
+};
+
+const AIRY1830: Ellipsoid = Ellipsoid {
+    a: 6_377_563.396,
+    b: 6_356_256.909,
+};
+
+/// Helmert parameters. Translations in metres, scale in ppm and rotations in
+/// arc seconds.
+struct Helmert {
+    tx: f64,
+    ty: f64,
+    tz: f64,
+    s: f64,
+    rx: f64,
+    ry: f64,
+    rz: f64,
+}
+
+impl Helmert {
+    fn inverse(&self) -> Helmert {
+        Helmert {
+            tx: -self.tx,
+            ty: -self.ty,
+            tz: -self.tz,
+            s: -self.s,
+            rx: -self.rx,
+            ry: -self.ry,
+            rz: -self.rz,
+        }
+    }
+}
+
+const TO_OSGB36: Helmert = Helmert {
+    tx: -446.448,
+    ty: 125.157,
+    tz: -542.060,
+    s: 20.4894,
+    rx: -0.1502,
+    ry: -0.2470,
+    rz: -0.8421,
+};
+
+fn helmert(lat: f64, lon: f64, from: &Ellipsoid, to: &Ellipsoid, params: &Helmert) -> (f64, f64) {
+    // Geodetic to cartesian, assuming zero ellipsoidal height.
+    let e2 = from.e2();
+    let nu = from.a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    let x1 = nu * lat.cos() * lon.cos();
+    let y1 = nu * lat.cos() * lon.sin();
+    let z1 = (1.0 - e2) * nu * lat.sin();
+
+    let sec = PI / (180.0 * 3600.0);
+    let s = params.s * 1e-6;
+    let (rx, ry, rz) = (params.rx * sec, params.ry * sec, params.rz * sec);
+
+    let x2 = params.tx + (1.0 + s) * x1 - rz * y1 + ry * z1;
+    let y2 = params.ty + rz * x1 + (1.0 + s) * y1 - rx * z1;
+    let z2 = params.tz - ry * x1 + rx * y1 + (1.0 + s) * z1;
+
+    // Cartesian to geodetic.
+    let e2 = to.e2();
+    let p = (x2 * x2 + y2 * y2).sqrt();
+    let mut phi = z2.atan2(p * (1.0 - e2));
+    loop {
+        let nu = to.a / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+        let next = (z2 + e2 * nu * phi.sin()).atan2(p);
+        if (next - phi).abs() < 1e-12 {
+            phi = next;
+            break;
+        }
+        phi = next;
+    }
+
+    (phi, y2.atan2(x2))
+}
+
+// National Grid projection constants.
+const F0: f64 = 0.999_601_271_7;
+const LAT0: f64 = 49.0;
+const LON0: f64 = -2.0;
+const E0: f64 = 400_000.0;
+const N0: f64 = -100_000.0;
+
+fn meridional_arc(phi: f64) -> f64 {
+    let (a, b) = (AIRY1830.a, AIRY1830.b);
+    let n = (a - b) / (a + b);
+    let (n2, n3) = (n * n, n * n * n);
+    let phi0 = LAT0.to_radians();
+    let dp = phi - phi0;
+    let sp = phi + phi0;
+
+    b * F0
+        * ((1.0 + n + 1.25 * n2 + 1.25 * n3) * dp
+            - (3.0 * n + 3.0 * n2 + 2.625 * n3) * dp.sin() * sp.cos()
+            + (1.875 * n2 + 1.875 * n3) * (2.0 * dp).sin() * (2.0 * sp).cos()
+            - (35.0 / 24.0) * n3 * (3.0 * dp).sin() * (3.0 * sp).cos())
+}
+
+/// Projects OSGB36 latitude and longitude (radians) to easting and northing.
+fn to_grid(phi: f64, lambda: f64) -> (f64, f64) {
+    let a = AIRY1830.a;
+    let e2 = AIRY1830.e2();
+    let (sin, cos, tan) = (phi.sin(), phi.cos(), phi.tan());
+    let nu = a * F0 / (1.0 - e2 * sin * sin).sqrt();
+    let rho = a * F0 * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+    let eta2 = nu / rho - 1.0;
+    let (tan2, tan4) = (tan * tan, tan.powi(4));
+
+    let i = meridional_arc(phi) + N0;
+    let ii = nu / 2.0 * sin * cos;
+    let iii = nu / 24.0 * sin * cos.powi(3) * (5.0 - tan2 + 9.0 * eta2);
+    let iiia = nu / 720.0 * sin * cos.powi(5) * (61.0 - 58.0 * tan2 + tan4);
+    let iv = nu * cos;
+    let v = nu / 6.0 * cos.powi(3) * (nu / rho - tan2);
+    let vi = nu / 120.0 * cos.powi(5)
+        * (5.0 - 18.0 * tan2 + tan4 + 14.0 * eta2 - 58.0 * tan2 * eta2);
+
+    let dl = lambda - LON0.to_radians();
+    let n = i + ii * dl.powi(2) + iii * dl.powi(4) + iiia * dl.powi(6);
+    let e = E0 + iv * dl + v * dl.powi(3) + vi * dl.powi(5);
+
+    (e, n)
+}
+
+/// Unprojects easting and northing to OSGB36 latitude and longitude (radians).
+fn from_grid(e: f64, n: f64) -> (f64, f64) {
+    let a = AIRY1830.a;
+    let e2 = AIRY1830.e2();
+
+    let mut phi = (n - N0) / (a * F0) + LAT0.to_radians();
+    let mut m = meridional_arc(phi);
+    while (n - N0 - m).abs() >= 0.000_01 {
+        phi += (n - N0 - m) / (a * F0);
+        m = meridional_arc(phi);
+    }
+
+    let (sin, cos, tan) = (phi.sin(), phi.cos(), phi.tan());
+    let sec = 1.0 / cos;
+    let nu = a * F0 / (1.0 - e2 * sin * sin).sqrt();
+    let rho = a * F0 * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+    let eta2 = nu / rho - 1.0;
+    let (tan2, tan4, tan6) = (tan * tan, tan.powi(4), tan.powi(6));
+
+    let vii = tan / (2.0 * rho * nu);
+    let viii = tan / (24.0 * rho * nu.powi(3)) * (5.0 + 3.0 * tan2 + eta2 - 9.0 * tan2 * eta2);
+    let ix = tan / (720.0 * rho * nu.powi(5)) * (61.0 + 90.0 * tan2 + 45.0 * tan4);
+    let x = sec / nu;
+    let xi = sec / (6.0 * nu.powi(3)) * (nu / rho + 2.0 * tan2);
+    let xii = sec / (120.0 * nu.powi(5)) * (5.0 + 28.0 * tan2 + 24.0 * tan4);
+    let xiia = sec / (5040.0 * nu.powi(7)) * (61.0 + 662.0 * tan2 + 1320.0 * tan4 + 720.0 * tan6);
+
+    let de = e - E0;
+    let lat = phi - vii * de.powi(2) + viii * de.powi(4) - ix * de.powi(6);
+    let lon = LON0.to_radians() + x * de - xi * de.powi(3) + xii * de.powi(5) - xiia * de.powi(7);
+
+    (lat, lon)
+}
+
+impl Diagnose for CrsError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            CrsError::InvalidSrid => Diagnostic::new(input, "SRID=".len(), "positive integer", self.to_string()),
+            CrsError::MissingSrid => Diagnostic::new(input, 0, "SRID", self.to_string()),
+            CrsError::OutOfBounds { srid, .. } => {
+                let offset = input.rfind('(').map_or(0, |i| i + 1);
+                let expected = format!("coordinates within the bounds of EPSG:{}", srid);
+                Diagnostic::new(input, offset, &expected, self.to_string())
+            }
+            _ => Diagnostic::new(input, 0, "geometry", self.to_string()),
+        }
+    }
+}
+
+/// Checks the optional `SRID=<code>;` prefix of an EWKT string.
+pub(crate) fn scan_srid(s: &mut Scanner) -> Result<(), Expected> {
+    if s.eat('S') {
+        s.literal("RID=")?;
+        s.digits(1, usize::MAX)?;
+        s.literal(";")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_without_srid() {
+        let (srid, wkt) = split_ewkt("POINT (0 0)").unwrap();
+
+        assert_eq!(srid, None);
+        assert_eq!(wkt, "POINT (0 0)");
+    }
+
+    #[test]
+    fn split_invalid_srid() {
+        assert!(split_ewkt("SRID=abc;POINT (0 0)").is_err());
+        assert!(split_ewkt("SRID=0;POINT (0 0)").is_err());
+        assert!(Srid::new(0).is_err());
+    }
+
+    #[test]
+    fn check_wgs84_bounds() {
+        assert!(Srid::WGS84.check(-180.0, 90.0).is_ok());
+        assert!(Srid::WGS84.check(181.0, 0.0).is_err());
+        assert!(Srid::WGS84.check(0.0, -91.0).is_err());
+    }
+
+    #[test]
+    fn check_bng_bounds() {
+        assert!(Srid::BNG.check(530268.0, 179640.0).is_ok());
+        assert!(Srid::BNG.check(-1.0, 0.0).is_err());
+        assert!(Srid::BNG.check(0.0, 1e20).is_err());
+        assert!(transform(0.0, 1e20, Srid::BNG, Srid::WGS84).is_err());
+    }
+
+    #[test]
+    fn grid_projection() {
+        // Worked example from "A guide to coordinate systems in Great Britain".
+        let lat = (52.0 + 39.0 / 60.0 + 27.2531 / 3600.0_f64).to_radians();
+        let lon = (1.0 + 43.0 / 60.0 + 4.5177 / 3600.0_f64).to_radians();
+        let (e, n) = to_grid(lat, lon);
+
+        assert!((e - 651_409.903).abs() < 0.001, "easting {}", e);
+        assert!((n - 313_177.270).abs() < 0.001, "northing {}", n);
+
+        let (lat2, lon2) = from_grid(e, n);
+        assert!((lat - lat2).abs() < 1e-9);
+        assert!((lon - lon2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn transform_roundtrip() {
+        let (e, n) = transform(-0.124_625, 51.500_729, Srid::WGS84, Srid::BNG).unwrap();
+
+        assert!((e - 530_268.0).abs() < 10.0, "easting {}", e);
+        assert!((n - 179_640.0).abs() < 10.0, "northing {}", n);
+
+        let (x, y) = transform(e, n, Srid::BNG, Srid::WGS84).unwrap();
+        assert!((x + 0.124_625).abs() < 1e-5, "longitude {}", x);
+        assert!((y - 51.500_729).abs() < 1e-5, "latitude {}", y);
+    }
+
+    #[test]
+    fn geodesic_distance() {
+        // Flinders Peak to Buninyong, the classic Vincenty test case.
+        let (x1, y1) = (144.0 + 25.0 / 60.0 + 29.52440 / 3600.0, -(37.0 + 57.0 / 60.0 + 3.72030 / 3600.0));
+        let (x2, y2) = (143.0 + 55.0 / 60.0 + 35.38390 / 3600.0, -(37.0 + 39.0 / 60.0 + 10.15610 / 3600.0));
+
+        let d = vincenty(x1, y1, x2, y2).unwrap();
+        assert!((d - 54_972.271).abs() < 0.01, "vincenty {}", d);
+
+        let h = haversine(x1, y1, x2, y2);
+        assert!((h - d).abs() / d < 0.005, "haversine {}", h);
+
+        assert_eq!(vincenty(x1, y1, x1, y1).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn transform_unsupported() {
+        assert!(transform(0.0, 0.0, Srid::WGS84, Srid::new(3857).unwrap()).is_err());
+    }
+}
@@ -5,25 +5,45 @@
 // according to those terms.
 
 use regex::Regex;
+use serde_json;
+use toml;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::fmt::{self, Debug, Display};
-use super::Parse;
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
+use super::url::{Url, UrlError};
+use super::{Parse, Value};
+use field::Fieldname;
+use register::Register;
 
 #[derive(Debug, Fail)]
 pub enum CurieError {
-    #[fail(display = "Invalid prefix. Ensure the prefix conforms to [a-z][a-z_-]+.")]
+    #[fail(display = "Invalid prefix. Ensure the prefix conforms to [a-z][a-z0-9-]+.")]
     InvalidPrefix,
-    #[fail(display = "Invalid reference. Ensure there are no reserved characters.")]
+    #[fail(display = "Invalid reference. Ensure the reference is a relative IRI as defined by RFC3987.")]
     InvalidReference,
     #[fail(display = "Unexpected CURIE pattern. A CURIE must be <prefix>:<reference>")]
     ParseError,
+    #[fail(display = "Unknown prefix {}. Ensure the prefix map declares it.", _0)]
+    UnknownPrefix(String),
+    #[fail(display = "Invalid prefix map. {}", _0)]
+    InvalidPrefixMap(String),
+    #[fail(display = "The CURIE does not expand to a valid Url")]
+    InvalidExpansion(#[cause] UrlError),
+}
+
+impl From<UrlError> for CurieError {
+    fn from(err: UrlError) -> CurieError {
+        CurieError::InvalidExpansion(err)
+    }
 }
 
 /// A restricted version of a CURIE defined by the W3C.
 ///
 /// TODO: Might be a good idea to rename this to `cref` (compact reference) or
 /// similar.
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Curie {
     prefix: Prefix,
     reference: Reference,
@@ -68,14 +88,128 @@ impl Curie {
             reference: reference,
         }
     }
+
+    pub fn prefix(&self) -> &Prefix {
+        &self.prefix
+    }
+
+    pub fn reference(&self) -> &Reference {
+        &self.reference
+    }
+}
+
+/// A map from CURIE prefixes to the base Url they expand to.
+///
+/// ```
+/// use sac::value::Parse;
+/// use sac::value::curie::{Curie, PrefixMap};
+/// use sac::value::url::Url;
+/// let map = PrefixMap::from_json(r#"{"country": "https://country.register.gov.uk/records/"}"#).unwrap();
+/// let curie = Curie::parse("country:GB").unwrap();
+/// let url = map.expand(&curie).unwrap();
+/// assert_eq!(url.to_string(), "https://country.register.gov.uk/records/GB");
+/// assert_eq!(map.compact(&url), Some(curie));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefixMap(BTreeMap<Prefix, Url>);
+
+impl PrefixMap {
+    pub fn new() -> Self {
+        PrefixMap(BTreeMap::new())
+    }
+
+    /// Loads a prefix map from a JSON object of prefixes to base Urls.
+    pub fn from_json(s: &str) -> Result<Self, CurieError> {
+        let raw: BTreeMap<String, String> =
+            serde_json::from_str(s).map_err(|err| CurieError::InvalidPrefixMap(err.to_string()))?;
+
+        PrefixMap::from_pairs(raw)
+    }
+
+    /// Loads a prefix map from a TOML table of prefixes to base Urls such as
+    /// `country = "https://country.register.gov.uk/records/"`.
+    ///
+    /// ```
+    /// use sac::value::curie::{Prefix, PrefixMap};
+    /// let map = PrefixMap::from_toml(r#"country = "https://country.register.gov.uk/records/""#).unwrap();
+    /// assert!(map.get(&Prefix::new("country")).is_some());
+    /// ```
+    pub fn from_toml(s: &str) -> Result<Self, CurieError> {
+        let raw: BTreeMap<String, String> =
+            toml::from_str(s).map_err(|err| CurieError::InvalidPrefixMap(err.to_string()))?;
+
+        PrefixMap::from_pairs(raw)
+    }
+
+    /// Loads a prefix map from a register of prefixes. Each live record is
+    /// keyed by its prefix and has a `url` field with the base Url.
+    pub fn from_register(register: &Register) -> Result<Self, CurieError> {
+        let url = Fieldname::from_str("url").expect("valid fieldname");
+        let mut raw = BTreeMap::new();
+
+        for key in register.keys() {
+            let base = register
+                .record(&key)
+                .and_then(|(_, entry)| register.items(entry).into_iter().next())
+                .and_then(|blob| match blob.get(&url) {
+                    Some(Value::Untyped(s)) | Some(Value::String(s)) => Some(s.clone()),
+                    _ => None,
+                })
+                .ok_or_else(|| CurieError::InvalidPrefixMap(format!("{}: missing url", key)))?;
+            raw.insert(key, base);
+        }
+
+        PrefixMap::from_pairs(raw)
+    }
+
+    fn from_pairs(raw: BTreeMap<String, String>) -> Result<Self, CurieError> {
+        let mut map = PrefixMap::new();
+
+        for (prefix, base) in raw {
+            let base = Url::parse(&base)
+                .map_err(|err| CurieError::InvalidPrefixMap(format!("{}: {}", prefix, err)))?;
+            map.insert(Prefix::from_str(&prefix)?, base);
+        }
+
+        Ok(map)
+    }
+
+    pub fn insert(&mut self, prefix: Prefix, base: Url) -> Option<Url> {
+        self.0.insert(prefix, base)
+    }
+
+    pub fn get(&self, prefix: &Prefix) -> Option<&Url> {
+        self.0.get(prefix)
+    }
+
+    /// Expands the CURIE by appending its reference to the prefix base Url.
+    pub fn expand(&self, curie: &Curie) -> Result<Url, CurieError> {
+        let base = self.get(curie.prefix())
+            .ok_or_else(|| CurieError::UnknownPrefix(curie.prefix().to_string()))?;
+
+        Ok(Url::parse(&format!("{}{}", base, curie.reference()))?)
+    }
+
+    /// Compacts the Url using the longest matching base Url. Returns `None`
+    /// when no base matches or the remainder is not a valid reference.
+    pub fn compact(&self, url: &Url) -> Option<Curie> {
+        self.0
+            .iter()
+            .filter(|&(_, base)| url.as_str().starts_with(base.as_str()))
+            .max_by_key(|&(_, base)| base.as_str().len())
+            .and_then(|(prefix, base)| {
+                let reference = Reference::from_str(&url.as_str()[base.as_str().len()..]).ok()?;
+                Some(Curie::new(prefix.clone(), reference))
+            })
+    }
 }
 
 /// A CURIE prefix is a string conforming to NCName but in Registers it must
-/// conform to a valid Register ID: [a-z][a-z0-9-]
+/// conform to a valid Register ID: `[a-z][a-z0-9-]+`
 ///
 ///
 /// TODO: Ensure prefixes and register IDs are compatible.
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Prefix(String);
 
 impl Debug for Prefix {
@@ -106,30 +240,64 @@ impl Prefix {
         Prefix(s.into())
     }
 
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     pub fn is_valid(s: &str) -> bool {
         lazy_static! {
-        static ref RE: Regex = Regex::new(r#"[a-z][a-z0-9-]+"#).unwrap();
+        static ref RE: Regex = Regex::new(r#"^[a-z][a-z0-9-]+$"#).unwrap();
         }
 
         RE.is_match(s)
     }
 }
 
-/// A CURIE reference is a string conforming to irelative-ref in [RFC3987]
+/// A CURIE reference is a string conforming to irelative-ref in [RFC3987].
+///
+/// Identifiers are a subset of the above so references are classified as:
 ///
-/// TODO: Consider skipping RFC3987 and conform to https://url.spec.whatwg.org/
-#[derive(Clone, PartialEq)]
-pub struct Reference(String);
+/// * `Id`: a single segment of unreserved or percent-encoded characters
+///   (e.g. `foo:bar` ~> `https://foo.org/records/bar`).
+/// * `Facet`: an identifier name and value separated by `=` (e.g.
+///   `foo:type=bar` ~> `https://foo.org/records/type=bar`).
+/// * `Any`: any other relative reference (e.g. `foo:bar/far`).
+///
+/// ```
+/// use std::str::FromStr;
+/// use sac::value::curie::Reference;
+/// let reference = Reference::from_str("type=bar").unwrap();
+/// assert_eq!(reference.facet(), Some(("type", "bar")));
+/// assert_eq!(Reference::from_str("bar").unwrap().id(), Some("bar"));
+/// assert!(Reference::from_str("b ar").is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Reference {
+    Id(String),
+    Facet { name: String, value: String },
+    Any(String),
+}
 
-impl Debug for Reference {
+impl Display for Reference {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(&self.0, formatter)
+        match *self {
+            Reference::Id(ref id) => Display::fmt(id, formatter),
+            Reference::Facet { ref name, ref value } => write!(formatter, "{}={}", name, value),
+            Reference::Any(ref s) => Display::fmt(s, formatter),
+        }
     }
 }
 
-impl Display for Reference {
-    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.0, formatter)
+/// References are ordered lexically as written.
+impl Ord for Reference {
+    fn cmp(&self, other: &Reference) -> Ordering {
+        self.to_string().cmp(&other.to_string())
+    }
+}
+
+impl PartialOrd for Reference {
+    fn partial_cmp(&self, other: &Reference) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -145,34 +313,197 @@ impl FromStr for Reference {
 }
 
 impl Reference {
+    /// Classifies the given string without validating it.
     pub fn new(s: &str) -> Self {
-        Reference(s.into())
-    }
-
-    // TODO: Validate conforms to RFC3987
-    //
-    //      reserved    = gen-delims / sub-delims
-    //      gen-delims  = ":" / "/" / "?" / "#" / "[" / "]" / "@"
-    //      sub-delims  = "!" / "$" / "&" / "'" / "(" / ")"
-    //      unreserved  = ALPHA / DIGIT / "-" / "." / "_" / "~"
-    //      pct-encoded = "%" HEXDIG HEXDIG
-    //
-    // Also, Identifiers are a subset of the above so a smarter reference
-    // implementation could identify different types of reference, such as:
-    //
-    //     enum Reference {
-    //       Any(String),   // foo:bar/far
-    //       Id(String),    // foo:bar ~> https://foo.org/records/bar (which could be seen as id=bar really
-    //       Facet(String), // foo:type=bar ~> https://foo.org/records/type=bar
-    //     }
+        if is_id(s) {
+            return Reference::Id(s.into());
+        }
+
+        match s.find('=') {
+            Some(i) if is_id(&s[..i]) && is_id(&s[i + 1..]) => Reference::Facet {
+                name: s[..i].into(),
+                value: s[i + 1..].into(),
+            },
+            _ => Reference::Any(s.into()),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        match *self {
+            Reference::Id(ref id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn facet(&self) -> Option<(&str, &str)> {
+        match *self {
+            Reference::Facet { ref name, ref value } => Some((name, value)),
+            _ => None,
+        }
+    }
+
+    /// Checks the string conforms to irelative-ref as defined by RFC3987:
+    ///
+    /// ```text
+    /// irelative-ref  = irelative-part [ "?" iquery ] [ "#" ifragment ]
+    /// irelative-part = "//" iauthority ipath-abempty
+    ///                / ipath-absolute
+    ///                / ipath-noscheme
+    ///                / ipath-empty
+    /// ipchar         = iunreserved / pct-encoded / sub-delims / ":" / "@"
+    /// iunreserved    = ALPHA / DIGIT / "-" / "." / "_" / "~" / ucschar
+    /// sub-delims     = "!" / "$" / "&" / "'" / "(" / ")"
+    ///                / "*" / "+" / "," / ";" / "="
+    /// pct-encoded    = "%" HEXDIG HEXDIG
+    /// ```
+    ///
+    /// The authority is checked as a sequence of ipchar, "[" and "]".
     pub fn is_valid(s: &str) -> bool {
-        !s.ends_with(':')
+        let (rest, fragment) = split_at_char(s, '#');
+        let (path, query) = split_at_char(rest, '?');
+
+        let query_ok = query.map_or(true, |q| is_valid_component(q, &['/', '?'], true));
+        let fragment_ok = fragment.map_or(true, |f| is_valid_component(f, &['/', '?'], false));
+
+        query_ok && fragment_ok && is_valid_relative_part(path)
+    }
+}
+
+const SUB_DELIMS: &str = "!$&'()*+,;=";
+
+fn split_at_char(s: &str, c: char) -> (&str, Option<&str>) {
+    match s.find(c) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    }
+}
+
+fn is_valid_relative_part(s: &str) -> bool {
+    if let Some(rest) = s.strip_prefix("//") {
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+
+        is_valid_component(authority, &['[', ']'], false) && is_valid_component(path, &['/'], false)
+    } else {
+        // ipath-noscheme: the first segment cannot contain a colon.
+        let first = s.split('/').next().unwrap_or("");
+
+        !first.contains(':') && is_valid_component(s, &['/'], false)
+    }
+}
+
+/// Checks every character is an ipchar, one of the extra characters or, when
+/// `private` is set, an iprivate character.
+fn is_valid_component(s: &str, extra: &[char], private: bool) -> bool {
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        let valid = if c == '%' {
+            chars.next().is_some_and(|h| h.is_ascii_hexdigit())
+                && chars.next().is_some_and(|h| h.is_ascii_hexdigit())
+        } else {
+            is_iunreserved(c)
+                || SUB_DELIMS.contains(c)
+                || c == ':'
+                || c == '@'
+                || extra.contains(&c)
+                || (private && is_iprivate(c))
+        };
+
+        if !valid {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Checks the string is made of unreserved and percent-encoded characters.
+fn is_id(s: &str) -> bool {
+    !s.is_empty() && !s.contains(|c| SUB_DELIMS.contains(c) || c == ':' || c == '@')
+        && is_valid_component(s, &[], false)
+}
+
+fn is_iunreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-._~".contains(c) || is_ucschar(c)
+}
+
+fn is_ucschar(c: char) -> bool {
+    let c = c as u32;
+
+    (0xA0..=0xD7FF).contains(&c)
+        || (0xF900..=0xFDCF).contains(&c)
+        || (0xFDF0..=0xFFEF).contains(&c)
+        || ((0x10000..0xE0000).contains(&c) && (c & 0xFFFF) <= 0xFFFD)
+        || (0xE1000..=0xEFFFD).contains(&c)
+}
+
+fn is_iprivate(c: char) -> bool {
+    let c = c as u32;
+
+    (0xE000..=0xF8FF).contains(&c)
+        || (0xF0000..=0xFFFFD).contains(&c)
+        || (0x100000..=0x10FFFD).contains(&c)
+}
+
+impl Diagnose for CurieError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            CurieError::UnknownPrefix(_) => return Diagnostic::new(input, 0, "declared prefix", self.to_string()),
+            CurieError::InvalidPrefixMap(_) | CurieError::InvalidExpansion(_) => {
+                return Diagnostic::new(input, 0, "CURIE", self.to_string())
+            }
+            _ => (),
+        }
+
+        let fixes = match input.trim().find(':') {
+            Some(i) => {
+                let s = input.trim();
+                vec![format!("{}:{}", s[..i].to_lowercase(), s[i + 1..].replace(' ', "%20"))]
+            }
+            None => vec![],
+        };
+
+        diagnostic::locate(input, scan(input), "CURIE", self.to_string())
+            .with_fix(diagnostic::first_valid(input, fixes, |s| Curie::parse(s).is_ok()))
+    }
+}
+
+/// Checks the prefix and blames the first character a relative IRI
+/// reference can never have.
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+
+    if !s.peek().is_some_and(|c| c.is_ascii_lowercase()) {
+        return Err(s.expected("lowercase letter"));
+    }
+    s.take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+
+    if s.offset() < 2 {
+        return Err(s.expected("lowercase letter, digit or -"));
+    }
+    if !s.eat(':') {
+        return Err(s.expected("lowercase letter, digit, - or :"));
+    }
+
+    let start = s.offset();
+    s.take_while(|c| {
+        is_iunreserved(c) || is_iprivate(c) || SUB_DELIMS.contains(c) || ":@/?#[]%".contains(c)
+    });
+
+    if s.is_end() {
+        Err(Expected::new(start, "relative IRI reference"))
+    } else {
+        Err(s.expected("IRI character"))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blob;
 
     #[test]
     fn from_str() {
@@ -204,6 +535,86 @@ mod tests {
         assert!(res.is_err(), "Expected curie to be an error");
     }
 
+    #[test]
+    fn from_str_wrong_prefix() {
+        let res = Curie::parse("Foo!:bar");
+
+        assert!(res.is_err(), "Expected curie to be an error");
+    }
+
+    #[test]
+    fn accessors() {
+        let curie = Curie::parse("country:GB").unwrap();
+
+        assert_eq!(curie.prefix().as_str(), "country");
+        assert_eq!(curie.reference().id(), Some("GB"));
+    }
+
+    #[test]
+    fn prefix_map_expands() {
+        let map = PrefixMap::from_json(r#"{"foo": "https://foo.org/records/"}"#).unwrap();
+        let actual = map.expand(&Curie::parse("foo:type=bar").unwrap());
+
+        assert_eq!(format!("{:?}", actual), r#"Ok("https://foo.org/records/type=bar")"#);
+    }
+
+    #[test]
+    fn prefix_map_unknown_prefix() {
+        let map = PrefixMap::new();
+        let actual = map.expand(&Curie::parse("foo:bar").unwrap());
+
+        assert_eq!(format!("{:?}", actual), r#"Err(UnknownPrefix("foo"))"#);
+    }
+
+    #[test]
+    fn prefix_map_compacts_longest_base() {
+        let map = PrefixMap::from_json(
+            r#"{"foo": "https://foo.org/", "bar": "https://foo.org/bar/"}"#,
+        ).unwrap();
+        let url = |s| Url::parse(s).unwrap();
+
+        assert_eq!(
+            map.compact(&url("https://foo.org/bar/1")),
+            Some(Curie::parse("bar:1").unwrap())
+        );
+        assert_eq!(
+            map.compact(&url("https://foo.org/qux")),
+            Some(Curie::parse("foo:qux").unwrap())
+        );
+        assert_eq!(map.compact(&url("https://example.org/qux")), None);
+    }
+
+    #[test]
+    fn prefix_map_rejects_invalid() {
+        assert!(PrefixMap::from_json(r#"{"Foo": "https://foo.org/"}"#).is_err());
+        assert!(PrefixMap::from_json(r#"{"foo": "foo.org"}"#).is_err());
+        assert!(PrefixMap::from_json(r#"["foo"]"#).is_err());
+        assert!(PrefixMap::from_toml(r#"foo = "foo.org""#).is_err());
+    }
+
+    #[test]
+    fn prefix_map_from_toml() {
+        let map = PrefixMap::from_toml("foo = \"https://foo.org/records/\"\n").unwrap();
+        let url = map.expand(&Curie::parse("foo:1").unwrap()).unwrap();
+
+        assert_eq!(url.to_string(), "https://foo.org/records/1");
+    }
+
+    #[test]
+    fn prefix_map_from_register() {
+        let blob = blob::from_json(r#"{"prefix":"foo","url":"https://foo.org/records/"}"#).unwrap();
+        let rsf = format!(
+            "add-item\t{}\nappend-entry\tuser\tfoo\t2018-01-01T00:00:00Z\t{}\n",
+            blob::to_json(&blob).unwrap(),
+            blob.id()
+        );
+        let register = Register::from_rsf(&rsf).unwrap();
+        let map = PrefixMap::from_register(&register).unwrap();
+        let url = map.expand(&Curie::parse("foo:1").unwrap()).unwrap();
+
+        assert_eq!(url.to_string(), "https://foo.org/records/1");
+    }
+
     mod prefix {
         use super::*;
         #[test]
@@ -213,6 +624,13 @@ mod tests {
                 "Expected prefix to be a valid str"
             );
         }
+
+        #[test]
+        fn invalid_with_surrounding_chars() {
+            for s in &["Foo!", "foo!", "-foo", "foo bar", "f"] {
+                assert!(Prefix::from_str(s).is_err(), "Expected {} to be invalid", s);
+            }
+        }
     }
 
     mod reference {
@@ -241,5 +659,35 @@ mod tests {
             );
         }
 
+        #[test]
+        fn kinds() {
+            assert_eq!(Reference::new("GB"), Reference::Id("GB".into()));
+            assert_eq!(Reference::new("caf%C3%A9"), Reference::Id("caf%C3%A9".into()));
+            assert_eq!(
+                Reference::new("type=bar"),
+                Reference::Facet {
+                    name: "type".into(),
+                    value: "bar".into(),
+                }
+            );
+            assert_eq!(Reference::new("bar/qux"), Reference::Any("bar/qux".into()));
+            assert_eq!(Reference::new("a=b=c"), Reference::Any("a=b=c".into()));
+            assert_eq!(Reference::new(""), Reference::Any("".into()));
+        }
+
+        #[test]
+        fn valid_relative_refs() {
+            for s in &["bar/qux:1", "/abs", "//host/path", "?q=1", "#frag", "a?b/c?#d", "ü", "a%2F"] {
+                assert!(Reference::is_valid(s), "Expected {} to be valid", s);
+            }
+        }
+
+        #[test]
+        fn invalid_relative_refs() {
+            for s in &["bar:", "a b", "a<b", "a%2", "a%zz", "a#b#c", "a\\b", "[x]", "\u{E000}"] {
+                assert!(!Reference::is_valid(s), "Expected {} to be invalid", s);
+            }
+        }
+
     }
 }
@@ -0,0 +1,309 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Datatypes defined outside the crate.
+//!
+//! Any type implementing `Parse` and `Display` can be registered under a
+//! name. From then on the name is a `Kind` like any other: it can be used in
+//! field definitions, with `Value::parse` and in the `--type` of the command
+//! line. Values keep the canonical form given by `Display`.
+//!
+//! Datatypes can also be defined by a regular expression with
+//! `register_pattern`, which is how the `sac` binary loads the datatypes
+//! listed in the file named by `SAC_DATATYPES`.
+//!
+//! ```
+//! use std::fmt::{self, Display};
+//! use sac::kind::Kind;
+//! use sac::value::{custom, Parse, Value};
+//!
+//! struct Postcode(String);
+//!
+//! impl Parse for Postcode {
+//!     type Err = String;
+//!     fn parse(s: &str) -> Result<Self, Self::Err> {
+//!         let s = s.trim().to_uppercase();
+//!         if s.len() >= 5 && s.contains(' ') {
+//!             Ok(Postcode(s))
+//!         } else {
+//!             Err(format!("Invalid postcode {}", s))
+//!         }
+//!     }
+//! }
+//!
+//! impl Display for Postcode {
+//!     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+//!         Display::fmt(&self.0, formatter)
+//!     }
+//! }
+//!
+//! custom::register::<Postcode>("postcode").unwrap();
+//!
+//! let kind: Kind = "postcode".parse().unwrap();
+//! let value = Value::parse("sw1a 1aa", kind).unwrap();
+//! assert_eq!(value.to_string(), "SW1A 1AA");
+//! ```
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use super::diagnostic::{Diagnose, Diagnostic};
+use super::Parse;
+use kind::Kind;
+
+#[derive(Debug, Fail)]
+pub enum CustomError {
+    #[fail(display = "Unknown datatype {}", _0)]
+    UnknownDatatype(String),
+    #[fail(display = "The datatype name {} is reserved", _0)]
+    ReservedName(String),
+    #[fail(display = "Invalid datatype name {}. Expected [a-z][a-z0-9-]*", _0)]
+    InvalidName(String),
+    #[fail(display = "Invalid pattern for datatype {}. {}", name, reason)]
+    InvalidPattern { name: String, reason: String },
+    #[fail(display = "{}", message)]
+    Invalid { kind: String, message: String },
+}
+
+/// Parses a string into the canonical form of a registered datatype.
+type Canonicalise = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+lazy_static! {
+    static ref DATATYPES: RwLock<BTreeMap<String, Canonicalise>> = RwLock::new(BTreeMap::new());
+}
+
+fn canonicalise<T>(s: &str) -> Result<String, String>
+where
+    T: Parse + Display,
+    T::Err: Display,
+{
+    T::parse(s).map(|v| v.to_string()).map_err(|e| e.to_string())
+}
+
+/// Registers a datatype under the given name, replacing any datatype
+/// previously registered with it. Names of built-in kinds can't be used.
+pub fn register<T>(name: &str) -> Result<(), CustomError>
+where
+    T: Parse + Display,
+    T::Err: Display,
+{
+    let canonicalise: fn(&str) -> Result<String, String> = canonicalise::<T>;
+
+    insert(name, Arc::new(canonicalise))
+}
+
+/// Registers a datatype whose values are the strings fully matching the
+/// regular expression. Values are canonical as written.
+///
+/// ```
+/// use sac::kind::Kind;
+/// use sac::value::{custom, Value};
+/// custom::register_pattern("iso-country", "[A-Z]{2}").unwrap();
+/// assert!(Value::parse("GB", Kind::Custom("iso-country".into())).is_ok());
+/// assert!(Value::parse("gb", Kind::Custom("iso-country".into())).is_err());
+/// ```
+pub fn register_pattern(name: &str, pattern: &str) -> Result<(), CustomError> {
+    let re = Regex::new(&format!("^(?:{})$", pattern)).map_err(|err| CustomError::InvalidPattern {
+        name: name.to_owned(),
+        reason: err.to_string(),
+    })?;
+    let message = format!("Expected a value matching {}", pattern);
+
+    insert(
+        name,
+        Arc::new(move |s: &str| {
+            if re.is_match(s) {
+                Ok(s.to_owned())
+            } else {
+                Err(message.clone())
+            }
+        }),
+    )
+}
+
+fn insert(name: &str, canonicalise: Canonicalise) -> Result<(), CustomError> {
+    if !is_valid_name(name) {
+        return Err(CustomError::InvalidName(name.to_owned()));
+    }
+
+    if Kind::from_str(name).map(|kind| !kind.is_custom()).unwrap_or(false) {
+        return Err(CustomError::ReservedName(name.to_owned()));
+    }
+
+    DATATYPES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_owned(), canonicalise);
+
+    Ok(())
+}
+
+pub fn is_registered(name: &str) -> bool {
+    DATATYPES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(name)
+}
+
+/// The names of the registered datatypes in lexicographical order.
+pub fn names() -> Vec<String> {
+    DATATYPES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// A value of a registered datatype in its canonical form. Values are
+/// ordered by datatype name and then lexically.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Custom {
+    kind: String,
+    value: String,
+}
+
+impl Custom {
+    /// Parses a value of the datatype registered under the given name.
+    pub fn parse(kind: &str, s: &str) -> Result<Self, CustomError> {
+        let canonicalise = DATATYPES
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(kind)
+            .cloned()
+            .ok_or_else(|| CustomError::UnknownDatatype(kind.to_owned()))?;
+        let value = canonicalise(s).map_err(|message| CustomError::Invalid {
+            kind: kind.to_owned(),
+            message,
+        })?;
+
+        Ok(Custom {
+            kind: kind.to_owned(),
+            value,
+        })
+    }
+
+    /// The name of the datatype.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Debug for Custom {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_tuple("Custom")
+            .field(&self.kind)
+            .field(&self.value)
+            .finish()
+    }
+}
+
+impl Display for Custom {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.value, formatter)
+    }
+}
+
+impl Diagnose for CustomError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let expected = match *self {
+            CustomError::Invalid { ref kind, .. } => kind.as_str(),
+            _ => "registered datatype",
+        };
+
+        Diagnostic::new(input, 0, expected, self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Code(String);
+
+    impl Parse for Code {
+        type Err = &'static str;
+        fn parse(s: &str) -> Result<Self, Self::Err> {
+            if s.len() == 2 && s.chars().all(|c| c.is_ascii_alphabetic()) {
+                Ok(Code(s.to_uppercase()))
+            } else {
+                Err("Expected two letters")
+            }
+        }
+    }
+
+    impl Display for Code {
+        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            Display::fmt(&self.0, formatter)
+        }
+    }
+
+    #[test]
+    fn parse_registered() {
+        register::<Code>("test-code").unwrap();
+
+        assert_eq!(
+            format!("{:?}", Custom::parse("test-code", "gb")),
+            r#"Ok(Custom("test-code", "GB"))"#
+        );
+        assert_eq!(
+            format!("{:?}", Custom::parse("test-code", "gbr")),
+            r#"Err(Invalid { kind: "test-code", message: "Expected two letters" })"#
+        );
+        assert!(names().contains(&"test-code".to_string()));
+    }
+
+    #[test]
+    fn usable_in_schemas() {
+        use schema::Schema;
+        use serde_json;
+
+        register::<Code>("test-country").unwrap();
+
+        let schema = Schema::from_json(r#"[{"field": "country", "datatype": "test-country"}]"#).unwrap();
+        let field = schema.fields().next().unwrap();
+        let value = field.parse("gb").unwrap();
+
+        assert_eq!(field.datatype, Kind::Custom("test-country".to_string()));
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#""GB""#);
+    }
+
+    #[test]
+    fn parse_unregistered() {
+        assert_eq!(
+            format!("{:?}", Custom::parse("test-missing", "gb")),
+            r#"Err(UnknownDatatype("test-missing"))"#
+        );
+    }
+
+    #[test]
+    fn register_errors() {
+        assert_eq!(
+            format!("{:?}", register::<Code>("string")),
+            r#"Err(ReservedName("string"))"#
+        );
+        assert_eq!(
+            format!("{:?}", register::<Code>("Code")),
+            r#"Err(InvalidName("Code"))"#
+        );
+        assert!(register_pattern("test-pattern", "[A-Z").is_err());
+    }
+}
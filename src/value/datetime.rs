@@ -5,8 +5,10 @@
 // according to those terms.
 
 use regex::{Regex, RegexSet};
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
 use super::Parse;
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
 
 #[derive(Debug, Fail)]
 pub enum DatetimeError {
@@ -15,7 +17,11 @@ pub enum DatetimeError {
 }
 
 /// ISO8601 Date time
-#[derive(Clone, PartialEq)]
+///
+/// Datetimes are ordered chronologically by the instant they start at. A
+/// coarser datetime goes before a finer one starting at the same instant
+/// (e.g. `2018` < `2018-01` < `2018-01-01`).
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Datetime {
     Year(u16),
     YearMonth(u16, u8),
@@ -53,6 +59,54 @@ impl Display for Datetime {
     }
 }
 
+impl Datetime {
+    /// The first second of the datetime, e.g. `2018-01-01T00:00:00Z` for
+    /// `2018`.
+    pub fn start(&self) -> Datetime {
+        let [y, m, d, h, mm, s] = self.components();
+
+        Datetime::Full(
+            y.unwrap_or(0),
+            m.unwrap_or(1) as u8,
+            d.unwrap_or(1) as u8,
+            h.unwrap_or(0) as u8,
+            mm.unwrap_or(0) as u8,
+            s.unwrap_or(0) as u8,
+        )
+    }
+
+    pub fn is_full(&self) -> bool {
+        matches!(*self, Datetime::Full(..))
+    }
+
+    /// The components from year to second. Missing components go before
+    /// any present one.
+    fn components(&self) -> [Option<u16>; 6] {
+        let (y, m, d, h, mm, s) = match *self {
+            Datetime::Year(y) => (y, None, None, None, None, None),
+            Datetime::YearMonth(y, m) => (y, Some(m), None, None, None, None),
+            Datetime::Date(y, m, d) => (y, Some(m), Some(d), None, None, None),
+            Datetime::DateHour(y, m, d, h) => (y, Some(m), Some(d), Some(h), None, None),
+            Datetime::DateHourMinute(y, m, d, h, mm) => (y, Some(m), Some(d), Some(h), Some(mm), None),
+            Datetime::Full(y, m, d, h, mm, s) => (y, Some(m), Some(d), Some(h), Some(mm), Some(s)),
+        };
+
+        [Some(y), m.map(u16::from), d.map(u16::from), h.map(u16::from), mm.map(u16::from), s.map(u16::from)]
+    }
+}
+
+impl Ord for Datetime {
+    fn cmp(&self, other: &Datetime) -> Ordering {
+        self.components().cmp(&other.components())
+    }
+}
+
+impl PartialOrd for Datetime {
+    fn partial_cmp(&self, other: &Datetime) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// TODO: Only pattern checks are performed. An out of range date will be
 /// accepted as valid.
 impl Parse for Datetime {
@@ -119,6 +173,92 @@ impl Parse for Datetime {
     }
 }
 
+impl Diagnose for DatetimeError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let scan = scan(&mut Scanner::new(input));
+
+        diagnostic::locate(input, scan, "ISO8601 datetime", self.to_string()).with_fix(diagnostic::first_valid(
+            input,
+            fixes(input),
+            |s| Datetime::parse(s).is_ok(),
+        ))
+    }
+}
+
+/// Checks the input against the datetime grammar in any of its accuracies.
+pub(crate) fn scan(s: &mut Scanner) -> Result<(), Expected> {
+    s.digits(4, 4)?;
+    if s.end_or('-')? {
+        return Ok(());
+    }
+    s.digits(2, 2)?;
+    if s.end_or('-')? {
+        return Ok(());
+    }
+    s.digits(2, 2)?;
+    if s.end_or('T')? {
+        return Ok(());
+    }
+    s.digits(2, 2)?;
+    if s.one_of(&[':', 'Z'])? == ':' {
+        s.digits(2, 2)?;
+        if s.one_of(&[':', 'Z'])? == ':' {
+            s.digits(2, 2)?;
+            s.literal("Z")?;
+        }
+    }
+
+    s.end()
+}
+
+/// Candidate corrections for common datetime mistakes, each building on the
+/// previous one: surrounding whitespace, lowercase designators, a space
+/// instead of `T` and a UTC offset instead of `Z`.
+pub(crate) fn fixes(input: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut s = input.trim().to_uppercase();
+    candidates.push(s.clone());
+
+    if s != time_separator(&s) {
+        s = time_separator(&s);
+        candidates.push(s.clone());
+    }
+
+    s = utc_designator(&s);
+
+    if s.len() > 10 && !s.ends_with('Z') {
+        s.push('Z');
+    }
+
+    if candidates.last() != Some(&s) {
+        candidates.push(s);
+    }
+
+    candidates
+}
+
+/// Replaces a space between the date and the time with `T`.
+pub(crate) fn time_separator(s: &str) -> String {
+    let mut s = s.to_owned();
+
+    if s.len() > 10 && s.is_char_boundary(10) && s.is_char_boundary(11) && &s[10..11] == " " {
+        s.replace_range(10..11, "T");
+    }
+
+    s
+}
+
+/// Replaces a zero UTC offset such as `+00:00` with `Z`.
+pub(crate) fn utc_designator(s: &str) -> String {
+    for offset in &["+00:00", "+0000", "+00"] {
+        if s.len() > 10 && s.ends_with(offset) {
+            return format!("{}Z", &s[..s.len() - offset.len()]);
+        }
+    }
+
+    s.to_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,4 +311,17 @@ mod tests {
         assert_eq!(format!("{:?}", actual), expected);
     }
 
+    #[test]
+    fn order_chronologically() {
+        let mut actual: Vec<Datetime> = ["2018-01-01T10Z", "2017-12", "2018", "2018-01", "2018-01-01"]
+            .iter()
+            .map(|s| Datetime::parse(s).unwrap())
+            .collect();
+        actual.sort();
+
+        assert_eq!(
+            format!("{:?}", actual),
+            "[Datetime(2017-12), Datetime(2018), Datetime(2018-01), Datetime(2018-01-01), Datetime(2018-01-01T10Z)]"
+        );
+    }
 }
@@ -5,9 +5,11 @@
 // according to those terms.
 
 use std::str::FromStr;
-use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::BTreeMap;
 use std::fmt;
 
+use super::langstring::LangString;
 use super::Value;
 
 struct ValueVisitor;
@@ -25,6 +27,42 @@ impl<'de> Visitor<'de> for ValueVisitor {
     {
         Value::from_str(value).map_err(de::Error::custom)
     }
+
+    // Cardinality n values are lists of strings.
+    fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+
+        while let Some(value) = access.next_element::<String>()? {
+            values.push(Value::from_str(&value).map_err(de::Error::custom)?);
+        }
+
+        Ok(Value::List(values))
+    }
+
+    // Objects are either `{"type": "inapplicable"}` or language-tagged
+    // strings. Four-letter language subtags are reserved so `type` is never
+    // a language tag.
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut map = BTreeMap::new();
+
+        while let Some((key, value)) = access.next_entry::<String, String>()? {
+            map.insert(key, value);
+        }
+
+        if map.len() == 1 && map.get("type").is_some_and(|t| t == "inapplicable") {
+            Ok(Value::Inapplicable)
+        } else {
+            LangString::from_map(map)
+                .map(Value::LangString)
+                .map_err(de::Error::custom)
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Value {
@@ -32,7 +70,7 @@ impl<'de> Deserialize<'de> for Value {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_str(ValueVisitor)
+        deserializer.deserialize_any(ValueVisitor)
     }
 }
 
@@ -67,4 +105,36 @@ mod tests {
 
         assert_eq!(format!("{:?}", res), expected);
     }
+
+    #[test]
+    fn list_value() {
+        let input = r#"["abc", "xyz"]"#;
+        let expected = r#"Ok(List([Untyped("abc"), Untyped("xyz")]))"#.to_string();
+        let res = serde_json::from_str::<Value>(input);
+
+        assert_eq!(format!("{:?}", res), expected);
+    }
+
+    #[test]
+    fn nested_list_value() {
+        let res = serde_json::from_str::<Value>(r#"[["abc"]]"#);
+
+        assert!(res.is_err(), "Expected nested lists to be an error");
+    }
+
+    #[test]
+    fn langstring_value() {
+        let input = r#"{"en": "Wales", "cy": "Cymru"}"#;
+        let expected = r#"Ok(LangString({"cy": "Cymru", "en": "Wales"}))"#.to_string();
+        let res = serde_json::from_str::<Value>(input);
+
+        assert_eq!(format!("{:?}", res), expected);
+    }
+
+    #[test]
+    fn inapplicable_value() {
+        let res = serde_json::from_str::<Value>(r#"{"type": "inapplicable"}"#);
+
+        assert_eq!(format!("{:?}", res), "Ok(Inapplicable)");
+    }
 }
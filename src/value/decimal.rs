@@ -0,0 +1,235 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display};
+
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
+use super::integer::Integer;
+use super::number::Number;
+use super::Parse;
+
+#[derive(Debug, Fail)]
+pub enum DecimalError {
+    #[fail(display = "Invalid decimal. Expected digits with an optional fraction (e.g. -12.50)")]
+    Invalid,
+    #[fail(display = "The decimal {} is less than the minimum {}", value, min)]
+    BelowMinimum { value: Decimal, min: Decimal },
+    #[fail(display = "The decimal {} is greater than the maximum {}", value, max)]
+    AboveMaximum { value: Decimal, max: Decimal },
+    #[fail(display = "The decimal {} has more than {} decimal places", value, scale)]
+    ScaleExceeded { value: Decimal, scale: usize },
+}
+
+/// An exact decimal number of arbitrary precision.
+///
+/// The canonical form has no exponent, no leading zeros, no trailing zeros
+/// in the fraction and no `-0`. Exponents are accepted when parsing.
+///
+/// ```
+/// use sac::value::Parse;
+/// use sac::value::decimal::Decimal;
+/// let decimal = Decimal::parse("0012.3400").unwrap();
+/// assert_eq!(decimal.to_string(), "12.34");
+/// assert_eq!(Decimal::parse("1.5e-2").unwrap().to_string(), "0.015");
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(Number);
+
+impl Debug for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_tuple("Decimal").field(&self.0.to_string()).finish()
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0, formatter)
+    }
+}
+
+impl Parse for Decimal {
+    type Err = DecimalError;
+
+    fn parse(s: &str) -> Result<Self, Self::Err> {
+        Number::parse(s).map(Decimal).ok_or(DecimalError::Invalid)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(n: i64) -> Decimal {
+        Decimal(Number::from(n))
+    }
+}
+
+impl Decimal {
+    pub(crate) fn from_number(n: Number) -> Decimal {
+        Decimal(n)
+    }
+
+    /// The number of digits after the decimal point in the canonical form.
+    pub fn scale(&self) -> usize {
+        self.0.scale()
+    }
+
+    /// The integer part of the decimal, rounding towards zero.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::decimal::Decimal;
+    /// assert_eq!(Decimal::parse("-0.5").unwrap().trunc().to_string(), "0");
+    /// assert_eq!(Decimal::parse("12.75").unwrap().trunc().to_string(), "12");
+    /// ```
+    pub fn trunc(&self) -> Integer {
+        Integer::from_number(self.0.trunc())
+    }
+
+    /// Checks the decimal is within the given inclusive bounds and has at
+    /// most the given number of decimal places.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::decimal::Decimal;
+    /// let max = Decimal::from(100);
+    /// assert!(Decimal::parse("99.99").unwrap().check(None, Some(&max), Some(2)).is_ok());
+    /// assert!(Decimal::parse("99.999").unwrap().check(None, Some(&max), Some(2)).is_err());
+    /// assert!(Decimal::parse("100.01").unwrap().check(None, Some(&max), None).is_err());
+    /// ```
+    pub fn check(
+        &self,
+        min: Option<&Decimal>,
+        max: Option<&Decimal>,
+        scale: Option<usize>,
+    ) -> Result<(), DecimalError> {
+        if let Some(min) = min.filter(|min| self.cmp(min) == Ordering::Less) {
+            return Err(DecimalError::BelowMinimum {
+                value: self.clone(),
+                min: min.clone(),
+            });
+        }
+
+        if let Some(max) = max.filter(|max| self.cmp(max) == Ordering::Greater) {
+            return Err(DecimalError::AboveMaximum {
+                value: self.clone(),
+                max: max.clone(),
+            });
+        }
+
+        match scale {
+            Some(scale) if self.scale() > scale => Err(DecimalError::ScaleExceeded {
+                value: self.clone(),
+                scale,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Diagnose for DecimalError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let message = self.to_string();
+
+        match *self {
+            DecimalError::Invalid => diagnostic::locate(input, scan(input), "decimal", message)
+                .with_fix(diagnostic::first_valid(input, fixes(input), |s| Decimal::parse(s).is_ok())),
+            DecimalError::BelowMinimum { ref min, .. } => {
+                Diagnostic::new(input, 0, &format!("decimal no less than {}", min), message)
+            }
+            DecimalError::AboveMaximum { ref max, .. } => {
+                Diagnostic::new(input, 0, &format!("decimal no greater than {}", max), message)
+            }
+            DecimalError::ScaleExceeded { scale, .. } => {
+                let offset = input.find('.').map_or(0, |i| i + 1 + scale);
+                Diagnostic::new(input, offset, &format!("at most {} decimal places", scale), message)
+            }
+        }
+    }
+}
+
+/// Checks the input against `[-+]?[0-9]+(\.[0-9]+)?([eE][-+]?[0-9]{1,4})?`.
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+    let mut expected = "digit, ., e or end of input";
+
+    if !s.eat('-') {
+        s.eat('+');
+    }
+    s.digits(1, usize::MAX)?;
+
+    if s.eat('.') {
+        s.digits(1, usize::MAX)?;
+        expected = "digit, e or end of input";
+    }
+
+    if s.eat('e') || s.eat('E') {
+        if !s.eat('-') {
+            s.eat('+');
+        }
+        s.digits(1, 4)?;
+        expected = "end of input";
+    }
+
+    if s.is_end() {
+        Ok(())
+    } else {
+        Err(s.expected(expected))
+    }
+}
+
+/// Candidate corrections: surrounding whitespace, a comma as decimal
+/// separator and a point without digits on either side.
+fn fixes(input: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut s = input.trim().to_owned();
+    candidates.push(s.clone());
+
+    if !s.contains('.') && s.matches(',').count() == 1 {
+        s = s.replace(',', ".");
+        candidates.push(s.clone());
+    }
+
+    if s.ends_with('.') {
+        s.pop();
+        candidates.push(s.clone());
+    }
+
+    if s.starts_with('.') || s.starts_with("-.") {
+        s = s.replacen('.', "0.", 1);
+        candidates.push(s);
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ok() {
+        let actual = Decimal::parse("-10.250");
+
+        assert_eq!(format!("{:?}", actual), r#"Ok(Decimal("-10.25"))"#);
+    }
+
+    #[test]
+    fn parse_fail() {
+        let actual = Decimal::parse("10,25");
+
+        assert_eq!(format!("{:?}", actual), "Err(Invalid)");
+    }
+
+    #[test]
+    fn check_bounds() {
+        let min = Decimal::parse("-1.5").unwrap();
+        let actual = Decimal::parse("-1.51").unwrap().check(Some(&min), None, None);
+
+        assert_eq!(
+            actual.map_err(|e| e.to_string()),
+            Err("The decimal -1.51 is less than the minimum -1.5".to_string())
+        );
+    }
+}
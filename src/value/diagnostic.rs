@@ -0,0 +1,368 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Position-aware explanations of why a value failed to parse.
+//!
+//! Every parse error implements `Diagnose`. Given the input that caused it,
+//! the error is explained with the byte offset where the input departs from
+//! the grammar, the token expected there and, when there is an obvious one,
+//! a corrected input that parses.
+//!
+//! ```
+//! use sac::kind::Kind;
+//! use sac::value::Value;
+//! use sac::value::diagnostic::Diagnose;
+//! let input = "2018-01-01 10:00:00Z";
+//! let diagnostic = Value::parse(input, Kind::Timestamp).unwrap_err().diagnose(input);
+//! assert_eq!(diagnostic.offset, 10);
+//! assert_eq!(diagnostic.expected, "T");
+//! assert_eq!(diagnostic.fix, Some("2018-01-01T10:00:00Z".to_string()));
+//! ```
+
+use std::fmt::{self, Display};
+use std::str::ParseBoolError;
+
+use super::ValueError;
+
+/// Explains why an input failed to parse.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub input: String,
+    /// The byte offset where the input departs from the grammar.
+    pub offset: usize,
+    /// The token expected at the offset.
+    pub expected: String,
+    pub message: String,
+    /// A corrected input that parses.
+    pub fix: Option<String>,
+}
+
+impl Diagnostic {
+    /// Builds a diagnostic clamping the offset to a character boundary
+    /// within the input.
+    pub fn new(input: &str, offset: usize, expected: &str, message: String) -> Self {
+        let mut offset = offset.min(input.len());
+
+        while !input.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        Diagnostic {
+            input: input.to_owned(),
+            offset,
+            expected: expected.to_owned(),
+            message,
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, fix: Option<String>) -> Self {
+        self.fix = fix;
+        self
+    }
+
+    /// Renders the diagnostic with a caret under the offending character.
+    /// Only the line where the offset falls is shown.
+    ///
+    /// ```text
+    /// Invalid RFC3339 timestamp.
+    ///
+    ///     2018-01-01 10:00:00Z
+    ///               ^ expected T
+    ///
+    /// Did you mean 2018-01-01T10:00:00Z?
+    /// ```
+    pub fn render(&self) -> String {
+        let start = self.input[..self.offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.input[self.offset..]
+            .find('\n')
+            .map_or(self.input.len(), |i| self.offset + i);
+        let column = self.input[start..self.offset].chars().count();
+        let mut out = format!(
+            "{}\n\n    {}\n    {}^ expected {}\n",
+            self.message.trim_end(),
+            &self.input[start..end],
+            " ".repeat(column),
+            self.expected
+        );
+
+        if let Some(ref fix) = self.fix {
+            out.push_str(&format!("\nDid you mean {}?\n", fix));
+        }
+
+        out
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} Expected {} at byte {}.",
+            self.message, self.expected, self.offset
+        )
+    }
+}
+
+/// An error that can explain itself in terms of the input that caused it.
+pub trait Diagnose {
+    fn diagnose(&self, input: &str) -> Diagnostic;
+}
+
+impl Diagnose for ValueError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            ValueError::InvalidUrl(ref e) => e.diagnose(input),
+            ValueError::InvalidBool(ref e) => e.diagnose(input),
+            ValueError::InvalidInteger(ref e) => e.diagnose(input),
+            ValueError::InvalidText(ref e) => e.diagnose(input),
+            ValueError::InvalidHash(ref e) => e.diagnose(input),
+            ValueError::InvalidDecimal(ref e) => e.diagnose(input),
+            ValueError::InvalidLangString(ref e) => e.diagnose(input),
+            ValueError::InvalidEnum(ref e) => e.diagnose(input),
+            ValueError::InvalidCurie(ref e) => e.diagnose(input),
+            ValueError::InvalidCustom(ref e) => e.diagnose(input),
+            ValueError::InvalidTimestamp(ref e) => e.diagnose(input),
+            ValueError::InvalidDatetime(ref e) => e.diagnose(input),
+            ValueError::InvalidPeriod(ref e) => e.diagnose(input),
+            ValueError::InvalidPoint(ref e) => e.diagnose(input),
+            ValueError::InvalidPolygon(ref e) => e.diagnose(input),
+            ValueError::InvalidUnknown => {
+                Diagnostic::new(input, 0, "null", self.to_string()).with_fix(first_valid(
+                    input,
+                    vec![input.trim().to_lowercase()],
+                    |s| s == "null",
+                ))
+            }
+            ValueError::InvalidInapplicable => {
+                Diagnostic::new(input, 0, "N/A", self.to_string()).with_fix(first_valid(
+                    input,
+                    vec![input.trim().to_uppercase()],
+                    |s| s == "N/A" || s == "NA",
+                ))
+            }
+            ValueError::Ambiguous { .. } => Diagnostic::new(input, 0, "unambiguous value", self.to_string()),
+            ValueError::Incoercible { ref to, .. } => Diagnostic::new(input, 0, to, self.to_string()),
+            ValueError::InvalidValue { .. } | ValueError::UnknownType { .. } => {
+                Diagnostic::new(input, 0, "value", self.to_string())
+            }
+        }
+    }
+}
+
+impl Diagnose for ParseBoolError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        Diagnostic::new(input, 0, "true or false", "Invalid boolean.".to_owned()).with_fix(first_valid(
+            input,
+            vec![input.trim().to_lowercase()],
+            |s| s.parse::<bool>().is_ok(),
+        ))
+    }
+}
+
+/// Returns the first candidate that differs from the input and is valid.
+pub(crate) fn first_valid<F>(input: &str, candidates: Vec<String>, is_valid: F) -> Option<String>
+where
+    F: Fn(&str) -> bool,
+{
+    candidates.into_iter().find(|c| c != input && is_valid(c))
+}
+
+/// The token a grammar expected at a given offset.
+#[derive(Debug)]
+pub(crate) struct Expected {
+    pub offset: usize,
+    pub token: String,
+}
+
+impl Expected {
+    pub fn new(offset: usize, token: &str) -> Self {
+        Expected {
+            offset,
+            token: token.to_owned(),
+        }
+    }
+
+    /// Moves the offset when the scanned input was a slice of a larger one.
+    pub fn shift(self, n: usize) -> Self {
+        Expected::new(self.offset + n, &self.token)
+    }
+}
+
+/// Builds a diagnostic from the result of scanning the input. When the scan
+/// finds nothing wrong the error is not about syntax so the whole input is
+/// blamed.
+pub(crate) fn locate(input: &str, scan: Result<(), Expected>, expected: &str, message: String) -> Diagnostic {
+    match scan {
+        Ok(()) => Diagnostic::new(input, 0, expected, message),
+        Err(e) => Diagnostic::new(input, e.offset, &e.token, message),
+    }
+}
+
+/// A cursor to check an input against a grammar by hand and report the
+/// first departure.
+pub(crate) struct Scanner<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Scanner { input, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.offset == self.input.len()
+    }
+
+    pub fn expected(&self, token: &str) -> Expected {
+        Expected::new(self.offset, token)
+    }
+
+    /// Consumes the character if it is next.
+    pub fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.offset += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes characters while they satisfy the predicate.
+    pub fn take_while<F>(&mut self, f: F) -> &'a str
+    where
+        F: Fn(char) -> bool,
+    {
+        let start = self.offset;
+
+        while let Some(c) = self.peek().filter(|c| f(*c)) {
+            self.offset += c.len_utf8();
+        }
+
+        &self.input[start..self.offset]
+    }
+
+    /// Consumes the literal. On a mismatch the rest of the literal is
+    /// expected at the first differing character.
+    pub fn literal(&mut self, literal: &str) -> Result<(), Expected> {
+        for (i, c) in literal.char_indices() {
+            if !self.eat(c) {
+                return Err(self.expected(&literal[i..]));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consumes between `min` and `max` digits.
+    pub fn digits(&mut self, min: usize, max: usize) -> Result<usize, Expected> {
+        let mut count = 0;
+
+        while count < max && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.offset += 1;
+            count += 1;
+        }
+
+        if count < min {
+            Err(self.expected("digit"))
+        } else {
+            Ok(count)
+        }
+    }
+
+    /// Consumes whichever of the characters is next.
+    pub fn one_of(&mut self, chars: &[char]) -> Result<char, Expected> {
+        match self.peek().filter(|c| chars.contains(c)) {
+            Some(c) => {
+                self.offset += c.len_utf8();
+                Ok(c)
+            }
+            None => {
+                let tokens: Vec<String> = chars.iter().map(|c| c.to_string()).collect();
+                Err(self.expected(&tokens.join(" or ")))
+            }
+        }
+    }
+
+    /// Whether the input ends here. Otherwise the character must be next.
+    pub fn end_or(&mut self, c: char) -> Result<bool, Expected> {
+        if self.is_end() {
+            Ok(true)
+        } else if self.eat(c) {
+            Ok(false)
+        } else {
+            Err(self.expected(&format!("{} or end of input", c)))
+        }
+    }
+
+    pub fn end(&self) -> Result<(), Expected> {
+        if self.is_end() {
+            Ok(())
+        } else {
+            Err(self.expected("end of input"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kind::Kind;
+    use value::Value;
+
+    fn diagnose(input: &str, kind: Kind) -> (usize, String, Option<String>) {
+        let d = Value::parse(input, kind).unwrap_err().diagnose(input);
+
+        (d.offset, d.expected, d.fix)
+    }
+
+    #[test]
+    fn render_caret() {
+        let d = Diagnostic::new("foo\nbår baz", 9, "digit", "Invalid.".to_owned()).with_fix(Some("1".to_owned()));
+
+        assert_eq!(d.render(), "Invalid.\n\n    bår baz\n        ^ expected digit\n\nDid you mean 1?\n");
+    }
+
+    #[test]
+    fn clamps_offset() {
+        let d = Diagnostic::new("å", 1, "digit", "Invalid.".to_owned());
+
+        assert_eq!(d.offset, 0);
+    }
+
+    #[test]
+    fn diagnose_kinds() {
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(diagnose("True", Kind::Bool), (0, "true or false".into(), some("true")));
+        assert_eq!(diagnose("+12", Kind::Integer), (0, "- or digit".into(), some("12")));
+        assert_eq!(diagnose("12,5", Kind::Decimal), (2, "digit, ., e or end of input".into(), some("12.5")));
+        assert_eq!(diagnose("2018-1", Kind::Datetime), (6, "digit".into(), None));
+        assert_eq!(diagnose("2018-01-01T10:00:00", Kind::Timestamp), (19, "Z".into(), some("2018-01-01T10:00:00Z")));
+        assert_eq!(diagnose("P1Y2X", Kind::Period), (4, "M or D".into(), None));
+        assert_eq!(diagnose("2018/P1X", Kind::Period), (7, "Y or M or D".into(), None));
+        assert_eq!(diagnose("SHA-256:ab", Kind::Hash), (0, "sha-256".into(), some("sha-256:ab")));
+        assert_eq!(diagnose("POINT (1, 2)", Kind::Point), (8, "space".into(), None));
+        assert_eq!(diagnose("point (1 2)", Kind::Point), (0, "POINT".into(), some("POINT (1 2)")));
+        assert_eq!(diagnose("POLYGON ((0 0, 1 1)", Kind::Polygon), (19, ", or )".into(), None));
+        assert_eq!(diagnose("Foo:bar", Kind::Curie), (0, "lowercase letter".into(), some("foo:bar")));
+        assert_eq!(diagnose("example.org", Kind::Url), (0, "scheme (e.g. https://)".into(), some("https://example.org")));
+        assert_eq!(diagnose(r#"{"en": 1}"#, Kind::LangString), (7, "JSON object of language tags to strings".into(), None));
+        assert_eq!(diagnose("a <b>c</b>", Kind::Text), (2, "Markdown without HTML".into(), None));
+        assert_eq!(diagnose("opne", Kind::Enum(vec!["open".into()])), (0, "one of open".into(), some("open")));
+        assert_eq!(diagnose("none", Kind::Unknown), (0, "null".into(), None));
+        assert_eq!(diagnose("n/a ", Kind::Inapplicable), (0, "N/A".into(), some("N/A")));
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Controlled vocabularies.
+
+use std::fmt::{self, Display};
+
+use super::diagnostic::{Diagnose, Diagnostic};
+
+#[derive(Debug, Fail)]
+pub enum EnumError {
+    #[fail(display = "Unexpected value {}. Allowed values: {}.{}", value, allowed, suggestion)]
+    NotAllowed {
+        value: String,
+        allowed: Vocabulary,
+        suggestion: Suggestion,
+    },
+    #[fail(display = "Empty enumeration. There are no allowed values.")]
+    Empty,
+    #[fail(display = "Unbound enumeration. The allowed values come from the {} register.", _0)]
+    Unbound(String),
+}
+
+/// The list of values allowed by an enumeration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vocabulary(pub Vec<String>);
+
+impl Display for Vocabulary {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0.join(", "), formatter)
+    }
+}
+
+/// The allowed value closest to an unexpected one, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion(pub Option<String>);
+
+impl Display for Suggestion {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(ref s) => write!(formatter, " Did you mean {}?", s),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks the value is one of the allowed values. Values are case
+/// sensitive and an empty list of allowed values is an error.
+///
+/// ```
+/// use sac::value::enumeration::check;
+/// let allowed = vec!["open".to_string(), "closed".to_string()];
+/// assert!(check("open", &allowed).is_ok());
+/// assert_eq!(
+///     check("clsoed", &allowed).unwrap_err().to_string(),
+///     "Unexpected value clsoed. Allowed values: open, closed. Did you mean closed?"
+/// );
+/// ```
+pub fn check(value: &str, allowed: &[String]) -> Result<(), EnumError> {
+    if allowed.is_empty() {
+        Err(EnumError::Empty)
+    } else if allowed.iter().any(|a| a == value) {
+        Ok(())
+    } else {
+        Err(EnumError::NotAllowed {
+            value: value.to_owned(),
+            allowed: Vocabulary(allowed.to_vec()),
+            suggestion: Suggestion(closest(value, allowed).map(|s| s.to_owned())),
+        })
+    }
+}
+
+/// The allowed value with the smallest case-insensitive edit distance. Ties
+/// are resolved by order.
+pub fn closest<'a>(value: &str, allowed: &'a [String]) -> Option<&'a str> {
+    let value = value.to_lowercase();
+
+    allowed
+        .iter()
+        .min_by_key(|a| levenshtein(&value, &a.to_lowercase()))
+        .map(|a| a.as_str())
+}
+
+/// Levenshtein edit distance between two strings counted in characters.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+impl Diagnose for EnumError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            EnumError::NotAllowed {
+                ref allowed,
+                ref suggestion,
+                ..
+            } => Diagnostic::new(input, 0, &format!("one of {}", allowed), self.to_string())
+                .with_fix(suggestion.0.clone()),
+            EnumError::Empty | EnumError::Unbound(_) => {
+                Diagnostic::new(input, 0, "an enumeration with allowed values", self.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("caf", "café"), 1);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn closest_ignores_case() {
+        let allowed = vec!["Local-authority".to_string(), "Charity".to_string()];
+
+        assert_eq!(closest("local authority", &allowed), Some("Local-authority"));
+        assert_eq!(closest("x", &[]), None);
+    }
+
+    #[test]
+    fn check_is_case_sensitive() {
+        let allowed = vec!["open".to_string()];
+        let actual = check("Open", &allowed).map_err(|e| e.to_string());
+
+        assert_eq!(
+            actual,
+            Err("Unexpected value Open. Allowed values: open. Did you mean open?".to_string())
+        );
+    }
+
+    #[test]
+    fn check_empty() {
+        assert_eq!(
+            check("open", &[]).map_err(|e| e.to_string()),
+            Err("Empty enumeration. There are no allowed values.".to_string())
+        );
+    }
+}
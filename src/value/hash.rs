@@ -7,6 +7,7 @@
 use std::str::FromStr;
 use std::fmt::{self, Debug, Display};
 use super::Parse;
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
 
 #[derive(Debug, Fail)]
 pub enum HashError {
@@ -18,7 +19,7 @@ pub enum HashError {
     InvalidValue,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Alg {
     Sha2256,
 }
@@ -41,7 +42,7 @@ impl Display for Alg {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Hash {
     alg: Alg,
     // TODO: The use of &[u8] makes FromStr unusable because conflicting
@@ -56,6 +57,11 @@ impl Hash {
             bytes: bytes,
         }
     }
+
+    /// The digest as lowercase hexadecimal.
+    pub fn bytes(&self) -> &str {
+        &self.bytes
+    }
 }
 
 impl Debug for Hash {
@@ -102,6 +108,29 @@ fn is_hex(c: char) -> bool {
     }
 }
 
+impl Diagnose for HashError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let fixes = vec![input.trim().to_lowercase().replacen("sha256:", "sha-256:", 1)];
+
+        diagnostic::locate(input, scan(input), "qualified hash", self.to_string())
+            .with_fix(diagnostic::first_valid(input, fixes, |s| Hash::parse(s).is_ok()))
+    }
+}
+
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+
+    s.literal("sha-256")?;
+    s.literal(":")?;
+    s.take_while(is_hex);
+
+    if s.is_end() {
+        Ok(())
+    } else {
+        Err(s.expected("lowercase hexadecimal digit"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,17 +4,43 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
-// use super::Parse;
 
-/// An decimal integer.
-/// TODO: spec doesn't allow floating point numbers.
-#[derive(Clone, PartialEq)]
-pub struct Integer(pub i64);
+use super::decimal::Decimal;
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
+use super::number::Number;
+use super::Parse;
+
+#[derive(Debug, Fail)]
+pub enum IntegerError {
+    #[fail(display = "Invalid integer. Expected digits without leading zeros or sign other than - (e.g. -12)")]
+    Invalid,
+    #[fail(display = "The integer {} is less than the minimum {}", value, min)]
+    BelowMinimum { value: Integer, min: Decimal },
+    #[fail(display = "The integer {} is greater than the maximum {}", value, max)]
+    AboveMaximum { value: Integer, max: Decimal },
+}
+
+/// A decimal integer of arbitrary size.
+///
+/// It must conform to `0|-?[1-9][0-9]*`: no leading zeros, no `+` and no
+/// `-0`.
+///
+/// ```
+/// use sac::value::Parse;
+/// use sac::value::integer::Integer;
+/// let big = Integer::parse("123456789012345678901234567890").unwrap();
+/// assert_eq!(big.to_i64(), None);
+/// assert!(Integer::parse("+1").is_err());
+/// assert!(Integer::parse("007").is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Integer(Number);
 
 impl Debug for Integer {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.debug_tuple("Integer").field(&self.0).finish()
+        write!(formatter, "Integer({})", self.0)
     }
 }
 
@@ -24,9 +50,144 @@ impl Display for Integer {
     }
 }
 
-// impl Parse for Integer {
-//     type Err = IntegerError;
-//     fn parse(s: &str) -> Result<Self, Self::Err> {
+impl Parse for Integer {
+    type Err = IntegerError;
+
+    fn parse(s: &str) -> Result<Self, Self::Err> {
+        Number::parse_integer(s).map(Integer).ok_or(IntegerError::Invalid)
+    }
+}
+
+impl From<i64> for Integer {
+    fn from(n: i64) -> Integer {
+        Integer(Number::from(n))
+    }
+}
+
+impl Integer {
+    /// The integer as an `i64` when it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        self.0.to_string().parse().ok()
+    }
 
-//     }
-// }
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal::from_number(self.0.clone())
+    }
+
+    pub(crate) fn from_number(n: Number) -> Integer {
+        Integer(n)
+    }
+
+    /// Checks the integer is within the given inclusive bounds.
+    pub fn check(&self, min: Option<&Decimal>, max: Option<&Decimal>) -> Result<(), IntegerError> {
+        let value = self.to_decimal();
+
+        if let Some(min) = min.filter(|min| value.cmp(min) == Ordering::Less) {
+            return Err(IntegerError::BelowMinimum {
+                value: self.clone(),
+                min: min.clone(),
+            });
+        }
+
+        if let Some(max) = max.filter(|max| value.cmp(max) == Ordering::Greater) {
+            return Err(IntegerError::AboveMaximum {
+                value: self.clone(),
+                max: max.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Diagnose for IntegerError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let message = self.to_string();
+
+        match *self {
+            IntegerError::Invalid => diagnostic::locate(input, scan(input), "integer", message)
+                .with_fix(diagnostic::first_valid(input, fixes(input), |s| Integer::parse(s).is_ok())),
+            IntegerError::BelowMinimum { ref min, .. } => {
+                Diagnostic::new(input, 0, &format!("integer no less than {}", min), message)
+            }
+            IntegerError::AboveMaximum { ref max, .. } => {
+                Diagnostic::new(input, 0, &format!("integer no greater than {}", max), message)
+            }
+        }
+    }
+}
+
+/// Checks the input against `0|-?[1-9][0-9]*`.
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+    let negative = s.eat('-');
+
+    match s.peek() {
+        Some('0') if negative => return Err(s.expected("non-zero digit")),
+        Some('0') => {
+            s.eat('0');
+            return s.end();
+        }
+        Some(c) if c.is_ascii_digit() => (),
+        _ if negative => return Err(s.expected("non-zero digit")),
+        _ => return Err(s.expected("- or digit")),
+    }
+
+    s.digits(1, usize::MAX)?;
+    s.end()
+}
+
+/// Candidate corrections: surrounding whitespace, a leading `+`, leading
+/// zeros and `-0`.
+fn fixes(input: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut s = input.trim().trim_start_matches('+').to_owned();
+    candidates.push(s.clone());
+
+    let negative = s.starts_with('-');
+    let digits = s.trim_start_matches('-').trim_start_matches('0').to_owned();
+    s = match (negative, digits.is_empty()) {
+        (_, true) if s.contains('0') => "0".to_owned(),
+        (_, true) => return candidates,
+        (true, false) => format!("-{}", digits),
+        (false, false) => digits,
+    };
+    candidates.push(s);
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ok() {
+        let actual = Integer::parse("-9223372036854775809");
+
+        assert_eq!(format!("{:?}", actual), "Ok(Integer(-9223372036854775809))");
+    }
+
+    #[test]
+    fn parse_fail() {
+        for s in &["+1", "01", "-0", "1.0", "1e3", "", " 1"] {
+            assert!(Integer::parse(s).is_err(), "input {:?}", s);
+        }
+    }
+
+    #[test]
+    fn to_i64() {
+        assert_eq!(Integer::parse("-42").unwrap().to_i64(), Some(-42));
+        assert_eq!(Integer::parse("9223372036854775808").unwrap().to_i64(), None);
+    }
+
+    #[test]
+    fn check_bounds() {
+        let min = Decimal::from(1);
+        let max = Decimal::from(10);
+
+        assert!(Integer::from(10).check(Some(&min), Some(&max)).is_ok());
+        assert!(Integer::from(0).check(Some(&min), Some(&max)).is_err());
+        assert!(Integer::parse("100000000000000000000").unwrap().check(None, Some(&max)).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+use regex::Regex;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+
+use super::diagnostic::{Diagnose, Diagnostic};
+use super::Parse;
+
+#[derive(Debug, Fail)]
+pub enum LangStringError {
+    #[fail(display = "Invalid language tag {}. Expected a BCP 47 tag (e.g. en, cy-GB)", _0)]
+    InvalidTag(String),
+    #[fail(display = "Duplicate language tag {}", _0)]
+    DuplicateTag(String),
+    #[fail(display = "A language-tagged string needs at least one language")]
+    Empty,
+    #[fail(display = "Unexpected language-tagged string. Expected a JSON object of tags to strings. {}", _0)]
+    ParseError(String),
+}
+
+/// A language tag as defined by BCP 47 (RFC 5646) excluding grandfathered
+/// tags and the four-letter language subtags reserved for future use.
+///
+/// Tags are case insensitive so they are normalised to the recommended case:
+/// lowercase language, titlecase script and uppercase region (e.g.
+/// `sr-Latn-RS`).
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LangTag(String);
+
+impl Debug for LangTag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(&self.0, formatter)
+    }
+}
+
+impl Display for LangTag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.0, formatter)
+    }
+}
+
+impl FromStr for LangTag {
+    type Err = LangStringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r"(?x)^(
+                  (?P<language>[a-z]{2,3}(-[a-z]{3}){0,3}|[a-z]{5,8})
+                  (-[a-z]{4})?
+                  (-([a-z]{2}|[0-9]{3}))?
+                  (-([a-z0-9]{5,8}|[0-9][a-z0-9]{3}))*
+                  (-[0-9a-wyz](-[a-z0-9]{2,8})+)*
+                  (-x(-[a-z0-9]{1,8})+)?
+                  |x(-[a-z0-9]{1,8})+
+                )$"
+            ).unwrap();
+        }
+
+        let lower = s.to_lowercase();
+
+        if RE.is_match(&lower) {
+            Ok(LangTag(normalise_case(&lower)))
+        } else {
+            Err(LangStringError::InvalidTag(s.to_owned()))
+        }
+    }
+}
+
+impl LangTag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The tag with its last subtag removed as used by the lookup fallback
+    /// defined in RFC 4647 (e.g. `zh-Hant-CN` ~> `zh-Hant`).
+    fn truncate(&self) -> Option<LangTag> {
+        let mut end = self.0.rfind('-')?;
+        // A single-letter subtag (extension or private use) can't end a tag.
+        if self.0[..end].rfind('-').is_some_and(|i| end - i == 2) {
+            end -= 2;
+        }
+
+        Some(LangTag(self.0[..end].to_owned()))
+    }
+}
+
+/// Applies the BCP 47 case conventions to a lowercase tag.
+fn normalise_case(tag: &str) -> String {
+    let mut subtags: Vec<String> = Vec::new();
+    let mut singleton = false;
+
+    for (i, subtag) in tag.split('-').enumerate() {
+        let normalised = if i == 0 || singleton {
+            subtag.to_owned()
+        } else if subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()) {
+            let mut chars = subtag.chars();
+            chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect()
+        } else if subtag.len() == 2 {
+            subtag.to_uppercase()
+        } else {
+            subtag.to_owned()
+        };
+
+        singleton = singleton || subtag.len() == 1;
+        subtags.push(normalised);
+    }
+
+    subtags.join("-")
+}
+
+/// A string in one or more languages keyed by BCP 47 language tag.
+///
+/// The textual form is a JSON object sorted by tag.
+///
+/// ```
+/// use sac::value::Parse;
+/// use sac::value::langstring::LangString;
+/// let name = LangString::parse(r#"{"EN": "Wales", "cy": "Cymru"}"#).unwrap();
+/// assert_eq!(name.to_string(), r#"{"cy":"Cymru","en":"Wales"}"#);
+/// assert_eq!(name.lookup(&["cy-GB", "en"]), Some("Cymru"));
+/// assert_eq!(name.preferred(&["fr", "en"]), "Wales");
+/// assert_eq!(name.preferred(&["fr"]), "Cymru");
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LangString(BTreeMap<LangTag, String>);
+
+impl Debug for LangString {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.debug_tuple("LangString").field(&self.0).finish()
+    }
+}
+
+impl Display for LangString {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let map: BTreeMap<&str, &str> = self.0.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let json = serde_json::to_string(&map).map_err(|_| fmt::Error)?;
+
+        Display::fmt(&json, formatter)
+    }
+}
+
+impl Parse for LangString {
+    type Err = LangStringError;
+
+    fn parse(s: &str) -> Result<Self, Self::Err> {
+        let map: BTreeMap<String, String> =
+            serde_json::from_str(s).map_err(|err| LangStringError::ParseError(err.to_string()))?;
+
+        LangString::from_map(map)
+    }
+}
+
+impl LangString {
+    /// Builds a language-tagged string validating and normalising every tag.
+    pub fn from_map<I>(map: I) -> Result<Self, LangStringError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut strings = BTreeMap::new();
+
+        for (tag, value) in map {
+            let tag = LangTag::from_str(&tag)?;
+            if strings.contains_key(&tag) {
+                return Err(LangStringError::DuplicateTag(tag.to_string()));
+            }
+            strings.insert(tag, value);
+        }
+
+        if strings.is_empty() {
+            Err(LangStringError::Empty)
+        } else {
+            Ok(LangString(strings))
+        }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&str> {
+        let tag = LangTag::from_str(tag).ok()?;
+
+        self.0.get(&tag).map(|s| s.as_str())
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &LangTag> {
+        self.0.keys()
+    }
+
+    /// Finds the string for the first preferred language available. Each
+    /// preference falls back to less specific tags as defined by the lookup
+    /// scheme in RFC 4647 (e.g. `cy-GB` ~> `cy`).
+    pub fn lookup(&self, preferences: &[&str]) -> Option<&str> {
+        preferences
+            .iter()
+            .filter_map(|p| LangTag::from_str(p).ok())
+            .filter_map(|mut tag| loop {
+                if let Some(s) = self.0.get(&tag) {
+                    return Some(s.as_str());
+                }
+                tag = tag.truncate()?;
+            })
+            .next()
+    }
+
+    /// Like `lookup` but falls back to the first string by tag when no
+    /// preference is available.
+    pub fn preferred(&self, preferences: &[&str]) -> &str {
+        self.lookup(preferences)
+            .or_else(|| self.0.values().next().map(|s| s.as_str()))
+            .expect("a language-tagged string is never empty")
+    }
+}
+
+impl Diagnose for LangStringError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let message = self.to_string();
+
+        match *self {
+            LangStringError::ParseError(_) => {
+                let offset = serde_json::from_str::<BTreeMap<String, String>>(input)
+                    .err()
+                    .map_or(0, |e| offset_of(input, e.line(), e.column()));
+                Diagnostic::new(input, offset, "JSON object of language tags to strings", message)
+            }
+            LangStringError::InvalidTag(ref tag) => {
+                Diagnostic::new(input, find_key(input, tag, 0), "BCP 47 language tag", message)
+            }
+            LangStringError::DuplicateTag(ref tag) => {
+                let first = find_key(input, tag, 0);
+                Diagnostic::new(input, find_key(input, tag, first + 1), "unique language tag", message)
+            }
+            LangStringError::Empty => Diagnostic::new(input, input.rfind('}').unwrap_or(0), "language tag", message),
+        }
+    }
+}
+
+/// Converts a one-based line and column as reported by serde_json into a
+/// byte offset.
+fn offset_of(input: &str, line: usize, column: usize) -> usize {
+    let start: usize = input.split('\n').take(line.saturating_sub(1)).map(|l| l.len() + 1).sum();
+
+    start + column.saturating_sub(1)
+}
+
+/// Finds the offset of the tag as an object key from the given offset,
+/// ignoring case. Returns 0 when it can't be found.
+fn find_key(input: &str, tag: &str, from: usize) -> usize {
+    let haystack = input.to_ascii_lowercase();
+    let needle = format!("\"{}\"", tag.to_ascii_lowercase());
+
+    haystack
+        .get(from..)
+        .and_then(|rest| rest.find(&needle))
+        .map_or(0, |i| from + i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_normalises_case() {
+        let actual: Vec<String> = vec!["EN", "en-gb", "SR-LATN-rs", "es-419", "de-CH-1996", "en-a-BBB-x-ABC", "X-Private"]
+            .into_iter()
+            .map(|s| LangTag::from_str(s).unwrap().to_string())
+            .collect();
+
+        assert_eq!(
+            actual,
+            vec!["en", "en-GB", "sr-Latn-RS", "es-419", "de-CH-1996", "en-a-bbb-x-abc", "x-private"]
+        );
+    }
+
+    #[test]
+    fn tag_rejects_invalid() {
+        for s in &["", "e", "englishlanguage", "en_GB", "en-", "en--GB", "cy-GB-a", "123", "type"] {
+            assert!(LangTag::from_str(s).is_err(), "input {:?}", s);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_duplicates_and_empty() {
+        assert_eq!(
+            format!("{:?}", LangString::parse(r#"{"en": "a", "EN": "b"}"#)),
+            r#"Err(DuplicateTag("en"))"#
+        );
+        assert_eq!(format!("{:?}", LangString::parse("{}")), "Err(Empty)");
+        assert!(LangString::parse(r#"{"en": 1}"#).is_err());
+    }
+
+    #[test]
+    fn lookup_falls_back() {
+        let s = LangString::parse(r#"{"cy": "Cymru", "en-GB": "Wales"}"#).unwrap();
+
+        assert_eq!(s.lookup(&["en-GB-x-foo"]), Some("Wales"));
+        assert_eq!(s.lookup(&["en"]), None);
+        assert_eq!(s.lookup(&["fr", "cy-Latn-GB"]), Some("Cymru"));
+        assert_eq!(s.preferred(&[]), "Cymru");
+    }
+}
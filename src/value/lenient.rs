@@ -0,0 +1,340 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Lenient parsing of values that arrive in well-known non-canonical forms.
+//!
+//! Each kind accepts a few variants of its canonical form, such as a
+//! lowercase WKT keyword or a URL without a scheme. The variant is repaired
+//! and the fixes applied are reported alongside the canonical value. Inputs
+//! that could be read in more than one way are refused.
+//!
+//! ```
+//! use sac::kind::Kind;
+//! use sac::value::lenient::{self, Fix};
+//! let repaired = lenient::parse("point (1  2)", Kind::Point).unwrap();
+//! assert_eq!(repaired.value.to_string(), "POINT (1 2)");
+//! assert_eq!(repaired.fixes, vec![Fix::Case, Fix::Spacing]);
+//! ```
+
+use regex::Regex;
+use std::fmt::{self, Display};
+
+use super::{datetime, point, Value, ValueError};
+use kind::Kind;
+
+/// A repair applied to an input to reach the canonical form.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fix {
+    Trim,
+    Case,
+    DayFirstDate,
+    TimeSeparator,
+    UtcDesignator,
+    Spacing,
+    PlusSign,
+    LeadingZeros,
+    HashAlgorithm,
+    Scheme,
+    EncodedSpaces,
+}
+
+impl Display for Fix {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            Fix::Trim => "Removed surrounding whitespace",
+            Fix::Case => "Normalised letter case",
+            Fix::DayFirstDate => "Read a day/month/year date as ISO 8601",
+            Fix::TimeSeparator => "Separated date and time with T",
+            Fix::UtcDesignator => "Replaced a zero UTC offset with Z",
+            Fix::Spacing => "Normalised spacing",
+            Fix::PlusSign => "Removed a leading plus sign",
+            Fix::LeadingZeros => "Removed leading zeros",
+            Fix::HashAlgorithm => "Renamed the hash algorithm to sha-256",
+            Fix::Scheme => "Added the https scheme",
+            Fix::EncodedSpaces => "Percent-encoded spaces",
+        };
+
+        Display::fmt(description, formatter)
+    }
+}
+
+/// A canonical value and the fixes applied to the input to get it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repaired {
+    pub value: Value,
+    pub fixes: Vec<Fix>,
+}
+
+/// Parses a value of the given kind accepting well-known variants of the
+/// canonical form. Valid inputs are returned untouched. When the repaired
+/// input is still invalid the error is the one for the original input so it
+/// can be diagnosed.
+///
+/// Slashed dates are read day first, as in `10/01/2018` for 10 January.
+/// Dates that only make sense month first, dates with two-digit years,
+/// timestamps without a time zone and numbers with commas are ambiguous.
+pub fn parse(s: &str, kind: Kind) -> Result<Repaired, ValueError> {
+    let error = match Value::parse(s, kind.clone()) {
+        Ok(value) => {
+            return Ok(Repaired {
+                value,
+                fixes: vec![],
+            })
+        }
+        Err(error) => error,
+    };
+    let mut repair = Repair::new(s);
+
+    match kind {
+        Kind::Bool | Kind::Unknown => {
+            repair.apply(Fix::Case, |s| s.to_lowercase());
+        }
+        Kind::Inapplicable | Kind::Period => {
+            repair.apply(Fix::Case, |s| s.to_uppercase());
+        }
+        Kind::Integer | Kind::Decimal => {
+            if repair.input.contains(',') {
+                return Err(repair.ambiguous("A comma may separate either thousands or decimals"));
+            }
+
+            repair.apply(Fix::PlusSign, |s| s.trim_start_matches('+').to_owned());
+            repair.apply(Fix::LeadingZeros, leading_zeros);
+        }
+        Kind::Datetime | Kind::Timestamp => {
+            repair.apply(Fix::Case, |s| s.to_uppercase());
+            repair.day_first_date()?;
+            repair.apply(Fix::TimeSeparator, datetime::time_separator);
+            repair.apply(Fix::UtcDesignator, datetime::utc_designator);
+
+            if kind == Kind::Timestamp && repair.input.len() > 10 && !has_zone(&repair.input) {
+                return Err(repair.ambiguous("Timestamps without a time zone are local times"));
+            }
+        }
+        Kind::Hash => {
+            repair.apply(Fix::Case, |s| s.to_lowercase());
+            repair.apply(Fix::HashAlgorithm, |s| {
+                if s.starts_with("sha256:") {
+                    s.replacen("sha256:", "sha-256:", 1)
+                } else {
+                    s.to_owned()
+                }
+            });
+        }
+        Kind::Point | Kind::Polygon => {
+            repair.apply(Fix::Case, point::wkt_keyword);
+            repair.apply(Fix::Spacing, point::wkt_spacing);
+        }
+        Kind::Url => {
+            repair.apply(Fix::EncodedSpaces, |s| s.replace(' ', "%20"));
+            repair.apply(Fix::Scheme, scheme);
+        }
+        Kind::Curie => {
+            repair.apply(Fix::Case, |s| match s.find(':') {
+                Some(i) => format!("{}{}", s[..i].to_lowercase(), &s[i..]),
+                None => s.to_owned(),
+            });
+        }
+        Kind::Enum(ref allowed) => {
+            let matches: Vec<&String> = allowed
+                .iter()
+                .filter(|a| a.to_lowercase() == repair.input.to_lowercase())
+                .collect();
+
+            match matches.len() {
+                1 => repair.apply(Fix::Case, |_| matches[0].to_owned()),
+                0 => (),
+                _ => return Err(repair.ambiguous("It matches more than one allowed value")),
+            }
+        }
+        _ => (),
+    }
+
+    match Value::parse(&repair.input, kind) {
+        Ok(value) if !repair.fixes.is_empty() => Ok(Repaired {
+            value,
+            fixes: repair.fixes,
+        }),
+        _ => Err(error),
+    }
+}
+
+/// An input on its way to the canonical form.
+struct Repair {
+    original: String,
+    input: String,
+    fixes: Vec<Fix>,
+}
+
+impl Repair {
+    /// Starts with the input trimmed as no kind has surrounding whitespace.
+    fn new(input: &str) -> Self {
+        let mut repair = Repair {
+            original: input.to_owned(),
+            input: input.to_owned(),
+            fixes: vec![],
+        };
+        repair.apply(Fix::Trim, |s| s.trim().to_owned());
+
+        repair
+    }
+
+    /// Applies the fix, recording it only when it changes the input.
+    fn apply<F>(&mut self, fix: Fix, f: F)
+    where
+        F: FnOnce(&str) -> String,
+    {
+        let repaired = f(&self.input);
+
+        if repaired != self.input {
+            self.input = repaired;
+            self.fixes.push(fix);
+        }
+    }
+
+    fn ambiguous(&self, reason: &str) -> ValueError {
+        ValueError::Ambiguous {
+            value: self.original.clone(),
+            reason: reason.to_owned(),
+        }
+    }
+
+    /// Rewrites a leading `dd/mm/yyyy` date, also separated by `.` or `-`,
+    /// as `yyyy-mm-dd`.
+    fn day_first_date(&mut self) -> Result<(), ValueError> {
+        lazy_static! {
+            static ref DATE: Regex = Regex::new(r"^(\d{1,2})([/.-])(\d{1,2})([/.-])(\d{2,4})\b").unwrap();
+        }
+
+        let (day, month, year, end) = match DATE.captures(&self.input) {
+            Some(ref caps) if caps[2] == caps[4] => (
+                caps[1].parse::<u8>().unwrap(),
+                caps[3].parse::<u8>().unwrap(),
+                caps[5].to_owned(),
+                caps[0].len(),
+            ),
+            _ => return Ok(()),
+        };
+
+        if year.len() != 4 {
+            return Err(self.ambiguous("Two-digit years could be in any century"));
+        }
+
+        if month > 12 && day <= 12 {
+            return Err(self.ambiguous("The date only makes sense month first"));
+        }
+
+        let rest = self.input[end..].to_owned();
+        self.apply(Fix::DayFirstDate, |_| format!("{}-{:02}-{:02}{}", year, month, day, rest));
+
+        Ok(())
+    }
+}
+
+fn leading_zeros(s: &str) -> String {
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", s),
+    };
+    let trimmed = digits.trim_start_matches('0');
+
+    if trimmed.is_empty() || trimmed.starts_with('.') {
+        format!("{}0{}", sign, trimmed)
+    } else {
+        format!("{}{}", sign, trimmed)
+    }
+}
+
+fn has_zone(s: &str) -> bool {
+    s.ends_with('Z') || s.get(10..).is_some_and(|time| time.contains(['+', '-']))
+}
+
+/// Adds `https://` to inputs that start with a domain name or with `//`.
+fn scheme(s: &str) -> String {
+    if let Some(rest) = s.strip_prefix("//") {
+        return format!("https://{}", rest);
+    }
+
+    let host = s.split(['/', '?', '#']).next().unwrap_or("");
+
+    if !s.contains("://") && host.contains('.') && !host.starts_with('.') {
+        format!("https://{}", s)
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repair(s: &str, kind: Kind) -> (String, Vec<Fix>) {
+        let repaired = parse(s, kind).unwrap();
+
+        (repaired.value.to_string(), repaired.fixes)
+    }
+
+    #[test]
+    fn canonical_untouched() {
+        assert_eq!(repair("true", Kind::Bool), ("true".into(), vec![]));
+        assert_eq!(repair("POINT (1 2)", Kind::Point), ("POINT (1 2)".into(), vec![]));
+    }
+
+    #[test]
+    fn repair_variants() {
+        assert_eq!(repair(" True", Kind::Bool), ("true".into(), vec![Fix::Trim, Fix::Case]));
+        assert_eq!(repair("10/01/2018", Kind::Datetime), ("2018-01-10".into(), vec![Fix::DayFirstDate]));
+        assert_eq!(
+            repair("10.01.2018 09:30:00+00:00", Kind::Timestamp),
+            (
+                "2018-01-10T09:30:00Z".into(),
+                vec![Fix::DayFirstDate, Fix::TimeSeparator, Fix::UtcDesignator]
+            )
+        );
+        assert_eq!(repair("point (1 2)", Kind::Point), ("POINT (1 2)".into(), vec![Fix::Case]));
+        assert_eq!(
+            repair("SHA256:AB", Kind::Hash),
+            ("sha-256:ab".into(), vec![Fix::Case, Fix::HashAlgorithm])
+        );
+        assert_eq!(
+            repair("example.org/a b", Kind::Url),
+            ("https://example.org/a%20b".into(), vec![Fix::EncodedSpaces, Fix::Scheme])
+        );
+        assert_eq!(repair("+007", Kind::Integer), ("7".into(), vec![Fix::PlusSign, Fix::LeadingZeros]));
+        assert_eq!(repair("Country:GB", Kind::Curie), ("country:GB".into(), vec![Fix::Case]));
+        assert_eq!(
+            repair("OPEN", Kind::Enum(vec!["open".into(), "closed".into()])),
+            ("open".into(), vec![Fix::Case])
+        );
+    }
+
+    #[test]
+    fn refuse_ambiguous() {
+        let reason = |s: &str, kind: Kind| match parse(s, kind) {
+            Err(ValueError::Ambiguous { reason, .. }) => reason,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(reason("01/13/2018", Kind::Datetime), "The date only makes sense month first");
+        assert_eq!(reason("10/01/18", Kind::Datetime), "Two-digit years could be in any century");
+        assert_eq!(reason("2018-01-10 09:30:00", Kind::Timestamp), "Timestamps without a time zone are local times");
+        assert_eq!(reason("1,500", Kind::Integer), "A comma may separate either thousands or decimals");
+        assert_eq!(
+            reason("open", Kind::Enum(vec!["Open".into(), "OPEN".into()])),
+            "It matches more than one allowed value"
+        );
+    }
+
+    #[test]
+    fn refuse_invalid() {
+        let actual = parse("yes", Kind::Bool);
+
+        assert_eq!(
+            format!("{:?}", actual),
+            r#"Err(InvalidBool(ParseBoolError))"#
+        );
+    }
+}
@@ -4,14 +4,22 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
-use std::num::ParseIntError;
 use std::str::{FromStr, ParseBoolError};
 
+pub mod crs;
 pub mod curie;
+pub mod custom;
 pub mod datetime;
+pub mod decimal;
+pub mod diagnostic;
+pub mod enumeration;
 pub mod hash;
 pub mod integer;
+pub mod langstring;
+pub mod lenient;
+mod number;
 pub mod period;
 pub mod point;
 pub mod polygon;
@@ -23,9 +31,13 @@ pub mod de;
 pub mod ser;
 
 use self::curie::{Curie, CurieError};
+use self::custom::{Custom, CustomError};
 use self::datetime::{Datetime, DatetimeError};
+use self::decimal::{Decimal, DecimalError};
+use self::enumeration::EnumError;
 use self::hash::{Hash, HashError};
-use self::integer::Integer;
+use self::integer::{Integer, IntegerError};
+use self::langstring::{LangString, LangStringError};
 use self::period::{Period, PeriodError};
 use self::point::{Point, PointError};
 use self::polygon::{Polygon, PolygonError};
@@ -42,12 +54,16 @@ pub enum ValueError {
     InvalidValue { value: String },
     #[fail(display = "Unknown type {}", kind)]
     UnknownType { kind: String },
+    #[fail(display = "Ambiguous value {}. {}", value, reason)]
+    Ambiguous { value: String, reason: String },
+    #[fail(display = "A {} value can't be coerced to {}", from, to)]
+    Incoercible { from: String, to: String },
     #[fail(display = "Invalid url")]
     InvalidUrl(#[cause] UrlError),
     #[fail(display = "Invalid boolean")]
     InvalidBool(#[cause] ParseBoolError),
     #[fail(display = "Invalid integer")]
-    InvalidInteger(#[cause] ParseIntError),
+    InvalidInteger(#[cause] IntegerError),
     #[fail(display = "Invalid unknown")]
     InvalidUnknown,
     #[fail(display = "Invalid inapplicable")]
@@ -56,8 +72,16 @@ pub enum ValueError {
     InvalidText(#[cause] TextError),
     #[fail(display = "Invalid hash")]
     InvalidHash(#[cause] HashError),
+    #[fail(display = "Invalid decimal")]
+    InvalidDecimal(#[cause] DecimalError),
+    #[fail(display = "Invalid language-tagged string")]
+    InvalidLangString(#[cause] LangStringError),
+    #[fail(display = "Invalid enumeration value")]
+    InvalidEnum(#[cause] EnumError),
     #[fail(display = "Invalid curie")]
     InvalidCurie(#[cause] CurieError),
+    #[fail(display = "Invalid custom value")]
+    InvalidCustom(#[cause] CustomError),
     #[fail(display = "Invalid timestamp")]
     InvalidTimestamp(#[cause] TimestampError),
     #[fail(display = "Invalid datetime")]
@@ -82,7 +106,26 @@ pub trait Parse: Sized {
 /// * Entry-reference (???). -- Some sort of CURIE.
 /// * Fieldname -- This is because the field register needs a restricted string.
 ///   If we move away from central schemas, there is no need for it.
-#[derive(Clone, PartialEq)]
+///
+/// Values of the same kind are ordered naturally: numbers numerically,
+/// datetimes chronologically and strings lexically. Values of different
+/// kinds are ordered by kind, with missing values (`Unknown` and then
+/// `Inapplicable`) after any other value.
+///
+/// ```
+/// use sac::kind::Kind;
+/// use sac::value::Value;
+/// let mut values: Vec<Value> = ["10", "null", "9", "N/A"]
+///     .iter()
+///     .map(|s| Value::parse(s, Kind::Integer)
+///         .or_else(|_| Value::parse(s, Kind::Unknown))
+///         .or_else(|_| Value::parse(s, Kind::Inapplicable))
+///         .unwrap())
+///     .collect();
+/// values.sort();
+/// assert_eq!(format!("{:?}", values), "[Integer(9), Integer(10), Unknown, Inapplicable]");
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Value {
     // An untyped value allows constructing an item without a known schema.
     // TODO: Is it better to have another value implementation for this case?
@@ -119,16 +162,31 @@ pub enum Value {
     String(String),
 
     /// Represents a UTF-8 string as defined by [http://spec.commonmark.org/].
-    /// It is recommended to use the core set of features (i.e. paragraphs,
-    /// headers, bold, italica, links and inline code).
+    /// By default only the core set of features is allowed (i.e. paragraphs,
+    /// headers, bold, italics, links and inline code). See `text::Policy`.
     ///
-    /// TODO: For security reasons, the Unicode character U+0000 must be replaced with the
+    /// For security reasons, the Unicode character U+0000 is replaced with the
     /// REPLACEMENT CHARACTER (U+FFFD).
     Text(Text),
 
-    // An decimal integer.
-    // TODO: spec doesn't allow floating point numbers.
+    /// Represents a string in one or more languages keyed by BCP 47 tag.
+    ///
+    /// * In JSON this is encoded as an object sorted by tag (e.g.
+    ///   `{"cy":"Cymru","en":"Wales"}`).
+    LangString(LangString),
+
+    /// Represents a decimal integer of arbitrary size.
+    ///
+    /// * In JSON this is encoded as a number when it fits in an `i64` and as
+    ///   a string otherwise (e.g. `-12` but `"9223372036854775808"`).
+    /// * In CSV this is encoded as a string.
     Integer(Integer),
+
+    /// Represents an exact decimal number of arbitrary precision.
+    ///
+    /// * In JSON this is encoded as a string to preserve its precision.
+    /// * In CSV this is encoded as a string.
+    Decimal(Decimal),
     Datetime(Datetime),
     Timestamp(Timestamp),
     Period(Period),
@@ -138,6 +196,13 @@ pub enum Value {
     // Hex with hashing algorithm
     Hash(Hash),
     Url(Url),
+
+    /// Represents a value of a datatype registered at runtime (see
+    /// `custom::register`).
+    ///
+    /// * In JSON this is encoded as its canonical string.
+    /// * In CSV this is encoded as its canonical string.
+    Custom(Custom),
 }
 
 impl Debug for Value {
@@ -145,10 +210,13 @@ impl Debug for Value {
         match *self {
             Value::Bool(v) => formatter.debug_tuple("Bool").field(&v).finish(),
             Value::Curie(ref v) => Debug::fmt(v, formatter),
+            Value::Custom(ref v) => Debug::fmt(v, formatter),
             Value::Datetime(ref v) => Debug::fmt(v, formatter),
+            Value::Decimal(ref v) => Debug::fmt(v, formatter),
             Value::Hash(ref v) => Debug::fmt(v, formatter),
             Value::Inapplicable => formatter.debug_tuple("Inapplicable").finish(),
             Value::Integer(ref v) => Debug::fmt(v, formatter),
+            Value::LangString(ref v) => Debug::fmt(v, formatter),
             Value::List(ref v) => formatter.debug_tuple("List").field(v).finish(),
             Value::Period(ref v) => Debug::fmt(v, formatter),
             Value::Point(ref v) => Debug::fmt(v, formatter),
@@ -163,6 +231,85 @@ impl Debug for Value {
     }
 }
 
+/// The result of coercing a value to another kind.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Coercion {
+    /// The value converts back to the original one.
+    Lossless(Value),
+    /// The value lost precision or gained made-up precision on the way.
+    Lossy(Value),
+}
+
+impl Coercion {
+    pub fn is_lossy(&self) -> bool {
+        matches!(*self, Coercion::Lossy(_))
+    }
+
+    pub fn into_value(self) -> Value {
+        match self {
+            Coercion::Lossless(value) | Coercion::Lossy(value) => value,
+        }
+    }
+}
+
+impl Value {
+    /// The position of the kind of the value in the order across kinds.
+    fn rank(&self) -> u8 {
+        match *self {
+            Value::Bool(_) => 0,
+            Value::Integer(_) => 1,
+            Value::Decimal(_) => 2,
+            Value::Datetime(_) => 3,
+            Value::Timestamp(_) => 4,
+            Value::Period(_) => 5,
+            Value::String(_) => 6,
+            Value::Text(_) => 7,
+            Value::LangString(_) => 8,
+            Value::Curie(_) => 9,
+            Value::Url(_) => 10,
+            Value::Hash(_) => 11,
+            Value::Point(_) => 12,
+            Value::Polygon(_) => 13,
+            Value::Custom(_) => 14,
+            Value::Untyped(_) => 15,
+            Value::List(_) => 16,
+            Value::Unknown => 17,
+            Value::Inapplicable => 18,
+        }
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Decimal(a), Value::Decimal(b)) => a.cmp(b),
+            (Value::Datetime(a), Value::Datetime(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::Period(a), Value::Period(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::LangString(a), Value::LangString(b)) => a.cmp(b),
+            (Value::Curie(a), Value::Curie(b)) => a.cmp(b),
+            (Value::Url(a), Value::Url(b)) => a.cmp(b),
+            (Value::Hash(a), Value::Hash(b)) => a.cmp(b),
+            (Value::Point(a), Value::Point(b)) => a.cmp(b),
+            (Value::Polygon(a), Value::Polygon(b)) => a.cmp(b),
+            (Value::Custom(a), Value::Custom(b)) => a.cmp(b),
+            (Value::Untyped(a), Value::Untyped(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl FromStr for Value {
     type Err = ValueError;
 
@@ -176,10 +323,13 @@ impl Display for Value {
         match *self {
             Value::Bool(ref v) => Display::fmt(v, formatter),
             Value::Curie(ref v) => Display::fmt(v, formatter),
+            Value::Custom(ref v) => Display::fmt(v, formatter),
             Value::Datetime(ref v) => Display::fmt(v, formatter),
+            Value::Decimal(ref v) => Display::fmt(v, formatter),
             Value::Hash(ref v) => Display::fmt(v, formatter),
             Value::Inapplicable => Display::fmt("N/A", formatter),
             Value::Integer(ref v) => Display::fmt(v, formatter),
+            Value::LangString(ref v) => Display::fmt(v, formatter),
             Value::Period(ref v) => Display::fmt(v, formatter),
             Value::Point(ref v) => Display::fmt(v, formatter),
             Value::Polygon(ref v) => Debug::fmt(v, formatter),
@@ -212,10 +362,22 @@ impl Value {
                 let c = Curie::parse(s)?;
                 Ok(Value::Curie(c))
             }
+            Kind::Custom(ref name) => {
+                let c = Custom::parse(name, s)?;
+                Ok(Value::Custom(c))
+            }
             Kind::Datetime => {
                 let d = Datetime::parse(s)?;
                 Ok(Value::Datetime(d))
             }
+            Kind::Decimal => {
+                let d = Decimal::parse(s)?;
+                Ok(Value::Decimal(d))
+            }
+            Kind::Enum(ref allowed) => {
+                enumeration::check(s, allowed)?;
+                Ok(Value::String(s.to_owned()))
+            }
             Kind::Hash => {
                 let hash = Hash::parse(s)?;
                 Ok(Value::Hash(hash))
@@ -229,8 +391,12 @@ impl Value {
                 }
             }
             Kind::Integer => {
-                let i = s.parse::<i64>()?;
-                Ok(Value::Integer(Integer(i)))
+                let i = Integer::parse(s)?;
+                Ok(Value::Integer(i))
+            }
+            Kind::LangString => {
+                let l = LangString::parse(s)?;
+                Ok(Value::LangString(l))
             }
             // Kind::List(Box<Kind>),
             Kind::Period => {
@@ -270,6 +436,121 @@ impl Value {
             _ => Ok(Value::Untyped(s.to_owned())),
         }
     }
+
+    /// Converts the value to another kind. Every value can become a string
+    /// and strings and untyped values become any kind they parse as, lossily
+    /// when the parsed value is written differently (e.g. a normalised Url).
+    /// Otherwise only these conversions are defined:
+    ///
+    /// * Integer to decimal and timestamp to datetime, losslessly.
+    /// * Decimal to integer, truncating any decimal places.
+    /// * Datetime to timestamp, taking the first second of the datetime.
+    ///
+    /// Missing values stay as they are and lists are coerced item by item.
+    ///
+    /// ```
+    /// use sac::kind::Kind;
+    /// use sac::value::Value;
+    /// let value = Value::parse("2018-01", Kind::Datetime).unwrap();
+    /// let coercion = value.coerce(Kind::Timestamp).unwrap();
+    /// assert!(coercion.is_lossy());
+    /// assert_eq!(coercion.into_value().to_string(), "2018-01-01T00:00:00Z");
+    /// ```
+    pub fn coerce(&self, kind: Kind) -> Result<Coercion, ValueError> {
+        let value = match (self, &kind) {
+            (Value::List(values), _) => {
+                let mut lossy = false;
+                let mut coerced = Vec::new();
+
+                for value in values {
+                    let coercion = value.coerce(kind.clone())?;
+                    lossy |= coercion.is_lossy();
+                    coerced.push(coercion.into_value());
+                }
+
+                return Ok(if lossy {
+                    Coercion::Lossy(Value::List(coerced))
+                } else {
+                    Coercion::Lossless(Value::List(coerced))
+                });
+            }
+            (Value::Unknown, _) | (Value::Inapplicable, _) => self.clone(),
+            (Value::Untyped(s), _) | (Value::String(s), _) => {
+                let coerced = Value::parse(s, kind)?;
+
+                return Ok(if coerced.to_string() == *s {
+                    Coercion::Lossless(coerced)
+                } else {
+                    Coercion::Lossy(coerced)
+                });
+            }
+            (_, &Kind::String) => Value::String(self.to_string()),
+            (_, &Kind::Untyped) => Value::Untyped(self.to_string()),
+            (Value::Integer(i), &Kind::Decimal) => Value::Decimal(i.to_decimal()),
+            (Value::Decimal(d), &Kind::Integer) => {
+                let coerced = Value::Integer(d.trunc());
+
+                return Ok(if d.scale() > 0 {
+                    Coercion::Lossy(coerced)
+                } else {
+                    Coercion::Lossless(coerced)
+                });
+            }
+            (Value::Timestamp(t), &Kind::Datetime) => Value::Datetime(Datetime::parse(&t.to_string())?),
+            (Value::Datetime(d), &Kind::Timestamp) => {
+                let coerced = Value::Timestamp(Timestamp::parse(&d.start().to_string())?);
+
+                return Ok(if d.is_full() {
+                    Coercion::Lossless(coerced)
+                } else {
+                    Coercion::Lossy(coerced)
+                });
+            }
+            _ if self.is_kind(&kind) => self.clone(),
+            _ => {
+                return Err(ValueError::Incoercible {
+                    from: self.kind_name().to_owned(),
+                    to: kind.name().to_owned(),
+                })
+            }
+        };
+
+        Ok(Coercion::Lossless(value))
+    }
+
+    /// Whether the value is of the given kind. Enumeration values must be
+    /// one of the allowed values.
+    fn is_kind(&self, kind: &Kind) -> bool {
+        match *kind {
+            Kind::Enum(ref allowed) => matches!(*self, Value::String(ref s) if allowed.contains(s)),
+            Kind::Custom(ref name) => matches!(*self, Value::Custom(ref c) if c.kind() == name),
+            _ => self.kind_name() == kind.name(),
+        }
+    }
+
+    fn kind_name(&self) -> &str {
+        match *self {
+            Value::Bool(_) => "bool",
+            Value::Curie(_) => "curie",
+            Value::Custom(ref c) => c.kind(),
+            Value::Datetime(_) => "datetime",
+            Value::Decimal(_) => "decimal",
+            Value::Hash(_) => "hash",
+            Value::Inapplicable => "inapplicable",
+            Value::Integer(_) => "integer",
+            Value::LangString(_) => "langstring",
+            Value::List(_) => "list",
+            Value::Period(_) => "period",
+            Value::Point(_) => "point",
+            Value::Polygon(_) => "polygon",
+            Value::String(_) => "string",
+            Value::Text(_) => "text",
+            Value::Timestamp(_) => "timestamp",
+            Value::Unknown => "unknown",
+            Value::Untyped(_) => "untyped",
+            Value::Url(_) => "url",
+        }
+    }
 }
 
 impl From<UrlError> for ValueError {
@@ -284,8 +565,8 @@ impl From<ParseBoolError> for ValueError {
     }
 }
 
-impl From<ParseIntError> for ValueError {
-    fn from(err: ParseIntError) -> ValueError {
+impl From<IntegerError> for ValueError {
+    fn from(err: IntegerError) -> ValueError {
         ValueError::InvalidInteger(err)
     }
 }
@@ -302,6 +583,30 @@ impl From<HashError> for ValueError {
     }
 }
 
+impl From<DecimalError> for ValueError {
+    fn from(err: DecimalError) -> ValueError {
+        ValueError::InvalidDecimal(err)
+    }
+}
+
+impl From<LangStringError> for ValueError {
+    fn from(err: LangStringError) -> ValueError {
+        ValueError::InvalidLangString(err)
+    }
+}
+
+impl From<EnumError> for ValueError {
+    fn from(err: EnumError) -> ValueError {
+        ValueError::InvalidEnum(err)
+    }
+}
+
+impl From<CustomError> for ValueError {
+    fn from(err: CustomError) -> ValueError {
+        ValueError::InvalidCustom(err)
+    }
+}
+
 impl From<CurieError> for ValueError {
     fn from(err: CurieError) -> ValueError {
         ValueError::InvalidCurie(err)
@@ -374,4 +679,79 @@ mod tests {
 
         assert_eq!(format!("{:?}", actual), expected);
     }
+
+    #[test]
+    fn order_within_and_across_kinds() {
+        let decimal = |s: &str| Value::parse(s, Kind::Decimal).unwrap();
+
+        assert!(decimal("9.5") < decimal("10"));
+        assert!(decimal("-10") < decimal("-9.5"));
+        assert!(Value::Bool(true) < decimal("0"));
+        assert!(decimal("0") < Value::Unknown);
+        assert!(Value::Unknown < Value::Inapplicable);
+        assert!(Value::String("b".into()) < Value::List(vec![]));
+    }
+
+    #[test]
+    fn dedupe_in_hash_set() {
+        use std::collections::HashSet;
+
+        let set: HashSet<Value> = ["1.50", "1.5", "01.5"]
+            .iter()
+            .map(|s| Value::parse(s, Kind::Decimal).unwrap())
+            .collect();
+
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn coerce_lossless() {
+        let coerce = |s: &str, from: Kind, to: Kind| format!("{:?}", Value::parse(s, from).unwrap().coerce(to));
+
+        assert_eq!(coerce("1", Kind::Integer, Kind::Decimal), r#"Ok(Lossless(Decimal("1")))"#);
+        assert_eq!(coerce("1", Kind::Integer, Kind::String), r#"Ok(Lossless(String("1")))"#);
+        assert_eq!(
+            coerce("2018-01-01T10:00:00Z", Kind::Timestamp, Kind::Datetime),
+            r#"Ok(Lossless(Datetime(2018-01-01T10:00:00Z)))"#
+        );
+        assert_eq!(
+            coerce("https://example.org/", Kind::String, Kind::Url),
+            r#"Ok(Lossless(Url("https://example.org/")))"#
+        );
+        assert_eq!(coerce("null", Kind::Unknown, Kind::Point), r#"Ok(Lossless(Unknown))"#);
+    }
+
+    #[test]
+    fn coerce_lossy() {
+        let coerce = |s: &str, from: Kind, to: Kind| format!("{:?}", Value::parse(s, from).unwrap().coerce(to));
+
+        assert_eq!(coerce("-1.5", Kind::Decimal, Kind::Integer), r#"Ok(Lossy(Integer(-1)))"#);
+        assert_eq!(coerce("2.0", Kind::Decimal, Kind::Integer), r#"Ok(Lossless(Integer(2)))"#);
+        assert_eq!(
+            coerce("2018", Kind::Datetime, Kind::Timestamp),
+            r#"Ok(Lossy(Timestamp("2018-01-01T00:00:00Z")))"#
+        );
+        assert_eq!(
+            coerce("https://example.org", Kind::String, Kind::Url),
+            r#"Ok(Lossy(Url("https://example.org/")))"#
+        );
+
+        let list = Value::List(vec![Value::Untyped("1".into()), Value::Untyped("2.5".into())]);
+        let coerced = list.coerce(Kind::Decimal).unwrap().into_value().coerce(Kind::Integer);
+        assert_eq!(format!("{:?}", coerced), "Ok(Lossy(List([Integer(1), Integer(2)])))");
+    }
+
+    #[test]
+    fn coerce_undefined() {
+        let point = Value::parse("POINT (1 2)", Kind::Point).unwrap();
+
+        assert_eq!(
+            format!("{:?}", point.coerce(Kind::Polygon)),
+            r#"Err(Incoercible { from: "point", to: "polygon" })"#
+        );
+        assert_eq!(
+            format!("{:?}", Value::String("foo".into()).coerce(Kind::Integer)),
+            r#"Err(InvalidInteger(Invalid))"#
+        );
+    }
 }
@@ -0,0 +1,195 @@
+// Copyright 2018 Arnau Siches
+//
+// Licensed under the MIT license <LICENSE or http://opensource.org/licenses/MIT>,
+// at your option. This file may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Arbitrary-precision decimal numbers shared by the numeric values.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+
+/// A decimal number as a sign and its digits. The integer part has no
+/// leading zeros (it is `0` when empty) and the fraction has no trailing
+/// zeros so equal numbers have equal representations.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Number {
+    negative: bool,
+    integer: String,
+    fraction: String,
+}
+
+impl Number {
+    /// Parses `-?[0-9]+(\.[0-9]+)?([eE][+-]?[0-9]+)?` into its canonical
+    /// form. A leading `+` is accepted.
+    pub(crate) fn parse(s: &str) -> Option<Number> {
+        let (negative, unsigned) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (mantissa, exponent) = match unsigned.find(&['e', 'E'][..]) {
+            Some(i) => (&unsigned[..i], parse_exponent(&unsigned[i + 1..])?),
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+
+        if integer.is_empty() || mantissa.ends_with('.') || !is_digits(integer) || !is_digits(fraction) {
+            return None;
+        }
+
+        // Shift the decimal point by the exponent.
+        let digits = format!("{}{}", integer, fraction);
+        let point = integer.len() as i64 + exponent;
+        let (integer, fraction) = if point <= 0 {
+            (String::new(), format!("{}{}", "0".repeat(-point as usize), digits))
+        } else if point as usize >= digits.len() {
+            (format!("{}{}", digits, "0".repeat(point as usize - digits.len())), String::new())
+        } else {
+            (digits[..point as usize].to_owned(), digits[point as usize..].to_owned())
+        };
+
+        Some(Number::new(negative, &integer, &fraction))
+    }
+
+    /// Parses the canonical integer grammar `0|-?[1-9][0-9]*`.
+    pub(crate) fn parse_integer(s: &str) -> Option<Number> {
+        let digits = s.strip_prefix('-').unwrap_or(s);
+
+        if !is_digits(digits) || digits.is_empty() || (digits.starts_with('0') && s != "0") {
+            None
+        } else {
+            Some(Number::new(s.starts_with('-'), digits, ""))
+        }
+    }
+
+    fn new(negative: bool, integer: &str, fraction: &str) -> Number {
+        let integer = integer.trim_start_matches('0');
+        let fraction = fraction.trim_end_matches('0');
+        let is_zero = integer.is_empty() && fraction.is_empty();
+
+        Number {
+            negative: negative && !is_zero,
+            integer: if integer.is_empty() { "0".to_owned() } else { integer.to_owned() },
+            fraction: fraction.to_owned(),
+        }
+    }
+
+    /// The integer part, rounding towards zero.
+    pub(crate) fn trunc(&self) -> Number {
+        Number::new(self.negative, &self.integer, "")
+    }
+
+    /// The number of digits after the decimal point.
+    pub(crate) fn scale(&self) -> usize {
+        self.fraction.len()
+    }
+
+    fn cmp_magnitude(&self, other: &Number) -> Ordering {
+        self.integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(&other.integer))
+            .then_with(|| self.fraction.cmp(&other.fraction))
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            formatter.write_str("-")?;
+        }
+        formatter.write_str(&self.integer)?;
+        if !self.fraction.is_empty() {
+            write!(formatter, ".{}", self.fraction)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Number) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Number {
+        Number::parse_integer(&n.to_string()).expect("i64 is a valid integer")
+    }
+}
+
+fn is_digits(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse_exponent(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix(&['+', '-'][..]).unwrap_or(s);
+
+    if digits.is_empty() || !is_digits(digits) || digits.len() > 4 {
+        None
+    } else {
+        s.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(s: &str) -> Option<String> {
+        Number::parse(s).map(|n| n.to_string())
+    }
+
+    #[test]
+    fn parse_canonicalises() {
+        assert_eq!(canonical("1.50"), Some("1.5".into()));
+        assert_eq!(canonical("007.0"), Some("7".into()));
+        assert_eq!(canonical("-0.00"), Some("0".into()));
+        assert_eq!(canonical("+1.5e3"), Some("1500".into()));
+        assert_eq!(canonical("15E-4"), Some("0.0015".into()));
+        assert_eq!(canonical("123456789012345678901234567890.1"), Some("123456789012345678901234567890.1".into()));
+    }
+
+    #[test]
+    fn parse_rejects() {
+        for s in &["", "-", ".5", "1.", "1.2.3", "1e", "1e1000000", "0x10", "1 ", "١"] {
+            assert_eq!(canonical(s), None, "input {:?}", s);
+        }
+    }
+
+    #[test]
+    fn parse_integer_is_strict() {
+        assert_eq!(Number::parse_integer("-12").map(|n| n.to_string()), Some("-12".into()));
+        for s in &["+1", "01", "-0", "", "-", "1.0"] {
+            assert_eq!(Number::parse_integer(s), None, "input {:?}", s);
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        let mut numbers: Vec<Number> = vec!["10", "-1.5", "9.99", "-10", "0", "0.001"]
+            .into_iter()
+            .map(|s| Number::parse(s).unwrap())
+            .collect();
+        numbers.sort();
+        let actual: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+
+        assert_eq!(actual, vec!["-10", "-1.5", "0", "0.001", "9.99", "10"]);
+    }
+}
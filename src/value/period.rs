@@ -7,7 +7,8 @@
 use regex::RegexSet;
 use std::fmt::{self, Debug, Display};
 use super::Parse;
-use super::datetime::{Datetime, DatetimeError};
+use super::datetime::{self, Datetime, DatetimeError};
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
 
 #[derive(Debug, Fail)]
 pub enum PeriodError {
@@ -19,7 +20,9 @@ pub enum PeriodError {
     InvalidDatetime(DatetimeError),
 }
 
-#[derive(Clone, PartialEq)]
+/// ISO 8601 durations and intervals. Durations can't be compared without an
+/// anchor so periods are ordered by form and then lexically.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Period {
     // P1Y
     Duration(String),
@@ -99,6 +102,68 @@ impl From<DatetimeError> for PeriodError {
     }
 }
 
+impl Diagnose for PeriodError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let scan = match input.find('/') {
+            Some(i) => scan_part(&input[..i])
+                .and_then(|_| scan_part(&input[i + 1..]).map_err(|e| e.shift(i + 1))),
+            None => scan_duration(&mut Scanner::new(input)),
+        };
+
+        diagnostic::locate(input, scan, "ISO8601 period", self.to_string())
+    }
+}
+
+/// Checks either side of a range as a duration or a datetime.
+fn scan_part(s: &str) -> Result<(), Expected> {
+    if s.starts_with('P') {
+        scan_duration(&mut Scanner::new(s))
+    } else {
+        datetime::scan(&mut Scanner::new(s))
+    }
+}
+
+/// Checks the input against the duration grammar. Components must come in
+/// order and at least one must be given.
+fn scan_duration(s: &mut Scanner) -> Result<(), Expected> {
+    s.literal("P")?;
+
+    let any = scan_components(s, &['Y', 'M', 'D'])?;
+
+    if s.eat('T') {
+        if !scan_components(s, &['H', 'M', 'S'])? {
+            return Err(s.expected("digit"));
+        }
+    } else if !any {
+        return Err(s.expected("digit or T"));
+    }
+
+    s.end()
+}
+
+fn scan_components(s: &mut Scanner, designators: &[char]) -> Result<bool, Expected> {
+    let mut remaining = designators;
+    let mut any = false;
+
+    while !remaining.is_empty() && s.peek().is_some_and(|c| c.is_ascii_digit()) {
+        s.digits(1, usize::MAX)?;
+
+        match s.peek().and_then(|c| remaining.iter().position(|d| *d == c)) {
+            Some(i) => {
+                s.eat(remaining[i]);
+                remaining = &remaining[i + 1..];
+                any = true;
+            }
+            None => {
+                let tokens: Vec<String> = remaining.iter().map(|d| d.to_string()).collect();
+                return Err(s.expected(&tokens.join(" or ")));
+            }
+        }
+    }
+
+    Ok(any)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
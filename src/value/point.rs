@@ -4,16 +4,79 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+use super::crs::{self, CrsError, Srid};
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
 use super::Parse;
 use regex::{Regex, RegexSet};
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Fail)]
 pub enum PointError {
-    #[fail(display = "Invalid WKT point. Valid examples:\n\n  POINT (0 1)\n  POINTZ (1 2 3)\n")]
+    #[fail(
+        display = "Invalid WKT point. Valid examples:\n\n  POINT (0 1)\n  POINTZ (1 2 3)\n  SRID=4326;POINT (-0.1 51.5)\n"
+    )]
     ParseError,
     #[fail(display = "Unexpected vector. Expected length {}", _0)]
     UnexpectedVectorLength(u8),
+    #[fail(display = "Invalid coordinate reference system")]
+    InvalidCrs(#[cause] CrsError),
+}
+
+impl From<CrsError> for PointError {
+    fn from(err: CrsError) -> PointError {
+        PointError::InvalidCrs(err)
+    }
+}
+
+/// Formats a coordinate component in its canonical form: the shortest decimal
+/// that round-trips to the same `f64`, without exponent, trailing zeros nor
+/// negative zero.
+///
+/// ```
+/// use sac::value::point::format_number;
+/// assert_eq!(format_number(10.0), "10");
+/// assert_eq!(format_number(-0.0), "0");
+/// assert_eq!(format_number(0.1), "0.1");
+/// assert_eq!(format_number(1e21), "1000000000000000000000");
+/// ```
+pub fn format_number(n: f64) -> String {
+    if n == 0.0 {
+        "0".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+/// Compares coordinate components in order with a total order on floats so
+/// coordinates can be sorted and hashed. Negative zero equals zero, as it
+/// does for `f64`, but NaN equals itself.
+fn cmp_components(a: &[f64], b: &[f64]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| positive_zero(*x).total_cmp(&positive_zero(*y)))
+        .find(|o| *o != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+fn hash_components<H: Hasher>(components: &[f64], state: &mut H) {
+    for n in components {
+        positive_zero(*n).to_bits().hash(state);
+    }
+}
+
+fn positive_zero(n: f64) -> f64 {
+    if n == 0.0 {
+        0.0
+    } else {
+        n
+    }
+}
+
+/// Rounds a coordinate component to the given number of decimal places.
+pub fn round(n: f64, decimals: u32) -> f64 {
+    format!("{:.*}", decimals as usize, n).parse().unwrap_or(n)
 }
 
 pub trait Coord: Clone + Display + Debug {
@@ -22,9 +85,35 @@ pub trait Coord: Clone + Display + Debug {
     fn dimension(&self) -> u8;
 
     fn from_vec(v: Vec<f64>) -> Result<Self, Self::Err>;
+
+    fn x(&self) -> f64;
+
+    fn y(&self) -> f64;
+
+    /// Returns a copy of the coordinate with the given horizontal components.
+    /// Any other component is kept as is.
+    fn with_xy(&self, x: f64, y: f64) -> Self;
+
+    /// Returns a copy of the coordinate with every component rounded to the
+    /// given number of decimal places.
+    fn round(&self, decimals: u32) -> Self;
+
+    /// Checks the coordinate is within the bounds of the given reference
+    /// system.
+    fn check(&self, srid: Srid) -> Result<(), CrsError> {
+        srid.check(self.x(), self.y())
+    }
+
+    /// Transforms the horizontal components of the coordinate between
+    /// reference systems.
+    fn transform(&self, from: Srid, to: Srid) -> Result<Self, CrsError> {
+        let (x, y) = crs::transform(self.x(), self.y(), from, to)?;
+
+        Ok(self.with_xy(x, y))
+    }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct Coord2(f64, f64);
 
 impl Coord for Coord2 {
@@ -40,6 +129,22 @@ impl Coord for Coord2 {
             Err(PointError::UnexpectedVectorLength(2))
         }
     }
+
+    fn x(&self) -> f64 {
+        self.0
+    }
+
+    fn y(&self) -> f64 {
+        self.1
+    }
+
+    fn with_xy(&self, x: f64, y: f64) -> Self {
+        Coord2(x, y)
+    }
+
+    fn round(&self, decimals: u32) -> Self {
+        Coord2(round(self.0, decimals), round(self.1, decimals))
+    }
 }
 
 impl Coord2 {
@@ -54,6 +159,36 @@ impl Coord2 {
     pub fn y(&self) -> f64 {
         self.1
     }
+
+    fn components(&self) -> [f64; 2] {
+        [self.0, self.1]
+    }
+}
+
+impl PartialEq for Coord2 {
+    fn eq(&self, other: &Coord2) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Coord2 {}
+
+impl Ord for Coord2 {
+    fn cmp(&self, other: &Coord2) -> Ordering {
+        cmp_components(&self.components(), &other.components())
+    }
+}
+
+impl PartialOrd for Coord2 {
+    fn partial_cmp(&self, other: &Coord2) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Coord2 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_components(&self.components(), state)
+    }
 }
 
 impl Debug for Coord2 {
@@ -64,11 +199,11 @@ impl Debug for Coord2 {
 
 impl Display for Coord2 {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "{} {}", &self.x(), &self.y())
+        write!(formatter, "{} {}", format_number(self.0), format_number(self.1))
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct Coord3(f64, f64, f64);
 
 impl Coord for Coord3 {
@@ -84,6 +219,26 @@ impl Coord for Coord3 {
             Err(PointError::UnexpectedVectorLength(3))
         }
     }
+
+    fn x(&self) -> f64 {
+        self.0
+    }
+
+    fn y(&self) -> f64 {
+        self.1
+    }
+
+    fn with_xy(&self, x: f64, y: f64) -> Self {
+        Coord3(x, y, self.2)
+    }
+
+    fn round(&self, decimals: u32) -> Self {
+        Coord3(
+            round(self.0, decimals),
+            round(self.1, decimals),
+            round(self.2, decimals),
+        )
+    }
 }
 
 impl Coord3 {
@@ -102,6 +257,36 @@ impl Coord3 {
     pub fn z(&self) -> f64 {
         self.2
     }
+
+    fn components(&self) -> [f64; 3] {
+        [self.0, self.1, self.2]
+    }
+}
+
+impl PartialEq for Coord3 {
+    fn eq(&self, other: &Coord3) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Coord3 {}
+
+impl Ord for Coord3 {
+    fn cmp(&self, other: &Coord3) -> Ordering {
+        cmp_components(&self.components(), &other.components())
+    }
+}
+
+impl PartialOrd for Coord3 {
+    fn partial_cmp(&self, other: &Coord3) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Coord3 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_components(&self.components(), state)
+    }
 }
 
 impl Debug for Coord3 {
@@ -112,7 +297,13 @@ impl Debug for Coord3 {
 
 impl Display for Coord3 {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "{} {} {}", &self.x(), &self.y(), &self.z())
+        write!(
+            formatter,
+            "{} {} {}",
+            format_number(self.0),
+            format_number(self.1),
+            format_number(self.2)
+        )
     }
 }
 
@@ -123,14 +314,19 @@ impl Display for Coord3 {
 /// Note: M values are not implemented so only WKT `POINT` and `POINTZ` are accepted when
 /// parsing from a string.
 ///
+/// A point can optionally declare its coordinate reference system using EWKT.
+/// When the SRID is 4326 the coordinates are expressed as `longitude latitude`
+/// and are checked to be within bounds.
+///
 /// ```text
-/// POINT(0 0)
-/// POINTZ(0 0 0)
+/// POINT (0 0)
+/// POINTZ (0 0 0)
+/// SRID=4326;POINT (-0.1246 51.5007)
 /// ```
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Point {
-    Point(Coord2),
-    PointZ(Coord3),
+    Point(Coord2, Option<Srid>),
+    PointZ(Coord3, Option<Srid>),
 }
 
 impl Debug for Point {
@@ -141,41 +337,145 @@ impl Debug for Point {
 
 impl Display for Point {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(srid) = self.srid() {
+            write!(formatter, "SRID={};", srid)?;
+        }
+
         match *self {
-            Point::Point(ref p) => write!(formatter, "POINT ({})", p),
-            Point::PointZ(ref p) => write!(formatter, "POINTZ ({})", p),
+            Point::Point(ref p, _) => write!(formatter, "POINT ({})", p),
+            Point::PointZ(ref p, _) => write!(formatter, "POINTZ ({})", p),
         }
     }
 }
 
 impl Point {
+    pub fn new(coord: Coord2) -> Self {
+        Point::Point(coord, None)
+    }
+
+    pub fn newz(coord: Coord3) -> Self {
+        Point::PointZ(coord, None)
+    }
+
+    /// Sets the coordinate reference system, checking the point is within its
+    /// bounds.
+    pub fn with_srid(self, srid: Srid) -> Result<Self, PointError> {
+        match self {
+            Point::Point(p, _) => {
+                p.check(srid)?;
+                Ok(Point::Point(p, Some(srid)))
+            }
+            Point::PointZ(p, _) => {
+                p.check(srid)?;
+                Ok(Point::PointZ(p, Some(srid)))
+            }
+        }
+    }
+
+    pub fn srid(&self) -> Option<Srid> {
+        match *self {
+            Point::Point(_, srid) => srid,
+            Point::PointZ(_, srid) => srid,
+        }
+    }
+
     pub fn dimension(&self) -> u8 {
         match *self {
-            Point::Point(ref p) => p.dimension(),
-            Point::PointZ(ref p) => p.dimension(),
+            Point::Point(ref p, _) => p.dimension(),
+            Point::PointZ(ref p, _) => p.dimension(),
         }
     }
 
     pub fn x(&self) -> f64 {
         match *self {
-            Point::Point(ref p) => p.x(),
-            Point::PointZ(ref p) => p.x(),
+            Point::Point(ref p, _) => p.x(),
+            Point::PointZ(ref p, _) => p.x(),
         }
     }
 
     pub fn y(&self) -> f64 {
         match *self {
-            Point::Point(ref p) => p.y(),
-            Point::PointZ(ref p) => p.y(),
+            Point::Point(ref p, _) => p.y(),
+            Point::PointZ(ref p, _) => p.y(),
         }
     }
 
     pub fn z(&self) -> Option<f64> {
         match *self {
-            Point::PointZ(ref p) => Some(p.z()),
+            Point::PointZ(ref p, _) => Some(p.z()),
             _ => None,
         }
     }
+
+    /// Rounds every coordinate to the given number of decimal places. Use it
+    /// to get a canonical representation at a known precision.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::point::Point;
+    /// let a = Point::parse("POINT (10.00001 25)").unwrap().round(3);
+    /// let b = Point::parse("POINT (10.0 25.000)").unwrap();
+    /// assert_eq!(a.to_string(), b.to_string());
+    /// ```
+    pub fn round(&self, decimals: u32) -> Self {
+        match *self {
+            Point::Point(ref p, srid) => Point::Point(p.round(decimals), srid),
+            Point::PointZ(ref p, srid) => Point::PointZ(p.round(decimals), srid),
+        }
+    }
+
+    /// Returns the point as WGS84 `(longitude, latitude)`. Points without an
+    /// SRID are assumed to be already expressed in WGS84.
+    pub fn lon_lat(&self) -> Result<(f64, f64), PointError> {
+        match self.srid() {
+            None | Some(Srid::WGS84) => Ok((self.x(), self.y())),
+            Some(srid) => Ok(crs::transform(self.x(), self.y(), srid, Srid::WGS84)?),
+        }
+    }
+
+    /// Great-circle distance in metres to the given point.
+    pub fn haversine(&self, other: &Point) -> Result<f64, PointError> {
+        let (x1, y1) = self.lon_lat()?;
+        let (x2, y2) = other.lon_lat()?;
+
+        Ok(crs::haversine(x1, y1, x2, y2))
+    }
+
+    /// Geodesic distance in metres to the given point on the WGS84 ellipsoid.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::point::Point;
+    /// let a = Point::parse("SRID=4326;POINT (-0.1246 51.5007)").unwrap();
+    /// let b = Point::parse("SRID=27700;POINT (530268 179640)").unwrap();
+    /// assert!(a.distance(&b).unwrap() < 10.0);
+    /// ```
+    pub fn distance(&self, other: &Point) -> Result<f64, PointError> {
+        let (x1, y1) = self.lon_lat()?;
+        let (x2, y2) = other.lon_lat()?;
+
+        Ok(crs::vincenty(x1, y1, x2, y2)?)
+    }
+
+    /// Transforms the point to the given reference system. The point must
+    /// declare its own SRID.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::crs::Srid;
+    /// use sac::value::point::Point;
+    /// let point = Point::parse("SRID=4326;POINT (-2 49)").unwrap();
+    /// let grid = point.transform(Srid::BNG).unwrap();
+    /// assert_eq!(grid.srid(), Some(Srid::BNG));
+    /// ```
+    pub fn transform(&self, to: Srid) -> Result<Self, PointError> {
+        let from = self.srid().ok_or(CrsError::MissingSrid)?;
+
+        match *self {
+            Point::Point(ref p, _) => Ok(Point::Point(p.transform(from, to)?, Some(to))),
+            Point::PointZ(ref p, _) => Ok(Point::PointZ(p.transform(from, to)?, Some(to))),
+        }
+    }
 }
 
 impl Parse for Point {
@@ -183,30 +483,35 @@ impl Parse for Point {
     fn parse(s: &str) -> Result<Self, Self::Err> {
         lazy_static! {
             static ref RE_SET: Vec<Regex> = vec![
-                Regex::new(r"^POINT\s\((\d+(?:\.\d+)?)\s(\d+(?:\.\d+)?)\)$").unwrap(),
-                Regex::new(r"^POINTZ\s\((\d+(?:\.\d+)?)\s(\d+(?:\.\d+)?)\s(\d+(?:\.\d+)?)\)$")
-                    .unwrap(),
+                Regex::new(r"^POINT\s\((-?\d+(?:\.\d+)?)\s(-?\d+(?:\.\d+)?)\)$").unwrap(),
+                Regex::new(
+                    r"^POINTZ\s\((-?\d+(?:\.\d+)?)\s(-?\d+(?:\.\d+)?)\s(-?\d+(?:\.\d+)?)\)$"
+                ).unwrap(),
             ];
             static ref RE: RegexSet = RegexSet::new(RE_SET.iter().map(|re| re.as_str())).unwrap();
         }
 
+        let (srid, s) = crs::split_ewkt(s)?;
+
         let ms: Vec<_> = RE.matches(s).into_iter().collect();
         if !ms.is_empty() {
             let idx = ms[0];
             let caps = RE_SET[idx].captures(s).unwrap();
 
-            match idx {
-                0 => Ok(Point::Point(Coord2(
-                    caps[1].parse().unwrap(),
-                    caps[2].parse().unwrap(),
-                ))),
-                1 => Ok(Point::PointZ(Coord3(
+            let point = match idx {
+                0 => Point::new(Coord2(caps[1].parse().unwrap(), caps[2].parse().unwrap())),
+                1 => Point::newz(Coord3(
                     caps[1].parse().unwrap(),
                     caps[2].parse().unwrap(),
                     caps[3].parse().unwrap(),
-                ))),
+                )),
 
                 _ => unreachable!(),
+            };
+
+            match srid {
+                Some(srid) => point.with_srid(srid),
+                None => Ok(point),
             }
         } else {
             Err(PointError::ParseError)
@@ -214,6 +519,94 @@ impl Parse for Point {
     }
 }
 
+impl Diagnose for PointError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            PointError::InvalidCrs(ref e) => e.diagnose(input),
+            _ => diagnostic::locate(input, scan(input), "WKT point", self.to_string())
+                .with_fix(diagnostic::first_valid(input, wkt_fixes(input), |s| Point::parse(s).is_ok())),
+        }
+    }
+}
+
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+
+    crs::scan_srid(&mut s)?;
+    s.literal("POINT")?;
+    let dimension = scan_dimension(&mut s)?;
+    s.literal("(")?;
+    scan_coord(&mut s, dimension)?;
+    s.literal(")")?;
+    s.end()
+}
+
+/// Checks the optional `Z` after a WKT keyword and the space that follows.
+pub(crate) fn scan_dimension(s: &mut Scanner) -> Result<u8, Expected> {
+    let dimension = if s.eat('Z') { 3 } else { 2 };
+
+    if s.eat(' ') {
+        Ok(dimension)
+    } else if dimension == 3 {
+        Err(s.expected("space"))
+    } else {
+        Err(s.expected("Z or space"))
+    }
+}
+
+/// Checks a coordinate of the given dimension as numbers separated by a
+/// space.
+pub(crate) fn scan_coord(s: &mut Scanner, dimension: u8) -> Result<(), Expected> {
+    for i in 0..dimension {
+        if i > 0 && !s.eat(' ') {
+            return Err(s.expected("space"));
+        }
+
+        s.eat('-');
+        s.digits(1, usize::MAX)?;
+        if s.eat('.') {
+            s.digits(1, usize::MAX)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Candidate corrections for WKT geometries: surrounding whitespace, a
+/// lowercase keyword and irregular spacing.
+pub(crate) fn wkt_fixes(input: &str) -> Vec<String> {
+    let trimmed = input.trim().to_owned();
+    let keyword = wkt_keyword(&trimmed);
+    let spacing = wkt_spacing(&keyword);
+
+    vec![trimmed, keyword, spacing]
+}
+
+/// Uppercases everything before the first parenthesis.
+pub(crate) fn wkt_keyword(s: &str) -> String {
+    let split = s.find('(').unwrap_or(s.len());
+
+    format!("{}{}", s[..split].to_uppercase(), &s[split..])
+}
+
+/// Collapses runs of whitespace and puts single spaces where WKT expects
+/// them: after the keyword and between coordinates but not around
+/// parentheses or before commas.
+pub(crate) fn wkt_spacing(s: &str) -> String {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let mut s = words
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(" ,", ",");
+
+    if let Some(i) = s.find('(').filter(|i| *i > 0 && !s[..*i].ends_with(' ')) {
+        s.insert(i, ' ');
+    }
+
+    s
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,4 +629,63 @@ mod tests {
         assert_eq!(point.z(), Some(1.0));
     }
 
+    #[test]
+    fn parse_negative() {
+        let point = Point::parse("POINT (-1.5 -2)").unwrap();
+
+        assert_eq!(point.x(), -1.5);
+        assert_eq!(point.y(), -2.0);
+    }
+
+    #[test]
+    fn display_canonical() {
+        let point = Point::parse("POINTZ (10.0 25.00 -0.0)").unwrap();
+
+        assert_eq!(point.to_string(), "POINTZ (10 25 0)");
+    }
+
+    #[test]
+    fn display_rounded() {
+        let point = Point::parse("POINT (0.123456 -0.0001)").unwrap().round(3);
+
+        assert_eq!(point.to_string(), "POINT (0.123 0)");
+    }
+
+    #[test]
+    fn parse_ewkt() {
+        let point = Point::parse("SRID=4326;POINT (-0.1 51.5)").unwrap();
+
+        assert_eq!(point.srid(), Some(Srid::WGS84));
+        assert_eq!(point.to_string(), "SRID=4326;POINT (-0.1 51.5)");
+    }
+
+    #[test]
+    fn parse_ewkt_out_of_bounds() {
+        let actual = Point::parse("SRID=4326;POINT (51.5 -181)");
+
+        assert!(actual.is_err(), "Expected latitude out of bounds");
+    }
+
+    #[test]
+    fn transform_without_srid() {
+        let point = Point::parse("POINT (0 0)").unwrap();
+
+        assert_eq!(
+            format!("{:?}", point.transform(Srid::BNG)),
+            "Err(InvalidCrs(MissingSrid))"
+        );
+    }
+
+    #[test]
+    fn negative_zero_equals_zero() {
+        use std::collections::HashSet;
+
+        let a = Point::parse("POINT (-0 1)").unwrap();
+        let b = Point::parse("POINT (0 1)").unwrap();
+        let set: HashSet<Point> = vec![a.clone(), b.clone()].into_iter().collect();
+
+        assert_eq!(a, b);
+        assert_eq!(set.len(), 1);
+        assert!(Point::parse("POINT (-1 5)").unwrap() < b);
+    }
 }
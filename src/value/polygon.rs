@@ -4,7 +4,9 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
-use super::point::{Coord, Coord2, Coord3};
+use super::crs::{self, CrsError, Srid};
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
+use super::point::{self, Coord, Coord2, Coord3};
 use super::Parse;
 use regex::{Regex, RegexSet};
 use std::fmt::{self, Debug, Display};
@@ -21,6 +23,14 @@ pub enum PolygonError {
     ParseFloatError(#[cause] ParseFloatError),
     #[fail(display = "Expected {} polygon coordinates.", _0)]
     InvalidCoord(String),
+    #[fail(display = "Invalid coordinate reference system")]
+    InvalidCrs(#[cause] CrsError),
+}
+
+impl From<CrsError> for PolygonError {
+    fn from(err: CrsError) -> PolygonError {
+        PolygonError::InvalidCrs(err)
+    }
 }
 
 impl From<ParseFloatError> for PolygonError {
@@ -29,7 +39,7 @@ impl From<ParseFloatError> for PolygonError {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Ring<T: Coord>(Vec<T>);
 
 impl<T: Coord> Ring<T> {
@@ -40,6 +50,23 @@ impl<T: Coord> Ring<T> {
     pub fn to_vec(&self) -> &Vec<T> {
         &self.0
     }
+
+    pub fn round(&self, decimals: u32) -> Self {
+        Ring(self.0.iter().map(|c| c.round(decimals)).collect())
+    }
+
+    fn check(&self, srid: Srid) -> Result<(), CrsError> {
+        self.0.iter().try_for_each(|c| c.check(srid))
+    }
+
+    fn transform(&self, from: Srid, to: Srid) -> Result<Self, CrsError> {
+        let vec = self.0
+            .iter()
+            .map(|c| c.transform(from, to))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Ring::new(vec))
+    }
 }
 
 impl<T: Coord> FromStr for Ring<T> {
@@ -47,6 +74,10 @@ impl<T: Coord> FromStr for Ring<T> {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let elements = parse_numbers(s)?;
 
+        if elements.iter().flat_map(|x| x.iter()).any(|n| !n.is_finite()) {
+            return Err(PolygonError::ParseError);
+        }
+
         let vec = elements
             .into_iter()
             .map(|x| match x.len() {
@@ -97,19 +128,25 @@ impl<T: Coord> Display for Ring<T> {
 /// POLYGON ((30 10, 40 40, 20 40, 10 20, 30 10))
 /// POLYGON ((35 10, 45 45, 15 40, 10 20, 35 10), (20 30, 35 35, 30 20, 20 30))
 /// POLYGON ((35 10, 45 45, 15 40, 10 20, 35 10), (20 30, 35 35, 30 20, 20 30), (10 10, 20 20, 30 30))
+///
+/// SRID=4326;POLYGON ((-1 50, 1 50, 1 52, -1 52, -1 50))
 /// ```
 ///
 /// Inner rings should not cross each other nor the outer ring. This implementation
 /// does not check this rule.
-#[derive(Clone, PartialEq)]
+///
+/// As with points, the coordinate reference system can be declared using EWKT.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Polygon {
     Polygon {
         outer_ring: Ring<Coord2>,
         inner_rings: Vec<Ring<Coord2>>,
+        srid: Option<Srid>,
     },
     PolygonZ {
         outer_ring: Ring<Coord3>,
         inner_rings: Vec<Ring<Coord3>>,
+        srid: Option<Srid>,
     },
 }
 
@@ -121,10 +158,15 @@ impl Debug for Polygon {
 
 impl Display for Polygon {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(srid) = self.srid() {
+            write!(formatter, "SRID={};", srid)?;
+        }
+
         match *self {
             Polygon::Polygon {
                 ref outer_ring,
                 ref inner_rings,
+                ..
             } => {
                 write!(formatter, "POLYGON ({}", outer_ring)?;
 
@@ -138,6 +180,7 @@ impl Display for Polygon {
             Polygon::PolygonZ {
                 ref outer_ring,
                 ref inner_rings,
+                ..
             } => {
                 write!(formatter, "POLYGONZ ({}", outer_ring)?;
 
@@ -157,6 +200,7 @@ impl Polygon {
         Polygon::Polygon {
             outer_ring,
             inner_rings,
+            srid: None,
         }
     }
 
@@ -164,10 +208,189 @@ impl Polygon {
         Polygon::PolygonZ {
             outer_ring,
             inner_rings,
+            srid: None,
+        }
+    }
+
+    /// Sets the coordinate reference system, checking every coordinate is
+    /// within its bounds.
+    pub fn with_srid(self, srid: Srid) -> Result<Self, PolygonError> {
+        match self {
+            Polygon::Polygon {
+                outer_ring,
+                inner_rings,
+                ..
+            } => {
+                outer_ring.check(srid)?;
+                for ring in &inner_rings {
+                    ring.check(srid)?;
+                }
+
+                Ok(Polygon::Polygon {
+                    outer_ring,
+                    inner_rings,
+                    srid: Some(srid),
+                })
+            }
+            Polygon::PolygonZ {
+                outer_ring,
+                inner_rings,
+                ..
+            } => {
+                outer_ring.check(srid)?;
+                for ring in &inner_rings {
+                    ring.check(srid)?;
+                }
+
+                Ok(Polygon::PolygonZ {
+                    outer_ring,
+                    inner_rings,
+                    srid: Some(srid),
+                })
+            }
+        }
+    }
+
+    pub fn srid(&self) -> Option<Srid> {
+        match *self {
+            Polygon::Polygon { srid, .. } => srid,
+            Polygon::PolygonZ { srid, .. } => srid,
+        }
+    }
+
+    /// Rounds every coordinate to the given number of decimal places. Use it
+    /// to get a canonical representation at a known precision.
+    pub fn round(&self, decimals: u32) -> Self {
+        match *self {
+            Polygon::Polygon {
+                ref outer_ring,
+                ref inner_rings,
+                srid,
+            } => Polygon::Polygon {
+                outer_ring: outer_ring.round(decimals),
+                inner_rings: inner_rings.iter().map(|r| r.round(decimals)).collect(),
+                srid,
+            },
+            Polygon::PolygonZ {
+                ref outer_ring,
+                ref inner_rings,
+                srid,
+            } => Polygon::PolygonZ {
+                outer_ring: outer_ring.round(decimals),
+                inner_rings: inner_rings.iter().map(|r| r.round(decimals)).collect(),
+                srid,
+            },
+        }
+    }
+
+    /// Returns the polygon expressed in WGS84. Polygons without an SRID are
+    /// assumed to be already expressed in WGS84.
+    pub fn lon_lat(&self) -> Result<Self, PolygonError> {
+        match self.srid() {
+            None | Some(Srid::WGS84) => Ok(self.clone()),
+            Some(_) => self.transform(Srid::WGS84),
+        }
+    }
+
+    /// Returns the horizontal components of every ring, outer ring first.
+    pub fn rings(&self) -> Vec<Vec<(f64, f64)>> {
+        fn xy<T: Coord>(ring: &Ring<T>) -> Vec<(f64, f64)> {
+            ring.to_vec().iter().map(|c| (c.x(), c.y())).collect()
+        }
+
+        match *self {
+            Polygon::Polygon {
+                ref outer_ring,
+                ref inner_rings,
+                ..
+            } => Some(outer_ring)
+                .into_iter()
+                .chain(inner_rings.iter())
+                .map(xy)
+                .collect(),
+            Polygon::PolygonZ {
+                ref outer_ring,
+                ref inner_rings,
+                ..
+            } => Some(outer_ring)
+                .into_iter()
+                .chain(inner_rings.iter())
+                .map(xy)
+                .collect(),
+        }
+    }
+
+    /// Returns the bounding box of the outer ring as `(min_x, min_y, max_x, max_y)`.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let rings = self.rings();
+
+        rings[0].iter().fold(
+            (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        )
+    }
+
+    /// Checks whether the given coordinate lies inside the outer ring and
+    /// outside every inner ring. Computed on the plane using the even-odd rule.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let rings = self.rings();
+        let (outer, inner) = rings.split_first().unwrap();
+
+        ring_contains(outer, x, y) && !inner.iter().any(|ring| ring_contains(ring, x, y))
+    }
+
+    /// Transforms the polygon to the given reference system. The polygon must
+    /// declare its own SRID.
+    pub fn transform(&self, to: Srid) -> Result<Self, PolygonError> {
+        let from = self.srid().ok_or(CrsError::MissingSrid)?;
+
+        match *self {
+            Polygon::Polygon {
+                ref outer_ring,
+                ref inner_rings,
+                ..
+            } => Ok(Polygon::Polygon {
+                outer_ring: outer_ring.transform(from, to)?,
+                inner_rings: inner_rings
+                    .iter()
+                    .map(|r| r.transform(from, to))
+                    .collect::<Result<Vec<_>, _>>()?,
+                srid: Some(to),
+            }),
+            Polygon::PolygonZ {
+                ref outer_ring,
+                ref inner_rings,
+                ..
+            } => Ok(Polygon::PolygonZ {
+                outer_ring: outer_ring.transform(from, to)?,
+                inner_rings: inner_rings
+                    .iter()
+                    .map(|r| r.transform(from, to))
+                    .collect::<Result<Vec<_>, _>>()?,
+                srid: Some(to),
+            }),
         }
     }
 }
 
+fn ring_contains(ring: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+
+    for i in 0..ring.len() {
+        let (xi, yi) = ring[i];
+        let (xj, yj) = ring[j];
+
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+
+        j = i;
+    }
+
+    inside
+}
+
 fn parse_rings<T: Coord>(s: &str) -> Result<(Ring<T>, Vec<Ring<T>>), PolygonError> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"(?:\((.+?)\)(?:,\s)?)").unwrap();
@@ -198,25 +421,32 @@ impl Parse for Polygon {
             static ref RE: RegexSet = RegexSet::new(RE_SET.iter().map(|re| re.as_str())).unwrap();
         }
 
+        let (srid, s) = crs::split_ewkt(s)?;
+
         let ms: Vec<_> = RE.matches(s).into_iter().collect();
         if !ms.is_empty() {
             let idx = ms[0];
             let caps = RE_SET[idx].captures(s).unwrap();
 
-            match idx {
+            let polygon = match idx {
                 0 => {
                     let (outer_ring, inner_rings) =
                         parse_rings::<Coord2>(&caps[1].parse::<String>().unwrap())?;
 
-                    Ok(Polygon::new(outer_ring, inner_rings))
+                    Polygon::new(outer_ring, inner_rings)
                 }
                 1 => {
                     let (outer_ring, inner_rings) =
                         parse_rings::<Coord3>(&caps[1].parse::<String>().unwrap())?;
 
-                    Ok(Polygon::newz(outer_ring, inner_rings))
+                    Polygon::newz(outer_ring, inner_rings)
                 }
                 _ => unreachable!(),
+            };
+
+            match srid {
+                Some(srid) => polygon.with_srid(srid),
+                None => Ok(polygon),
             }
         } else {
             Err(PolygonError::ParseError)
@@ -224,6 +454,44 @@ impl Parse for Polygon {
     }
 }
 
+impl Diagnose for PolygonError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        match *self {
+            PolygonError::InvalidCrs(ref e) => e.diagnose(input),
+            _ => diagnostic::locate(input, scan(input), "WKT polygon", self.to_string())
+                .with_fix(diagnostic::first_valid(input, point::wkt_fixes(input), |s| Polygon::parse(s).is_ok())),
+        }
+    }
+}
+
+fn scan(input: &str) -> Result<(), Expected> {
+    let mut s = Scanner::new(input);
+
+    crs::scan_srid(&mut s)?;
+    s.literal("POLYGON")?;
+    let dimension = point::scan_dimension(&mut s)?;
+    s.literal("(")?;
+
+    loop {
+        s.literal("(")?;
+
+        loop {
+            point::scan_coord(&mut s, dimension)?;
+            if s.one_of(&[',', ')'])? == ')' {
+                break;
+            }
+            s.take_while(|c| c == ' ');
+        }
+
+        if s.one_of(&[',', ')'])? == ')' {
+            break;
+        }
+        s.take_while(|c| c == ' ');
+    }
+
+    s.end()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,6 +538,48 @@ mod tests {
         assert_eq!(actual, Some(expected));
     }
 
+    #[test]
+    fn parse_polygon_ewkt() {
+        let raw = "SRID=4326;POLYGON ((-1 50, 1 50, 1 52, -1 50))";
+        let actual = Polygon::parse(raw).unwrap();
+
+        assert_eq!(actual.srid(), Some(Srid::WGS84));
+        assert_eq!(actual.to_string(), raw);
+    }
+
+    #[test]
+    fn parse_polygon_ewkt_out_of_bounds() {
+        let actual = Polygon::parse("SRID=4326;POLYGON ((-1 50, 1 50, 200 52, -1 50))");
+
+        assert!(actual.is_err(), "Expected longitude out of bounds");
+    }
+
+    #[test]
+    fn parse_polygon_non_finite() {
+        let actual = Polygon::parse("POLYGON ((0 0, inf 10, NaN 20))");
+
+        assert!(actual.is_err(), "Expected non-finite numbers to be rejected");
+    }
+
+    #[test]
+    fn display_canonical() {
+        let actual = Polygon::parse("POLYGON ((0.0 -0, 10.50 10, 20 20.000))").unwrap();
+
+        assert_eq!(actual.to_string(), "POLYGON ((0 0, 10.5 10, 20 20))");
+    }
+
+    #[test]
+    fn contains_with_hole() {
+        let polygon =
+            Polygon::parse("POLYGON ((0 0, 10 0, 10 10, 0 10, 0 0), (4 4, 6 4, 6 6, 4 6, 4 4))")
+                .unwrap();
+
+        assert!(polygon.contains(1.0, 1.0));
+        assert!(!polygon.contains(5.0, 5.0));
+        assert!(!polygon.contains(11.0, 5.0));
+        assert_eq!(polygon.bounds(), (0.0, 0.0, 10.0, 10.0));
+    }
+
     mod rings2 {
         use super::*;
 
@@ -30,8 +30,23 @@ impl Serialize for Value {
             }
             Value::Bool(v) => serializer.serialize_bool(v),
             Value::Curie(ref v) => serializer.serialize_str(&v.to_string()),
+            Value::Custom(ref v) => serializer.serialize_str(v.as_str()),
+            Value::Decimal(ref v) => serializer.serialize_str(&v.to_string()),
             Value::Hash(ref v) => serializer.serialize_str(&v.to_string()),
-            Value::Integer(ref v) => serializer.serialize_i64(v.0),
+            Value::Integer(ref v) => match v.to_i64() {
+                Some(i) => serializer.serialize_i64(i),
+                None => serializer.serialize_str(&v.to_string()),
+            },
+            Value::LangString(ref v) => {
+                use serde::ser::SerializeMap;
+                let mut map = serializer.serialize_map(None)?;
+                for tag in v.tags() {
+                    map.serialize_entry(tag.as_str(), &v.get(tag.as_str()))?;
+                }
+                map.end()
+            }
+            Value::Point(ref v) => serializer.serialize_str(&v.to_string()),
+            Value::Polygon(ref v) => serializer.serialize_str(&v.to_string()),
             Value::String(ref v) => serializer.serialize_str(v),
             Value::Text(ref v) => serializer.serialize_str(&v.to_string()),
             Value::Url(ref v) => serializer.serialize_str(&v.to_string()),
@@ -47,8 +62,6 @@ impl Serialize for Value {
             // Value::DateTime(ref v) => Debug::fmt(v, formatter),
             // Value::Timestamp(ref v) => Debug::fmt(v, formatter),
             // Value::Period(ref v) => Debug::fmt(v, formatter),
-            // Value::Point(ref v) => Debug::fmt(v, formatter),
-            // Value::Polygon(ref v) => Debug::fmt(v, formatter),
         }
     }
 }
@@ -115,17 +128,28 @@ mod tests {
     #[test]
     fn serialize_integer() {
         use value::integer::Integer;
-        let input = Value::Integer(Integer(0));
+        let input = Value::Integer(Integer::from(0));
         let expected = r#"Ok("0")"#.to_string();
         let res = serde_json::to_string(&input);
 
         assert_eq!(format!("{:?}", res), expected);
     }
 
+    #[test]
+    fn serialize_big_integer() {
+        use value::integer::Integer;
+        use value::Parse;
+        let input = Value::Integer(Integer::parse("12345678901234567890").unwrap());
+        let expected = r#"Ok("\"12345678901234567890\"")"#.to_string();
+        let res = serde_json::to_string(&input);
+
+        assert_eq!(format!("{:?}", res), expected);
+    }
+
     #[test]
     fn serialize_integer_list() {
         use value::integer::Integer;
-        let list = vec![Value::Integer(Integer(0)), Value::Integer(Integer(1))];
+        let list = vec![Value::Integer(Integer::from(0)), Value::Integer(Integer::from(1))];
         let input = Value::List(list);
         let expected = r#"Ok("[0,1]")"#.to_string();
         let res = serde_json::to_string(&input);
@@ -153,4 +177,38 @@ mod tests {
         assert_eq!(format!("{:?}", res), expected);
     }
 
+    #[test]
+    fn serialize_decimal() {
+        use value::decimal::Decimal;
+        use value::Parse;
+        let input = Value::Decimal(Decimal::parse("12345678901234567890.10").unwrap());
+        let expected = r#"Ok("\"12345678901234567890.1\"")"#.to_string();
+        let res = serde_json::to_string(&input);
+
+        assert_eq!(format!("{:?}", res), expected);
+    }
+
+    #[test]
+    fn serialize_langstring() {
+        use value::langstring::LangString;
+        use value::Parse;
+        let input = Value::LangString(LangString::parse(r#"{"en": "Wales", "CY": "Cymru"}"#).unwrap());
+        let expected = r#"Ok("{\"cy\":\"Cymru\",\"en\":\"Wales\"}")"#.to_string();
+        let res = serde_json::to_string(&input);
+
+        assert_eq!(format!("{:?}", res), expected);
+    }
+
+    #[test]
+    fn serialize_point() {
+        use value::point::Point;
+        use value::Parse;
+        let a = Value::Point(Point::parse("POINT (10 25)").unwrap());
+        let b = Value::Point(Point::parse("POINT (10.0 25.00)").unwrap());
+        let expected = r#"Ok("\"POINT (10 25)\"")"#.to_string();
+
+        assert_eq!(format!("{:?}", serde_json::to_string(&a)), expected);
+        assert_eq!(format!("{:?}", serde_json::to_string(&b)), expected);
+    }
+
 }
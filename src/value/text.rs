@@ -4,22 +4,235 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+use pulldown_cmark::{html, Event, Parser, Tag};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug, Display};
-use pulldown_cmark::{Event, Parser};
+use std::iter::Peekable;
+use std::str::FromStr;
 
+use super::diagnostic::{Diagnose, Diagnostic};
+use super::url::{SchemePolicy, Url, UrlError};
 use super::Parse;
 
 #[derive(Debug, Fail)]
 pub enum TextError {
-    #[fail(display = "HTML is not allowed in Text. Found {}", value)]
-    DisallowedHtml { value: String },
-    #[fail(display = "Inline HTML is not allowed in Text. Found {}", value)]
-    DisallowedInlineHtml { value: String },
+    #[fail(display = "HTML is not allowed in Text. Found {} at byte {}", value, offset)]
+    DisallowedHtml { value: String, offset: usize },
+    #[fail(display = "Inline HTML is not allowed in Text. Found {} at byte {}", value, offset)]
+    DisallowedInlineHtml { value: String, offset: usize },
+    #[fail(display = "Markdown {} is not allowed in Text. Found at byte {}", construct, offset)]
+    DisallowedConstruct { construct: Construct, offset: usize },
+    #[fail(display = "Invalid link {} at byte {}: {}", destination, offset, cause)]
+    InvalidLink {
+        destination: String,
+        offset: usize,
+        #[cause]
+        cause: UrlError,
+    },
+    #[fail(display = "Unknown Markdown construct {}", _0)]
+    UnknownConstruct(String),
     #[fail(display = "Validation errors")]
     List(Vec<TextError>),
 }
 
-#[derive(Clone, PartialEq)]
+/// A Markdown construct that can be allowed or disallowed by a `Policy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Construct {
+    Paragraph,
+    Header,
+    Emphasis,
+    Strong,
+    Link,
+    Code,
+    CodeBlock,
+    BlockQuote,
+    List,
+    Rule,
+    Image,
+    Table,
+    Footnote,
+}
+
+impl Construct {
+    pub fn all() -> Vec<Construct> {
+        vec![
+            Construct::Paragraph,
+            Construct::Header,
+            Construct::Emphasis,
+            Construct::Strong,
+            Construct::Link,
+            Construct::Code,
+            Construct::CodeBlock,
+            Construct::BlockQuote,
+            Construct::List,
+            Construct::Rule,
+            Construct::Image,
+            Construct::Table,
+            Construct::Footnote,
+        ]
+    }
+
+    fn from_tag(tag: &Tag) -> Construct {
+        match *tag {
+            Tag::Paragraph => Construct::Paragraph,
+            Tag::Rule => Construct::Rule,
+            Tag::Header(_) => Construct::Header,
+            Tag::BlockQuote => Construct::BlockQuote,
+            Tag::CodeBlock(_) => Construct::CodeBlock,
+            Tag::List(_) | Tag::Item => Construct::List,
+            Tag::FootnoteDefinition(_) => Construct::Footnote,
+            Tag::Table(_) | Tag::TableHead | Tag::TableRow | Tag::TableCell => Construct::Table,
+            Tag::Emphasis => Construct::Emphasis,
+            Tag::Strong => Construct::Strong,
+            Tag::Code => Construct::Code,
+            Tag::Link(..) => Construct::Link,
+            Tag::Image(..) => Construct::Image,
+        }
+    }
+}
+
+impl Display for Construct {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Construct::Paragraph => "paragraph",
+            Construct::Header => "header",
+            Construct::Emphasis => "emphasis",
+            Construct::Strong => "strong",
+            Construct::Link => "link",
+            Construct::Code => "code",
+            Construct::CodeBlock => "code-block",
+            Construct::BlockQuote => "block-quote",
+            Construct::List => "list",
+            Construct::Rule => "rule",
+            Construct::Image => "image",
+            Construct::Table => "table",
+            Construct::Footnote => "footnote",
+        };
+
+        Display::fmt(name, formatter)
+    }
+}
+
+impl FromStr for Construct {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Construct::all()
+            .into_iter()
+            .find(|c| c.to_string() == s)
+            .ok_or_else(|| TextError::UnknownConstruct(s.to_owned()))
+    }
+}
+
+/// The set of Markdown constructs allowed in a Text. HTML is never allowed.
+///
+/// The default policy is the core set of features: paragraphs, headers,
+/// bold, italics, links and inline code. Link and image destinations must be
+/// valid `Url` values with a scheme allowed by the policy (http or https by
+/// default) unless link checking is turned off.
+///
+/// ```
+/// use sac::value::text::{Construct, Policy, Text};
+/// let policy = Policy::core().allow(Construct::List);
+/// assert!(Text::parse_with("* foo\n* bar", &policy).is_ok());
+/// assert!(Text::parse_with("> foo", &policy).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    allowed: BTreeSet<Construct>,
+    check_links: bool,
+    schemes: SchemePolicy,
+}
+
+impl Policy {
+    pub fn core() -> Self {
+        Policy {
+            allowed: vec![
+                Construct::Paragraph,
+                Construct::Header,
+                Construct::Emphasis,
+                Construct::Strong,
+                Construct::Link,
+                Construct::Code,
+            ].into_iter()
+                .collect(),
+            check_links: true,
+            schemes: SchemePolicy::default(),
+        }
+    }
+
+    /// Allows every construct and any link destination. HTML is still
+    /// disallowed.
+    pub fn permissive() -> Self {
+        Policy {
+            allowed: Construct::all().into_iter().collect(),
+            check_links: false,
+            schemes: SchemePolicy::default(),
+        }
+    }
+
+    pub fn allow(mut self, construct: Construct) -> Self {
+        self.allowed.insert(construct);
+        self
+    }
+
+    pub fn deny(mut self, construct: Construct) -> Self {
+        self.allowed.remove(&construct);
+        self
+    }
+
+    pub fn allows(&self, construct: Construct) -> bool {
+        self.allowed.contains(&construct)
+    }
+
+    /// Sets whether link and image destinations must be valid `Url` values.
+    pub fn check_links(mut self, check: bool) -> Self {
+        self.check_links = check;
+        self
+    }
+
+    /// Sets the schemes allowed in link and image destinations.
+    pub fn schemes(mut self, schemes: SchemePolicy) -> Self {
+        self.schemes = schemes;
+        self
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::core()
+    }
+}
+
+/// A link or image found in a Text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    /// Either `Construct::Link` or `Construct::Image`.
+    pub construct: Construct,
+    pub destination: String,
+    pub title: String,
+    /// Byte offset where the link starts.
+    pub offset: usize,
+}
+
+impl Link {
+    /// Validates the destination as a `Url` with one of the given schemes.
+    pub fn check(&self, schemes: &SchemePolicy) -> Result<Url, TextError> {
+        Url::parse_with(&self.destination, schemes).map_err(|cause| TextError::InvalidLink {
+            destination: self.destination.clone(),
+            offset: self.offset,
+            cause,
+        })
+    }
+}
+
+/// A UTF-8 string as defined by [http://spec.commonmark.org/].
+///
+/// For security reasons, the Unicode character U+0000 is replaced with the
+/// REPLACEMENT CHARACTER (U+FFFD).
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Text(String);
 
 impl Debug for Text {
@@ -38,23 +251,659 @@ impl Parse for Text {
     type Err = TextError;
 
     fn parse(s: &str) -> Result<Self, Self::Err> {
-        let errors: Vec<TextError> = Parser::new(s)
-            .filter_map(|ev| match ev {
-                // Escape inline html
-                Event::Html(html) => Some(TextError::DisallowedHtml {
+        Text::parse_with(s, &Policy::default())
+    }
+}
+
+impl Text {
+    /// Parses a Text checking every construct is allowed by the given policy.
+    pub fn parse_with(s: &str, policy: &Policy) -> Result<Self, TextError> {
+        let s = s.replace('\u{0}', "\u{FFFD}");
+        let mut errors = Vec::new();
+        let mut parser = Parser::new(&s);
+
+        loop {
+            let offset = start_offset(&s, parser.get_offset());
+            let ev = match parser.next() {
+                Some(ev) => ev,
+                None => break,
+            };
+
+            match ev {
+                Event::Html(html) => errors.push(TextError::DisallowedHtml {
                     value: format!("{}", html),
+                    offset,
                 }),
-                Event::InlineHtml(html) => Some(TextError::DisallowedInlineHtml {
+                Event::InlineHtml(html) => errors.push(TextError::DisallowedInlineHtml {
                     value: format!("{}", html),
+                    offset,
                 }),
-                _ => None,
-            })
-            .collect();
+                Event::FootnoteReference(_) if !policy.allows(Construct::Footnote) => {
+                    errors.push(TextError::DisallowedConstruct {
+                        construct: Construct::Footnote,
+                        offset,
+                    })
+                }
+                Event::Start(tag) => {
+                    let construct = Construct::from_tag(&tag);
+
+                    if !policy.allows(construct) {
+                        errors.push(TextError::DisallowedConstruct { construct, offset });
+                    } else if let Some(link) = link_from_tag(tag, offset) {
+                        if policy.check_links {
+                            if let Err(err) = link.check(&policy.schemes) {
+                                errors.push(err);
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
 
         if errors.is_empty() {
-            Ok(Text(s.to_owned()))
+            Ok(Text(s))
         } else {
             Err(TextError::List(errors))
         }
     }
+
+    /// Returns the links and images in the text in document order.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::text::Text;
+    /// let text = Text::parse("See [foo](https://example.org/foo \"Foo\").").unwrap();
+    /// let links = text.links();
+    /// assert_eq!(links[0].destination, "https://example.org/foo");
+    /// assert_eq!(links[0].title, "Foo");
+    /// assert_eq!(links[0].offset, 4);
+    /// ```
+    pub fn links(&self) -> Vec<Link> {
+        let mut links = Vec::new();
+        let mut parser = Parser::new(&self.0);
+
+        loop {
+            let offset = start_offset(&self.0, parser.get_offset());
+            match parser.next() {
+                Some(Event::Start(tag)) => links.extend(link_from_tag(tag, offset)),
+                Some(_) => (),
+                None => break,
+            }
+        }
+
+        links
+    }
+
+    /// Renders the text as HTML. Links and images with a scheme other than
+    /// http, https or mailto are neutralised.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::text::Text;
+    /// let text = Text::parse("foo *bar*").unwrap();
+    /// assert_eq!(text.to_html(), "<p>foo <em>bar</em></p>\n");
+    /// ```
+    pub fn to_html(&self) -> String {
+        let events = Parser::new(&self.0).map(|ev| match ev {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::Link(url, title)) => {
+                Event::Start(Tag::Link(sanitise_url(url), title))
+            }
+            Event::End(Tag::Link(url, title)) => Event::End(Tag::Link(sanitise_url(url), title)),
+            Event::Start(Tag::Image(url, title)) => {
+                Event::Start(Tag::Image(sanitise_url(url), title))
+            }
+            Event::End(Tag::Image(url, title)) => Event::End(Tag::Image(sanitise_url(url), title)),
+            ev => ev,
+        });
+
+        let mut buf = String::new();
+        html::push_html(&mut buf, events);
+
+        buf
+    }
+
+    /// Renders the text as plain text, dropping any Markdown markup.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::text::Text;
+    /// let text = Text::parse("# Title\n\nfoo *bar* [baz](http://example.org)").unwrap();
+    /// assert_eq!(text.to_plain(), "Title\n\nfoo bar baz");
+    /// ```
+    pub fn to_plain(&self) -> String {
+        let mut buf = String::new();
+
+        for ev in Parser::new(&self.0) {
+            match ev {
+                Event::Text(t) | Event::Html(t) | Event::InlineHtml(t) => buf.push_str(&t),
+                Event::SoftBreak | Event::HardBreak => buf.push('\n'),
+                Event::Start(Tag::Item) => buf.push_str("- "),
+                Event::End(Tag::Paragraph)
+                | Event::End(Tag::Header(_))
+                | Event::End(Tag::CodeBlock(_))
+                | Event::End(Tag::List(_))
+                | Event::End(Tag::Rule) => buf.push_str("\n\n"),
+                Event::End(Tag::Item) | Event::End(Tag::TableRow) | Event::End(Tag::TableHead) => {
+                    buf.push('\n')
+                }
+                Event::End(Tag::TableCell) => buf.push('\t'),
+                _ => (),
+            }
+        }
+
+        buf.trim_end().to_owned()
+    }
+
+    /// Re-emits the text as canonical CommonMark so equivalent Markdown
+    /// yields the same string and, therefore, the same hash.
+    ///
+    /// * Emphasis uses `*`, strong emphasis uses `**`.
+    /// * Headers are ATX (`#`), bullet lists use `-` and ordered lists `1.`.
+    /// * Code blocks are fenced and rules are `***`.
+    /// * Hard breaks are a backslash followed by a newline.
+    /// * Blocks are separated by a single blank line and trailing whitespace
+    ///   is removed.
+    ///
+    /// ```
+    /// use sac::value::Parse;
+    /// use sac::value::text::Text;
+    /// let a = Text::parse("Setext\n======\n\n__bold__ _it_  ").unwrap();
+    /// let b = Text::parse("# Setext\n\n**bold** *it*").unwrap();
+    /// assert_eq!(a.normalise(), b.normalise());
+    /// assert_eq!(a.normalise().to_string(), "# Setext\n\n**bold** *it*");
+    /// ```
+    pub fn normalise(&self) -> Text {
+        let mut events = MergeText(Parser::new(&self.0).peekable());
+        let blocks: Vec<String> = render_blocks(&mut events)
+            .into_iter()
+            .map(|(block, _)| block)
+            .collect();
+
+        Text(blocks.join("\n\n"))
+    }
+}
+
+/// Merges consecutive text events so escaping can look at the whole run of
+/// text regardless of how the parser split it.
+struct MergeText<'a, I: Iterator<Item = Event<'a>>>(Peekable<I>);
+
+impl<'a, I: Iterator<Item = Event<'a>>> Iterator for MergeText<'a, I> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        match self.0.next() {
+            Some(Event::Text(t)) => {
+                let mut text = t.into_owned();
+                while let Some(&Event::Text(_)) = self.0.peek() {
+                    if let Some(Event::Text(t)) = self.0.next() {
+                        text.push_str(&t);
+                    }
+                }
+                Some(Event::Text(text.into()))
+            }
+            ev => ev,
+        }
+    }
+}
+
+/// Renders a sequence of blocks until the end of the enclosing container.
+/// Each block is paired with whether it is a paragraph so lists can tell
+/// tight from loose items.
+fn render_blocks<'a, I>(events: &mut I) -> Vec<(String, bool)>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut blocks = Vec::new();
+    let mut pending = String::new();
+    // Adjacent lists would be merged into one so their markers alternate.
+    let mut alternate = None;
+
+    while let Some(ev) = events.next() {
+        let is_list = matches!(ev, Event::Start(Tag::List(_)));
+
+        let block = match ev {
+            Event::Start(Tag::Paragraph) => {
+                Some((render_inlines(events).trim_end().to_owned(), true))
+            }
+            Event::Start(Tag::Header(level)) => {
+                let content = render_inlines(events);
+                Some((
+                    format!("{} {}", "#".repeat(level as usize), content.trim_end()),
+                    false,
+                ))
+            }
+            Event::Start(Tag::BlockQuote) => {
+                let inner = join_blocks(render_blocks(events));
+                Some((prefix_lines(&inner, "> ", ">"), false))
+            }
+            Event::Start(Tag::CodeBlock(info)) => {
+                let mut content = String::new();
+                for ev in events.by_ref() {
+                    match ev {
+                        Event::Text(t) => content.push_str(&t),
+                        _ => break,
+                    }
+                }
+                let fence = "`".repeat(3.max(longest_run(&content, '`') + 1));
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                Some((format!("{}{}\n{}{}", fence, info.trim(), content, fence), false))
+            }
+            Event::Start(Tag::List(start)) => {
+                let alt = alternate == Some(false);
+                alternate = Some(alt);
+                Some((render_list(events, start, alt), false))
+            }
+            Event::Start(Tag::Rule) => {
+                events.next();
+                Some(("***".to_string(), false))
+            }
+            Event::End(_) => break,
+            ev => {
+                render_inline(ev, events, &mut pending);
+                None
+            }
+        };
+
+        if !is_list && block.is_some() {
+            alternate = None;
+        }
+
+        if let Some(block) = block {
+            if !pending.is_empty() {
+                blocks.push((pending.trim_end().to_owned(), false));
+                pending = String::new();
+            }
+            blocks.push(block);
+        }
+    }
+
+    if !pending.is_empty() {
+        blocks.push((pending.trim_end().to_owned(), false));
+    }
+
+    blocks
+}
+
+fn join_blocks(blocks: Vec<(String, bool)>) -> String {
+    blocks
+        .into_iter()
+        .map(|(block, _)| block)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn render_list<'a, I>(events: &mut I, start: Option<usize>, alternate: bool) -> String
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut items = Vec::new();
+
+    while let Some(ev) = events.next() {
+        match ev {
+            Event::Start(Tag::Item) => items.push(render_blocks(events)),
+            _ => break,
+        }
+    }
+
+    let loose = items.iter().any(|item| item.iter().any(|&(_, para)| para));
+    let separator = if loose { "\n\n" } else { "\n" };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, blocks)| {
+            let marker = match (start, alternate) {
+                (Some(n), false) => format!("{}. ", n + i),
+                (Some(n), true) => format!("{}) ", n + i),
+                (None, false) => "- ".to_string(),
+                (None, true) => "* ".to_string(),
+            };
+            let content = blocks
+                .into_iter()
+                .map(|(block, _)| block)
+                .collect::<Vec<_>>()
+                .join(separator);
+            let indent = " ".repeat(marker.len());
+
+            content
+                .lines()
+                .enumerate()
+                .map(|(n, line)| match (n, line.is_empty()) {
+                    (0, _) => format!("{}{}", marker, line),
+                    (_, true) => String::new(),
+                    (_, false) => format!("{}{}", indent, line),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim_end()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Renders inline events until the end of the enclosing tag.
+fn render_inlines<'a, I>(events: &mut I) -> String
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut out = String::new();
+
+    while let Some(ev) = events.next() {
+        match ev {
+            Event::End(_) => break,
+            ev => render_inline(ev, events, &mut out),
+        }
+    }
+
+    out
+}
+
+fn render_inline<'a, I>(ev: Event<'a>, events: &mut I, out: &mut String)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    match ev {
+        Event::Text(t) => escape(&t, out),
+        Event::Html(t) | Event::InlineHtml(t) => out.push_str(&t),
+        Event::SoftBreak => out.push('\n'),
+        Event::HardBreak => out.push_str("\\\n"),
+        Event::FootnoteReference(name) => {
+            out.push_str("[^");
+            out.push_str(&name);
+            out.push(']');
+        }
+        Event::Start(Tag::Emphasis) => {
+            let inner = render_inlines(events);
+            out.push_str(&format!("*{}*", inner));
+        }
+        Event::Start(Tag::Strong) => {
+            let inner = render_inlines(events);
+            out.push_str(&format!("**{}**", inner));
+        }
+        Event::Start(Tag::Code) => {
+            let mut code = String::new();
+            for ev in events.by_ref() {
+                match ev {
+                    Event::Text(t) => code.push_str(&t),
+                    _ => break,
+                }
+            }
+            let fence = "`".repeat(longest_run(&code, '`') + 1);
+            let pad = if code.starts_with('`') || code.ends_with('`') {
+                " "
+            } else {
+                ""
+            };
+            out.push_str(&format!("{}{}{}{}{}", fence, pad, code, pad, fence));
+        }
+        Event::Start(Tag::Link(url, title)) => {
+            let inner = render_inlines(events);
+            out.push_str(&format!("[{}]({})", inner, destination(&url, &title)));
+        }
+        Event::Start(Tag::Image(url, title)) => {
+            let inner = render_inlines(events);
+            out.push_str(&format!("![{}]({})", inner, destination(&url, &title)));
+        }
+        Event::Start(_) => out.push_str(&render_inlines(events)),
+        Event::End(_) => (),
+    }
+}
+
+fn destination(url: &str, title: &str) -> String {
+    let url = if url.is_empty() || url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+        format!("<{}>", url.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        url.to_owned()
+    };
+
+    if title.is_empty() {
+        url
+    } else {
+        format!(
+            "{} \"{}\"",
+            url,
+            title.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    }
+}
+
+/// Escapes any character that could be interpreted as Markdown.
+fn escape(text: &str, out: &mut String) {
+    lazy_static! {
+        static ref ENTITY: Regex = Regex::new(r"^&#?[A-Za-z0-9]+;").unwrap();
+        static ref ORDERED: Regex = Regex::new(r"^\d+[.)]").unwrap();
+    }
+
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    for (i, &(idx, c)) in chars.iter().enumerate() {
+        let prev = if i == 0 {
+            out.chars().last()
+        } else {
+            Some(chars[i - 1].1)
+        };
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let line_start = prev.map_or(true, |p| p == '\n');
+
+        let escaped = match c {
+            '\\' | '*' | '`' | '[' | ']' | '<' => true,
+            '_' => !(prev.is_some_and(char::is_alphanumeric)
+                && next.is_some_and(char::is_alphanumeric)),
+            '!' => next == Some('['),
+            '&' => ENTITY.is_match(&text[idx..]),
+            '#' | '>' | '-' | '+' | '=' => line_start,
+            '.' | ')' => {
+                let line = &out[out.rfind('\n').map_or(0, |n| n + 1)..];
+                ORDERED.is_match(&format!("{}{}", line, &text[..=idx]))
+                    && line.chars().chain(text[..idx].chars()).all(|c| c.is_ascii_digit())
+            }
+            _ => false,
+        };
+
+        if escaped {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+fn longest_run(s: &str, needle: char) -> usize {
+    s.split(|c| c != needle).map(str::len).max().unwrap_or(0)
+}
+
+fn prefix_lines(s: &str, prefix: &str, empty: &str) -> String {
+    s.lines()
+        .map(|line| {
+            if line.is_empty() {
+                empty.to_owned()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The offset reported by the parser before an event is the end of the
+/// previous one so any leading whitespace is skipped.
+fn start_offset(s: &str, offset: usize) -> usize {
+    let rest = &s[offset..];
+
+    offset + rest.len() - rest.trim_start().len()
+}
+
+fn link_from_tag(tag: Tag, offset: usize) -> Option<Link> {
+    let (construct, destination, title) = match tag {
+        Tag::Link(url, title) => (Construct::Link, url, title),
+        Tag::Image(url, title) => (Construct::Image, url, title),
+        _ => return None,
+    };
+
+    Some(Link {
+        construct,
+        destination: destination.into_owned(),
+        title: title.into_owned(),
+        offset,
+    })
+}
+
+fn sanitise_url(url: Cow<str>) -> Cow<str> {
+    let scheme = url.find(':')
+        .map(|i| &url[..i])
+        .filter(|s| !s.contains(&['/', '?', '#'][..]))
+        .map(|s| s.to_lowercase());
+
+    match scheme {
+        None => url,
+        Some(ref s) if s == "http" || s == "https" || s == "mailto" => url,
+        Some(_) => "".into(),
+    }
+}
+
+impl Diagnose for TextError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let (offset, expected) = match *self {
+            TextError::List(ref errors) if !errors.is_empty() => return errors[0].diagnose(input),
+            TextError::DisallowedHtml { offset, .. } | TextError::DisallowedInlineHtml { offset, .. } => {
+                (offset, "Markdown without HTML")
+            }
+            TextError::DisallowedConstruct { offset, .. } => (offset, "allowed Markdown construct"),
+            TextError::InvalidLink { offset, .. } => (offset, "link to an allowed URL"),
+            _ => (0, "Markdown text"),
+        };
+
+        Diagnostic::new(input, offset, expected, self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_nul() {
+        let text = Text::parse("foo\u{0}bar").unwrap();
+
+        assert_eq!(text.to_string(), "foo\u{FFFD}bar");
+    }
+
+    #[test]
+    fn disallows_outside_core() {
+        let actual = Text::parse("foo\n\n> bar");
+
+        assert_eq!(
+            format!("{:?}", actual),
+            "Err(List([DisallowedConstruct { construct: BlockQuote, offset: 5 }]))"
+        );
+    }
+
+    #[test]
+    fn disallows_html_with_permissive() {
+        let actual = Text::parse_with("foo <b>bar</b>", &Policy::permissive());
+
+        assert!(actual.is_err(), "Expected HTML to be disallowed");
+    }
+
+    #[test]
+    fn construct_from_str() {
+        assert_eq!("code-block".parse::<Construct>().ok(), Some(Construct::CodeBlock));
+        assert!("blink".parse::<Construct>().is_err());
+    }
+
+    fn normalise(s: &str) -> String {
+        Text::parse_with(s, &Policy::permissive())
+            .unwrap()
+            .normalise()
+            .to_string()
+    }
+
+    #[test]
+    fn normalise_inlines() {
+        assert_eq!(normalise("__a__ _b_ `c` [d](http://e.org 'f')  "), "**a** *b* `c` [d](http://e.org \"f\")");
+        assert_eq!(normalise("snake_case 2 * 3 _x"), "snake_case 2 \\* 3 \\_x");
+        assert_eq!(normalise("``a ` b``"), "``a ` b``");
+    }
+
+    #[test]
+    fn normalise_blocks() {
+        assert_eq!(normalise("* a\n* b\n- c\n\n+ d"), "- a\n- b\n\n* c\n\n- d");
+        assert_eq!(normalise("3) a\n4) b"), "3. a\n4. b");
+        assert_eq!(normalise("    code\n\n***\n> quote\nmore"), "```\ncode\n```\n\n***\n\n> quote\n> more");
+        assert_eq!(normalise("- a\n\n  b\n- c\n  - d"), "- a\n\n  b\n\n- c\n\n  - d");
+    }
+
+    #[test]
+    fn normalise_escapes() {
+        assert_eq!(normalise("\\# not a header"), "\\# not a header");
+        assert_eq!(normalise("1\\. not a list"), "1\\. not a list");
+        assert_eq!(normalise("&amp;copy; & co"), "\\&copy; & co");
+    }
+
+    #[test]
+    fn normalise_is_idempotent() {
+        let inputs = vec![
+            "# a *b*\n\n1. x\n2. y\n   * z\n\n> q\n>\n> - r",
+            "a  \nb\\\nc",
+            "![alt *x*](<a b> \"t\") \\* \\_ \\` \\[",
+        ];
+
+        for input in inputs {
+            let once = normalise(input);
+            assert_eq!(normalise(&once), once, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn html_sanitises_links() {
+        let policy = Policy::core().check_links(false);
+        let text = Text::parse_with("[a](javascript:alert(1)) [b](/foo) [c](https://example.org)", &policy).unwrap();
+
+        assert_eq!(
+            text.to_html(),
+            "<p><a href=\"\">a</a> <a href=\"/foo\">b</a> <a href=\"https://example.org\">c</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn allows_links_with_policy_schemes() {
+        let policy = Policy::core().schemes(SchemePolicy::default().allow("mailto"));
+
+        assert!(Text::parse_with("[a](mailto:a@example.org)", &policy).is_ok());
+        assert!(Text::parse("[a](mailto:a@example.org)").is_err());
+    }
+
+    #[test]
+    fn extracts_links() {
+        let text = Text::parse_with("[a](http://a.org)\n\n![b](b.png) <https://c.org>", &Policy::permissive()).unwrap();
+        let links = text.links();
+        let actual: Vec<(Construct, &str, usize)> = links
+            .iter()
+            .map(|l| (l.construct, l.destination.as_str(), l.offset))
+            .collect();
+
+        assert_eq!(
+            actual,
+            vec![
+                (Construct::Link, "http://a.org", 0),
+                (Construct::Image, "b.png", 19),
+                (Construct::Link, "https://c.org", 31),
+            ]
+        );
+    }
+
+    #[test]
+    fn disallows_invalid_links() {
+        let actual = Text::parse("[a](javascript:alert(1)) and [b](/foo)");
+
+        assert_eq!(
+            format!("{:?}", actual),
+            "Err(List([InvalidLink { destination: \"javascript:alert(1)\", offset: 0, cause: InvalidProtocol(\"javascript\") }, \
+             InvalidLink { destination: \"/foo\", offset: 29, cause: RelativeUrl }]))"
+        );
+    }
 }
@@ -6,7 +6,10 @@
 
 use regex::Regex;
 use std::fmt::{self, Debug, Display};
+use std::time::{SystemTime, UNIX_EPOCH};
 use super::Parse;
+use super::datetime;
+use super::diagnostic::{self, Diagnose, Diagnostic, Expected, Scanner};
 
 #[derive(Debug, Fail)]
 pub enum TimestampError {
@@ -18,7 +21,9 @@ pub enum TimestampError {
 /// constrained to UTC expressed as "Z".
 ///
 /// Note that "T" and "Z" must be capitalised.
-#[derive(Clone, PartialEq)]
+///
+/// The fixed width of the format makes the lexical order chronological.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Timestamp(String);
 
 impl Debug for Timestamp {
@@ -33,6 +38,48 @@ impl Display for Timestamp {
     }
 }
 
+impl Timestamp {
+    /// The current time to the second.
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Timestamp::from_unix(secs)
+    }
+
+    /// The timestamp for the given number of seconds since the Unix epoch.
+    ///
+    /// ```
+    /// use sac::value::timestamp::Timestamp;
+    /// assert_eq!(Timestamp::from_unix(1528358950).to_string(), "2018-06-07T08:09:10Z");
+    /// ```
+    pub fn from_unix(secs: u64) -> Self {
+        // Days to civil date as in http://howardhinnant.github.io/date_algorithms.html
+        let days = (secs / 86_400) as i64 + 719_468;
+        let era = days / 146_097;
+        let doe = days - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        let time = secs % 86_400;
+
+        Timestamp(format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60
+        ))
+    }
+}
+
 /// TODO: Only pattern checks are performed. An out of range timestamp will be
 /// accepted as a valid timestamp.
 impl Parse for Timestamp {
@@ -49,3 +96,44 @@ impl Parse for Timestamp {
         }
     }
 }
+
+impl Diagnose for TimestampError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let scan = scan(&mut Scanner::new(input));
+
+        diagnostic::locate(input, scan, "RFC3339 timestamp", self.to_string()).with_fix(diagnostic::first_valid(
+            input,
+            datetime::fixes(input),
+            |s| Timestamp::parse(s).is_ok(),
+        ))
+    }
+}
+
+fn scan(s: &mut Scanner) -> Result<(), Expected> {
+    s.digits(4, 4)?;
+    s.literal("-")?;
+    s.digits(2, 2)?;
+    s.literal("-")?;
+    s.digits(2, 2)?;
+    s.literal("T")?;
+    s.digits(2, 2)?;
+    s.literal(":")?;
+    s.digits(2, 2)?;
+    s.literal(":")?;
+    s.digits(2, 2)?;
+    s.literal("Z")?;
+    s.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_unix() {
+        assert_eq!(Timestamp::from_unix(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(Timestamp::from_unix(951_782_400).to_string(), "2000-02-29T00:00:00Z");
+        assert_eq!(Timestamp::from_unix(951_868_799).to_string(), "2000-02-29T23:59:59Z");
+        assert!(Timestamp::parse(&Timestamp::now().to_string()).is_ok());
+    }
+}
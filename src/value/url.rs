@@ -4,14 +4,16 @@
 // at your option. This file may not be copied, modified, or distributed except
 // according to those terms.
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug, Display};
 use url;
 use super::Parse;
+use super::diagnostic::{self, Diagnose, Diagnostic};
 
 #[derive(Debug, Fail)]
 pub enum UrlError {
-    #[fail(display = "Invalid protocol. Url must be either http or https")]
-    InvalidProtocol,
+    #[fail(display = "Invalid protocol {}. Url must use an allowed scheme (http or https by default)", _0)]
+    InvalidProtocol(String),
     #[fail(display = "Invalid port number")]
     InvalidPort,
     #[fail(display = "Invalid IPv4 address")]
@@ -28,15 +30,70 @@ pub enum UrlError {
     ParseError,
 }
 
-/// A Url resource
+/// The set of URL schemes allowed in a Url. The default policy allows http
+/// and https.
+///
+/// ```
+/// use sac::value::url::{SchemePolicy, Url};
+/// let policy = SchemePolicy::default().allow("mailto");
+/// assert!(Url::parse_with("mailto:foo@example.org", &policy).is_ok());
+/// assert!(Url::parse_with("urn:isbn:0451450523", &policy).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemePolicy {
+    allowed: BTreeSet<String>,
+}
+
+impl SchemePolicy {
+    pub fn web() -> Self {
+        SchemePolicy::none().allow("http").allow("https")
+    }
+
+    pub fn none() -> Self {
+        SchemePolicy {
+            allowed: BTreeSet::new(),
+        }
+    }
+
+    pub fn allow(mut self, scheme: &str) -> Self {
+        self.allowed.insert(scheme.to_lowercase());
+        self
+    }
+
+    pub fn deny(mut self, scheme: &str) -> Self {
+        self.allowed.remove(&scheme.to_lowercase());
+        self
+    }
+
+    pub fn allows(&self, scheme: &str) -> bool {
+        self.allowed.contains(&scheme.to_lowercase())
+    }
+}
+
+impl Default for SchemePolicy {
+    fn default() -> SchemePolicy {
+        SchemePolicy::web()
+    }
+}
+
+/// A Url resource normalised as defined by RFC 3986 section 6.2.2:
+///
+/// * Scheme and host are lowercased.
+/// * The default port for the scheme is removed.
+/// * Percent-encoded octets use uppercase hexadecimal digits and unreserved
+///   characters are decoded.
+/// * An empty path is `/` for http and https.
 ///
 /// ```
 /// use sac::value::Parse;
 /// use sac::value::url::Url;
 /// let url = Url::parse("https://example.org/foo:bar").unwrap();
 /// assert_eq!(url.to_string(), "https://example.org/foo:bar".to_string());
+///
+/// let url = Url::parse("HTTPS://Example.ORG:443/%7efoo%2f").unwrap();
+/// assert_eq!(url.to_string(), "https://example.org/~foo%2F".to_string());
 /// ```
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Url(url::Url);
 
 impl Debug for Url {
@@ -54,14 +111,79 @@ impl Display for Url {
 impl Parse for Url {
     type Err = UrlError;
     fn parse(s: &str) -> Result<Self, Self::Err> {
+        Url::parse_with(s, &SchemePolicy::default())
+    }
+}
+
+impl Url {
+    /// Parses and normalises a Url checking its scheme is allowed by the
+    /// given policy.
+    pub fn parse_with(s: &str, policy: &SchemePolicy) -> Result<Self, UrlError> {
         let u = url::Url::parse(s)?;
 
-        if s.starts_with("http://") || s.starts_with("https://") {
+        if !policy.allows(u.scheme()) {
+            return Err(UrlError::InvalidProtocol(u.scheme().to_owned()));
+        }
+
+        let normalised = normalise_percent_encoding(u.as_str());
+
+        if normalised == u.as_str() {
             Ok(Url(u))
         } else {
-            Err(UrlError::InvalidProtocol)
+            Ok(Url(url::Url::parse(&normalised)?))
         }
     }
+
+    pub fn scheme(&self) -> &str {
+        self.0.scheme()
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Uppercases the hexadecimal digits of percent-encoded octets and decodes
+/// the ones that represent unreserved characters.
+fn normalise_percent_encoding(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut buf = String::with_capacity(s.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let octet = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            hex_value(bytes[i + 1]).and_then(|hi| hex_value(bytes[i + 2]).map(|lo| hi * 16 + lo))
+        } else {
+            None
+        };
+
+        match octet {
+            Some(o) if is_unreserved(o) => {
+                buf.push(o as char);
+                i += 3;
+            }
+            Some(_) => {
+                buf.push('%');
+                buf.push_str(&s[i + 1..i + 3].to_uppercase());
+                i += 3;
+            }
+            None => {
+                let c = s[i..].chars().next().expect("char boundary");
+                buf.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+
+    buf
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'.' || b == b'_' || b == b'~'
 }
 
 impl From<url::ParseError> for UrlError {
@@ -79,6 +201,47 @@ impl From<url::ParseError> for UrlError {
     }
 }
 
+impl Diagnose for UrlError {
+    fn diagnose(&self, input: &str) -> Diagnostic {
+        let host = input.find("://").map_or(0, |i| i + 3);
+        let authority = &input[host..input[host..].find(&['/', '?', '#'][..]).map_or(input.len(), |i| host + i)];
+        let (offset, expected) = match *self {
+            UrlError::InvalidProtocol(_) => (0, "allowed scheme (e.g. https)"),
+            UrlError::RelativeUrl => (0, "scheme (e.g. https://)"),
+            UrlError::InvalidPort => (authority.rfind(':').map_or(host, |i| host + i + 1), "port number"),
+            UrlError::InvalidIpv4Address => (host, "IPv4 address"),
+            UrlError::InvalidIpv6Address => (host, "IPv6 address"),
+            UrlError::InvalidDomain => {
+                let start = authority.find('@').map_or(0, |i| i + 1);
+                let invalid = authority[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || "-.:[]".contains(c)))
+                    .map_or(start, |i| start + i);
+                (host + invalid, "domain name")
+            }
+            UrlError::Overflow => (0, "URL up to 4 GB"),
+            UrlError::ParseError => (input.find(|c: char| c.is_whitespace() || c.is_control()).unwrap_or(0), "URL character"),
+        };
+
+        Diagnostic::new(input, offset, expected, self.to_string())
+            .with_fix(diagnostic::first_valid(input, fixes(input), |s| Url::parse(s).is_ok()))
+    }
+}
+
+/// Candidate corrections: surrounding whitespace, unencoded spaces and a
+/// missing scheme.
+fn fixes(input: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut s = input.trim().replace(' ', "%20");
+    candidates.push(s.clone());
+
+    if !s.contains("://") {
+        s = format!("https://{}", s.trim_start_matches('/'));
+        candidates.push(s);
+    }
+
+    candidates
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,10 +264,42 @@ mod tests {
 
     #[test]
     fn fail_with_non_http() {
-        let expected = r#"Err(InvalidProtocol)"#.to_string();
+        let expected = r#"Err(InvalidProtocol("ftp"))"#.to_string();
         let actual = Url::parse("ftp://example.org");
 
         assert_eq!(format!("{:?}", actual), expected);
     }
 
+    #[test]
+    fn accepts_uppercase_scheme() {
+        let actual = Url::parse("HTTPS://Example.org");
+
+        assert_eq!(format!("{:?}", actual), r#"Ok("https://example.org/")"#);
+    }
+
+    #[test]
+    fn normalises_equivalent_urls() {
+        let expected = Url::parse("http://example.org/~a%2Fb?q=%C3%A9").unwrap();
+        let inputs = vec![
+            "http://EXAMPLE.org:80/%7Ea%2fb?q=%c3%a9",
+            "HTTP://example.org/%7ea%2Fb?q=%C3%A9",
+            "http://example.org:80/~a%2Fb?q=%c3%A9",
+        ];
+
+        for input in inputs {
+            assert_eq!(Url::parse(input).unwrap(), expected, "input {}", input);
+        }
+    }
+
+    #[test]
+    fn scheme_policy() {
+        let policy = SchemePolicy::none().allow("URN").allow("mailto");
+
+        assert_eq!(
+            Url::parse_with("urn:isbn:0451450523", &policy).map(|u| u.to_string()).ok(),
+            Some("urn:isbn:0451450523".to_string())
+        );
+        assert!(Url::parse_with("MAILTO:foo@example.org", &policy).is_ok());
+        assert!(Url::parse_with("https://example.org", &policy).is_err());
+    }
 }